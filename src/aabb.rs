@@ -0,0 +1,104 @@
+use crate::{interval::Interval, ray::Ray, vec3::Vec3};
+
+/// Axis-aligned bounding box
+#[derive(Debug, Clone, Copy)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub const fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+    /// A box that contains nothing, `union` with it is a no-op
+    pub const fn empty() -> Self {
+        Self {
+            min: Vec3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+            max: Vec3::new(-f64::INFINITY, -f64::INFINITY, -f64::INFINITY),
+        }
+    }
+    /// A box around all of space, for unbounded objects like planes
+    pub const fn everything() -> Self {
+        Self {
+            min: Vec3::new(-f64::INFINITY, -f64::INFINITY, -f64::INFINITY),
+            max: Vec3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+        }
+    }
+    /// The box spanning two corners given in any order
+    pub fn from_points(a: Vec3, b: Vec3) -> Self {
+        Self { min: a.min(b), max: a.max(b) }
+    }
+    pub fn union(&self, other: Aabb) -> Self {
+        Self { min: self.min.min(other.min), max: self.max.max(other.max) }
+    }
+    /// The overlap of both boxes, empty if they don't touch
+    pub fn intersection(&self, other: Aabb) -> Self {
+        Self { min: self.min.max(other.min), max: self.max.min(other.max) }
+    }
+    pub fn grow(&self, point: Vec3) -> Self {
+        Self { min: self.min.min(point), max: self.max.max(point) }
+    }
+    /// Expands any axis thinner than `delta` so flat primitives still have volume
+    pub fn pad(&self, delta: f64) -> Self {
+        let mut min = self.min;
+        let mut max = self.max;
+        if max.x - min.x < delta { min.x -= delta / 2.0; max.x += delta / 2.0; }
+        if max.y - min.y < delta { min.y -= delta / 2.0; max.y += delta / 2.0; }
+        if max.z - min.z < delta { min.z -= delta / 2.0; max.z += delta / 2.0; }
+        Self { min, max }
+    }
+    pub fn corners(&self) -> [Vec3; 8] {
+        let (a, b) = (self.min, self.max);
+        [
+            Vec3::new(a.x, a.y, a.z), Vec3::new(b.x, a.y, a.z), Vec3::new(a.x, b.y, a.z), Vec3::new(b.x, b.y, a.z),
+            Vec3::new(a.x, a.y, b.z), Vec3::new(b.x, a.y, b.z), Vec3::new(a.x, b.y, b.z), Vec3::new(b.x, b.y, b.z),
+        ]
+    }
+    pub fn centroid(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+    /// False for unbounded boxes, which a BVH can't split
+    pub fn is_finite(&self) -> bool {
+        self.min.x.is_finite() && self.min.y.is_finite() && self.min.z.is_finite()
+            && self.max.x.is_finite() && self.max.y.is_finite() && self.max.z.is_finite()
+    }
+    /// Whether `point` is inside the box or on its surface
+    pub fn contains(&self, point: Vec3) -> bool {
+        (self.min.x..=self.max.x).contains(&point.x) && (self.min.y..=self.max.y).contains(&point.y) && (self.min.z..=self.max.z).contains(&point.z)
+    }
+    pub fn surface_area(&self) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let d = self.max - self.min;
+        2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+    pub fn longest_axis(&self) -> usize {
+        let d = self.max - self.min;
+        if d.x > d.y && d.x > d.z {0} else if d.y > d.z {1} else {2}
+    }
+
+    /// Slab test, `inv_dir` is `1 / ray.direction` computed once per ray
+    pub fn hit(&self, ray: &Ray, inv_dir: Vec3, ray_t: Interval) -> bool {
+        let mut t_min = ray_t.min;
+        let mut t_max = ray_t.max;
+
+        for axis in 0..3 {
+            let t0 = (self.min[axis] - ray.origin[axis]) * inv_dir[axis];
+            let t1 = (self.max[axis] - ray.origin[axis]) * inv_dir[axis];
+            let (t0, t1) = if inv_dir[axis] < 0.0 {(t1, t0)} else {(t0, t1)};
+
+            // written so a NaN (0 * inf) never shrinks the interval
+            if t0 > t_min { t_min = t0; }
+            if t1 < t_max { t_max = t1; }
+            if t_max < t_min {
+                return false;
+            }
+        }
+        true
+    }
+}
@@ -0,0 +1,85 @@
+use std::{f64::consts::PI, path::Path, sync::Arc};
+
+use crate::{color::Color, hdr::{load_hdr, HdrError, HdrImage}, utils::degrees_to_radians, vec3::Vec3};
+
+/// What a ray sees when it escapes the scene
+#[derive(Debug, Clone)]
+pub enum Background {
+    Solid(Color),
+    /// Blends from `bottom` to `top` with the height of the ray direction
+    Gradient { bottom: Color, top: Color },
+    Environment(EnvironmentMap),
+}
+
+impl Default for Background {
+    /// The white to blue sky
+    fn default() -> Self {
+        Background::Gradient { bottom: Color::new(1.0, 1.0, 1.0), top: Color::new(0.5, 0.7, 1.0) }
+    }
+}
+
+impl Background {
+    pub fn color(&self, direction: Vec3) -> Color {
+        match self {
+            Background::Solid(color) => *color,
+            Background::Gradient { bottom, top } => {
+                let hight = (direction.unit().y + 1.0) * 0.5;
+                (*bottom * (1.0-hight)) + (*top * hight)
+            },
+            Background::Environment(map) => map.color(direction),
+        }
+    }
+}
+
+/// Equirectangular (latitude/longitude) environment map
+#[derive(Debug, Clone)]
+pub struct EnvironmentMap {
+    pub image: Arc<HdrImage>,
+    /// Rotation around the up axis in radians
+    pub rotation: f64,
+    pub intensity: f64,
+}
+
+impl EnvironmentMap {
+    /// Loads a Radiance `.hdr` file, `rotation` is in degrees around the up axis
+    pub fn load(path: impl AsRef<Path>, rotation: f64, intensity: f64) -> Result<Background, HdrError> {
+        let image = load_hdr(path)?;
+        Ok(Background::Environment(Self { image: Arc::new(image), rotation: degrees_to_radians(rotation), intensity }))
+    }
+
+    pub fn color(&self, direction: Vec3) -> Color {
+        let d = direction.unit();
+
+        // the center of the image looks down -z, the camera's default forward
+        let phi = d.x.atan2(-d.z) + self.rotation;
+        let theta = d.y.clamp(-1.0, 1.0).acos();
+
+        let u = (phi / (2.0 * PI) + 0.5).rem_euclid(1.0);
+        let v = theta / PI;
+
+        self.sample(u, v) * self.intensity
+    }
+
+    /// Bilinear lookup, wrapping horizontally and clamping at the poles
+    fn sample(&self, u: f64, v: f64) -> Color {
+        let width = self.image.width;
+        let height = self.image.height;
+
+        let x = u * width as f64 - 0.5;
+        let y = (v * height as f64 - 0.5).clamp(0.0, (height - 1) as f64);
+
+        let x0 = x.floor();
+        let y0 = y.floor();
+        let fx = x - x0;
+        let fy = y - y0;
+
+        let x0 = (x0 as isize).rem_euclid(width as isize) as usize;
+        let x1 = (x0 + 1) % width;
+        let y0 = y0 as usize;
+        let y1 = (y0 + 1).min(height - 1);
+
+        let top = self.image.get(x0, y0) * (1.0 - fx) + self.image.get(x1, y0) * fx;
+        let bottom = self.image.get(x0, y1) * (1.0 - fx) + self.image.get(x1, y1) * fx;
+        top * (1.0 - fy) + bottom * fy
+    }
+}
@@ -0,0 +1,297 @@
+use crate::{aabb::Aabb, hittable::{HitRecord, Hittable}, hittable_list::HittableList, interval::Interval, ray::Ray, vec3::Vec3};
+
+const BIN_COUNT: usize = 16;
+const MAX_LEAF_SIZE: usize = 4;
+/// Cost of visiting a node relative to intersecting one object
+const TRAVERSAL_COST: f64 = 0.125;
+/// Past this depth nodes are split at the median so traversal fits in its fixed size stack
+const MAX_SAH_DEPTH: usize = 32;
+const STACK_SIZE: usize = 64;
+
+#[derive(Debug, Clone, Copy)]
+struct BvhNode {
+    bbox: Aabb,
+    /// Leaf: index of the first object. Interior: index of the second child,
+    /// the first child is always stored right after its parent.
+    offset: usize,
+    /// Number of objects in a leaf, zero for interior nodes
+    count: usize,
+    /// Axis the node was split on, used to visit the nearer child first
+    axis: usize,
+}
+
+#[derive(Clone, Copy)]
+struct BuildObject {
+    index: usize,
+    bbox: Aabb,
+    centroid: Vec3,
+}
+
+/// Bounding volume hierarchy built with a binned surface area heuristic
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    objects: Vec<Hittable>,
+    /// Objects without a finite bounding box, like planes, tested before the tree
+    unbounded: Vec<Hittable>,
+}
+
+impl Bvh {
+    pub fn new(world: HittableList) -> Self {
+        let (objects, unbounded): (Vec<_>, Vec<_>) = world.into_objects().into_iter().partition(|object| object.bounding_box().is_finite());
+        let mut bvh = Self { nodes: Vec::with_capacity(objects.len() * 2), objects: Vec::with_capacity(objects.len()), unbounded };
+        if objects.is_empty() {
+            return bvh;
+        }
+
+        let mut build: Vec<BuildObject> = objects.iter().enumerate().map(|(index, object)| {
+            let bbox = object.bounding_box();
+            BuildObject { index, bbox, centroid: bbox.centroid() }
+        }).collect();
+
+        bvh.build(&mut build, 0, 0);
+
+        // store the objects in leaf order so each leaf is one contiguous slice
+        let mut objects: Vec<Option<Hittable>> = objects.into_iter().map(Some).collect();
+        bvh.objects = build.iter().map(|b| objects[b.index].take().unwrap()).collect();
+
+        bvh
+    }
+
+    pub fn objects(&self) -> impl Iterator<Item = &Hittable> {
+        self.objects.iter().chain(&self.unbounded)
+    }
+
+    pub fn bounding_box(&self) -> Aabb {
+        if !self.unbounded.is_empty() {
+            return Aabb::everything();
+        }
+        self.nodes.first().map_or(Aabb::empty(), |node| node.bbox)
+    }
+
+    /// Builds the subtree for `objects` (which start at `first` in the final order) and returns its node index
+    fn build(&mut self, objects: &mut [BuildObject], first: usize, depth: usize) -> usize {
+        let bbox = objects.iter().fold(Aabb::empty(), |b, o| b.union(o.bbox));
+        let node_index = self.nodes.len();
+        self.nodes.push(BvhNode { bbox, offset: first, count: objects.len(), axis: 0 });
+
+        if objects.len() == 1 {
+            return node_index;
+        }
+
+        let centroid_bounds = objects.iter().fold(Aabb::empty(), |b, o| b.grow(o.centroid));
+
+        let split = if depth < MAX_SAH_DEPTH {Self::sah_split(objects, bbox, centroid_bounds)} else {None};
+        let (axis, mid) = match split {
+            Some((axis, bin)) => (axis, partition(objects, |o| bin_index(o.centroid, centroid_bounds, axis) < bin)),
+            None if objects.len() <= MAX_LEAF_SIZE => return node_index,
+            None => {
+                let axis = centroid_bounds.longest_axis();
+                (axis, Self::median_split(objects, axis))
+            },
+        };
+
+        let (left, right) = objects.split_at_mut(mid);
+        self.build(left, first, depth + 1);
+        let second = self.build(right, first + mid, depth + 1);
+
+        self.nodes[node_index] = BvhNode { bbox, offset: second, count: 0, axis };
+        node_index
+    }
+
+    /// Returns the axis and bin to split at, or `None` if keeping a leaf is cheaper
+    fn sah_split(objects: &[BuildObject], bbox: Aabb, centroid_bounds: Aabb) -> Option<(usize, usize)> {
+        let leaf_cost = objects.len() as f64;
+        let parent_area = bbox.surface_area();
+        let mut best: Option<(f64, usize, usize)> = None;
+
+        for axis in 0..3 {
+            if centroid_bounds.max[axis] <= centroid_bounds.min[axis] {
+                continue;
+            }
+
+            let mut bins = [(Aabb::empty(), 0usize); BIN_COUNT];
+            for object in objects {
+                let bin = &mut bins[bin_index(object.centroid, centroid_bounds, axis)];
+                bin.0 = bin.0.union(object.bbox);
+                bin.1 += 1;
+            }
+
+            // sweep from the right to get the area and count of every right hand side
+            let mut right = [(0.0, 0usize); BIN_COUNT];
+            let mut right_box = Aabb::empty();
+            let mut right_count = 0;
+            for i in (1..BIN_COUNT).rev() {
+                right_box = right_box.union(bins[i].0);
+                right_count += bins[i].1;
+                right[i] = (right_box.surface_area(), right_count);
+            }
+
+            let mut left_box = Aabb::empty();
+            let mut left_count = 0;
+            for split in 1..BIN_COUNT {
+                left_box = left_box.union(bins[split - 1].0);
+                left_count += bins[split - 1].1;
+                let (right_area, right_count) = right[split];
+                if left_count == 0 || right_count == 0 {
+                    continue;
+                }
+
+                let cost = TRAVERSAL_COST + (left_box.surface_area() * left_count as f64 + right_area * right_count as f64) / parent_area;
+                if best.is_none_or(|(best_cost, _, _)| cost < best_cost) {
+                    best = Some((cost, axis, split));
+                }
+            }
+        }
+
+        match best {
+            Some((cost, axis, split)) if cost < leaf_cost || objects.len() > MAX_LEAF_SIZE => Some((axis, split)),
+            _ => None,
+        }
+    }
+
+    fn median_split(objects: &mut [BuildObject], axis: usize) -> usize {
+        let mid = objects.len() / 2;
+        objects.select_nth_unstable_by(mid, |a, b| a.centroid[axis].total_cmp(&b.centroid[axis]));
+        mid
+    }
+
+    pub fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let mut temp_rec = None::<HitRecord<'_>>;
+        let mut closest = ray_t.max;
+
+        for object in &self.unbounded {
+            if let Some(x) = object.hit(ray, Interval::new(ray_t.min, closest)) {
+                closest = x.t;
+                temp_rec = Some(x);
+            }
+        }
+        if self.nodes.is_empty() {
+            return temp_rec;
+        }
+
+        let inv_dir = Vec3::new(1.0 / ray.direction.x, 1.0 / ray.direction.y, 1.0 / ray.direction.z);
+        let dir_is_neg = [inv_dir.x < 0.0, inv_dir.y < 0.0, inv_dir.z < 0.0];
+
+        let mut stack = [0usize; STACK_SIZE];
+        let mut stack_len = 0;
+        let mut current = 0;
+
+        loop {
+            let node = &self.nodes[current];
+            let mut next = None;
+
+            if node.bbox.hit(ray, inv_dir, Interval::new(ray_t.min, closest)) {
+                if node.count > 0 {
+                    for object in &self.objects[node.offset..node.offset + node.count] {
+                        if let Some(x) = object.hit(ray, Interval::new(ray_t.min, closest)) {
+                            closest = x.t;
+                            temp_rec = Some(x);
+                        }
+                    }
+                } else {
+                    // visit the child nearer to the ray origin first
+                    let (near, far) = if dir_is_neg[node.axis] {(node.offset, current + 1)} else {(current + 1, node.offset)};
+                    stack[stack_len] = far;
+                    stack_len += 1;
+                    next = Some(near);
+                }
+            }
+
+            current = match next {
+                Some(n) => n,
+                None if stack_len == 0 => break,
+                None => {
+                    stack_len -= 1;
+                    stack[stack_len]
+                },
+            };
+        }
+
+        temp_rec
+    }
+}
+
+fn bin_index(centroid: Vec3, centroid_bounds: Aabb, axis: usize) -> usize {
+    let extent = centroid_bounds.max[axis] - centroid_bounds.min[axis];
+    let bin = ((centroid[axis] - centroid_bounds.min[axis]) / extent * BIN_COUNT as f64) as usize;
+    bin.min(BIN_COUNT - 1)
+}
+
+/// Moves every object matching `pred` to the front and returns how many there were
+fn partition(objects: &mut [BuildObject], pred: impl Fn(&BuildObject) -> bool) -> usize {
+    let mut mid = 0;
+    for i in 0..objects.len() {
+        if pred(&objects[i]) {
+            objects.swap(i, mid);
+            mid += 1;
+        }
+    }
+    mid
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::*;
+    use crate::{color::Color, material::Lambertian, plane::Plane, sphere::Sphere, utils::Pcg32};
+
+    fn random_world(rng: &mut Pcg32, count: usize) -> HittableList {
+        let mut world = HittableList::new();
+        for _ in 0..count {
+            let center = (Vec3::new(rng.gen(), rng.gen(), rng.gen()) - Vec3::new(0.5, 0.5, 0.5)) * 20.0;
+            world.add(Sphere::new(center, 0.05 + rng.gen::<f64>() * 0.5, Lambertian::new(Color::new(0.5, 0.5, 0.5))));
+        }
+        world
+    }
+
+    #[test]
+    fn matches_linear_list() {
+        let mut rng = Pcg32::new(7, 0);
+        let list = random_world(&mut rng, 500);
+        let mut copy = HittableList::new();
+        for object in list.objects() {
+            copy.add(object.clone());
+        }
+        let bvh = Bvh::new(copy);
+
+        let mut hits = 0;
+        for _ in 0..5000 {
+            let origin = (Vec3::new(rng.gen(), rng.gen(), rng.gen()) - Vec3::new(0.5, 0.5, 0.5)) * 30.0;
+            let ray = Ray::new(origin, Vec3::random_vector(&mut rng));
+            let ray_t = Interval::new(0.001, f64::INFINITY);
+
+            match (list.hit(&ray, ray_t), bvh.hit(&ray, ray_t)) {
+                (None, None) => {},
+                (Some(a), Some(b)) => {
+                    assert_eq!(a.t, b.t);
+                    assert_eq!(a.p.x, b.p.x);
+                    hits += 1;
+                },
+                (a, b) => panic!("list hit {:?} but bvh hit {:?}", a.map(|x| x.t), b.map(|x| x.t)),
+            }
+        }
+        assert!(hits > 0);
+    }
+
+    /// Planes sit outside the tree but still hide what's behind them
+    #[test]
+    fn unbounded_objects() {
+        let mut world = random_world(&mut Pcg32::new(3, 0), 50);
+        world.add(Plane::new(Vec3::new(0.0, -20.0, 0.0), Vec3::new(0.0, 1.0, 0.0), Lambertian::new(Color::new(0.5, 0.5, 0.5))));
+        let bvh = Bvh::new(world);
+        assert!(!bvh.bounding_box().is_finite());
+
+        let rec = bvh.hit(&Ray::new(Vec3::new(100.0, 0.0, 0.0), Vec3::new(0.0, -1.0, 0.0)), Interval::new(0.001, f64::INFINITY)).unwrap();
+        assert_eq!(rec.t, 20.0);
+        let rec = bvh.hit(&Ray::new(Vec3::new(0.0, -30.0, 0.0), Vec3::new(0.0, 1.0, 0.0)), Interval::new(0.001, f64::INFINITY)).unwrap();
+        assert_eq!(rec.t, 10.0);
+    }
+
+    #[test]
+    fn empty_world() {
+        let bvh = Bvh::new(HittableList::new());
+        let ray = Ray::new(Vec3::zero(), Vec3::new(0.0, 0.0, -1.0));
+        assert!(bvh.hit(&ray, Interval::new(0.001, f64::INFINITY)).is_none());
+    }
+}
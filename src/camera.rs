@@ -1,11 +1,53 @@
-use std::{fs::File, io::{BufWriter, Write}, sync::{mpsc, Arc, Mutex}, thread, usize};
+use std::{path::Path, sync::{atomic::{AtomicU64, Ordering}, Arc, Mutex}, time::{Duration, Instant}};
 
 use indicatif::ProgressBar;
 
-use crate::{color::Color, hittable_list::HittableList, interval::Interval, ray::Ray, utils::{degrees_to_radians, sample_square, ThreadPool}, vec3::{cross, Vec3}};
+use crate::{background::Background, bvh::Bvh, color::Color, hittable::HitRecord, hittable_list::HittableList, light::{is_hit_on, Light, LightSampler}, interval::Interval, medium::Atmosphere, output::{write_image, ImageFormat, OutputError, OutputSettings}, ray::Ray, sampler::{sample_unit_disk, Sampler, SamplerKind}, tonemap::{DisplayTransform, ToneMapper}, utils::{degrees_to_radians, ThreadPool}, vec3::{cross, Vec3}};
 
 
+/// Width and height of the square tiles handed to each worker
+const TILE_SIZE: usize = 16;
+
+/// Pixel range `[x0, x1) x [y0, y1)` rendered as one job
 #[derive(Debug, Clone, Copy)]
+struct Tile {
+    x0: usize,
+    y0: usize,
+    x1: usize,
+    y1: usize,
+}
+
+impl Tile {
+    fn width(&self) -> usize {
+        self.x1 - self.x0
+    }
+    fn hight(&self) -> usize {
+        self.y1 - self.y0
+    }
+}
+
+/// Veach's power heuristic with an exponent of two, the MIS weight of a
+/// sample drawn with density `pdf` when `other_pdf` could also have produced it
+fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    if a + b == 0.0 {0.0} else {a / (a + b)}
+}
+
+/// Summary of a finished render
+#[derive(Debug, Clone, Copy)]
+pub struct RenderStats {
+    pub elapsed: Duration,
+    /// Every ray traced against the world, camera rays and bounces alike
+    pub rays: u64,
+}
+
+impl RenderStats {
+    pub fn rays_per_second(&self) -> f64 {
+        self.rays as f64 / self.elapsed.as_secs_f64()
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Camera {
     image_width: usize,
     image_hight: usize,
@@ -14,12 +56,24 @@ pub struct Camera {
     thread_count: usize,
     max_depth: usize,
     samples_per_pixel: usize,
+    sampler: SamplerKind,
+    seed: u64,
 
     focal_length: f64,
     veiwport_hight: f64,
     veiwport_width: f64,
 
     depth_of_field_blur: f64,
+    /// Lens radius along the camera's right and up directions
+    lens_u: Vec3,
+    lens_v: Vec3,
+    shutter_open: f64,
+    shutter_close: f64,
+
+    background: Background,
+    atmosphere: Option<Atmosphere>,
+    output_settings: OutputSettings,
+    display: DisplayTransform,
 
     pixel00_loc: Vec3,
     pixel_delta_u: Vec3,
@@ -29,88 +83,236 @@ pub struct Camera {
 
 impl Camera {
 
-    pub fn render(self, world: HittableList) {
-        
-        let mut file_buf = BufWriter::new(File::create("image.ppm").expect("Could not create file"));
-        file_buf.write_all(format!("P3\n{} {}\n255\n", self.image_width, self.image_hight).as_bytes()).expect("Could not write to file");
-        
-        let pool = ThreadPool::new(self.thread_count);
-        let (sender, receiver) = mpsc::channel::<(Color, usize)>();
-        
-        let receiver = Mutex::new(receiver);
-        
-        let max_depth = self.max_depth;
-        let samples = self.samples_per_pixel;
+    /// Renders `world` and writes the image to `output`, the format is chosen by the file extension
+    pub fn render(self, world: HittableList, output: impl AsRef<Path>) -> Result<RenderStats, OutputError> {
+        let output = output.as_ref();
+        // fail before rendering rather than after
+        ImageFormat::from_path(output)?;
 
-        let world_arc = Arc::new(world);
-        
-        let mut image: Vec<Color> = vec![Color::new(0.0, 0.0, 0.0); self.image_hight*self.image_width];
-        let t = thread::spawn(move || {
-            for _ in 0..(samples * image.len()) {
-                let (c, pixel) = receiver.lock().unwrap().recv().unwrap();
-                
-                image[pixel] = image[pixel] + c;
-            }
+        let (width, hight, settings, display) = (self.image_width, self.image_hight, self.output_settings, self.display);
+        let (image, stats) = self.render_pixels(world);
 
-            for c in image {
-                (c / samples as f64).write(&mut file_buf);
-            }
-        });
+        write_image(output, width, hight, &image, settings, display)?;
+        Ok(stats)
+    }
 
+    /// Renders `world` into a row major buffer of linear colors, averaged over all samples.
+    ///
+    /// The image is cut into tiles which are handed to the thread pool. Each
+    /// worker renders every sample of its tile before writing the finished tile
+    /// into the shared framebuffer, so the lock is only taken once per tile.
+    pub fn render_pixels(self, world: HittableList) -> (Vec<Color>, RenderStats) {
+        let start = Instant::now();
+
+        let width = self.image_width;
+        let tiles = self.tiles();
+
+        let camera = Arc::new(self);
+        let lights = Arc::new(LightSampler::new(world.lights()));
+        let world = Arc::new(Bvh::new(world));
+        let framebuffer = Arc::new(Mutex::new(vec![Color::new(0.0, 0.0, 0.0); camera.image_hight*camera.image_width]));
+        let rays = Arc::new(AtomicU64::new(0));
+
+        let pb = ProgressBar::new(tiles.len() as u64);
+
+        let pool = ThreadPool::new(camera.thread_count);
+        for tile in tiles {
+            let camera = camera.clone();
+            let world = world.clone();
+            let lights = lights.clone();
+            let framebuffer = framebuffer.clone();
+            let rays = rays.clone();
+            let pb = pb.clone();
+
+            pool.execute(vec![move || {
+                let (pixels, tile_rays) = camera.render_tile(&world, &lights, tile);
+
+                let mut framebuffer = framebuffer.lock().unwrap();
+                for (row, colors) in pixels.chunks(tile.width()).enumerate() {
+                    let start = (tile.y0 + row) * width + tile.x0;
+                    framebuffer[start..start + colors.len()].copy_from_slice(colors);
+                }
+                drop(framebuffer);
 
-        let pb = ProgressBar::new(self.image_hight as u64);
-        
-        let mut pixel = 0;
-        for h in 0..self.image_hight {
-            for w in 0..self.image_width {
-                
-                let mut que = Vec::with_capacity(self.samples_per_pixel);
-                
-                for _ in 0..self.samples_per_pixel {
-                    let new_ray = self.get_ray(w as f64, h as f64).unit();
-                    let world_clone = world_arc.clone();
-                    let sender = sender.clone();
-                    que.push(move || {
-                        sender.send((Camera::ray_color(&new_ray, world_clone, max_depth), pixel)).unwrap();
-                    });
+                rays.fetch_add(tile_rays, Ordering::Relaxed);
+                pb.inc(1);
+            }]);
+        }
+        // waits for every worker to finish
+        drop(pool);
+
+        pb.finish_with_message("Done!");
+
+        let image = Arc::try_unwrap(framebuffer).unwrap().into_inner().unwrap();
+        let stats = RenderStats { elapsed: start.elapsed(), rays: rays.load(Ordering::Relaxed) };
+        (image, stats)
+    }
+
+    /// Splits the image into tiles, starting from the top left
+    fn tiles(&self) -> Vec<Tile> {
+        let mut tiles = Vec::new();
+        for y0 in (0..self.image_hight).step_by(TILE_SIZE) {
+            for x0 in (0..self.image_width).step_by(TILE_SIZE) {
+                tiles.push(Tile { x0, y0, x1: (x0 + TILE_SIZE).min(self.image_width), y1: (y0 + TILE_SIZE).min(self.image_hight) });
+            }
+        }
+        tiles
+    }
+
+    /// Renders every sample of every pixel in `tile`, returning the averaged
+    /// pixels row by row and the number of rays traced
+    fn render_tile(&self, world: &Bvh, lights: &LightSampler, tile: Tile) -> (Vec<Color>, u64) {
+        let mut pixels = Vec::with_capacity(tile.width() * tile.hight());
+        let mut rays = 0;
+
+        for h in tile.y0..tile.y1 {
+            for w in tile.x0..tile.x1 {
+                let mut sampler = Sampler::new(self.sampler, self.samples_per_pixel, self.seed, (h * self.image_width + w) as u64);
+                let mut color = Color::new(0.0, 0.0, 0.0);
+                for sample in 0..self.samples_per_pixel {
+                    sampler.start_sample(sample);
+                    let ray = self.get_ray(w as f64, h as f64, &mut sampler).unit();
+                    color = color + self.ray_color(ray, world, lights, &mut rays, &mut sampler);
                 }
-                pool.execute(que);
-                
-                pixel += 1;
-                
+                pixels.push(color / self.samples_per_pixel as f64);
             }
-            pb.inc(1);
         }
 
-        t.join().unwrap();
+        (pixels, rays)
+    }
 
-        pb.finish_with_message("Done!");
-        
+    pub fn image_width(&self) -> usize {
+        self.image_width
+    }
+    pub fn image_hight(&self) -> usize {
+        self.image_hight
     }
+    pub fn samples_per_pixel(&self) -> usize {
+        self.samples_per_pixel
+    }
+
+    fn get_ray(&self, w: f64, h: f64, sampler: &mut Sampler) -> Ray {
+        let (dx, dy) = sampler.get_2d();
+        let pixel_sample = self.pixel00_loc + (self.pixel_delta_u * (w + dx - 0.5)) + (self.pixel_delta_v * (h + dy - 0.5));
+
+        let (lens_x, lens_y) = sample_unit_disk(sampler.get_2d());
+        let blur = self.lens_u * lens_x + self.lens_v * lens_y;
+        let time = self.shutter_open + (self.shutter_close - self.shutter_open) * sampler.get_1d();
+
+        Ray::with_time(self.camera_center + blur, pixel_sample - self.camera_center - blur, time)
+    }
+
+    /// Path traces `ray`, `rays` is incremented for every ray traced.
+    ///
+    /// At every non-specular vertex one of `lights` is sampled directly and
+    /// the BSDF is sampled to continue the path. Both can find the same light,
+    /// so each contribution is weighted with the power heuristic.
+    fn ray_color(&self, mut ray: Ray, world: &Bvh, lights: &LightSampler, rays: &mut u64, sampler: &mut Sampler) -> Color {
+        let mut radiance = Color::new(0.0, 0.0, 0.0);
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        // camera rays and specular bounces can't be matched by light sampling
+        let mut specular_bounce = true;
+        let mut bsdf_pdf = 0.0;
+
+        for depth in 0..self.max_depth {
+            *rays += 1;
+            let surface = world.hit(&ray, Interval::new(0.001, f64::INFINITY));
+            // drawn whether or not there is an atmosphere to keep the dimensions the same
+            let fog_distance = sampler.get_1d();
+            let fog = self.atmosphere.as_ref()
+                .and_then(|atmosphere| atmosphere.interaction(&ray, surface.as_ref().map_or(f64::INFINITY, |rec| rec.t), fog_distance));
+            let Some(rec) = fog.or(surface) else {
+                radiance = radiance + throughput * self.background.color(ray.direction);
+                break;
+            };
+
+            let emitted = rec.mat.emitted(&rec);
+            if specular_bounce {
+                radiance = radiance + throughput * emitted;
+            } else if emitted.luminance() > 0.0 {
+                let light_pdf = lights.pdf(&ray, &rec);
+                radiance = radiance + throughput * emitted * power_heuristic(bsdf_pdf, light_pdf);
+            }
 
-    fn get_ray(&self, w: f64, h: f64) -> Ray {
-        let offset = sample_square();
-        let pixel_sample = self.pixel00_loc + (self.pixel_delta_u * (w + offset.x)) + (self.pixel_delta_v * (h + offset.y));
+            // drawn up front so the sampler dimensions line up whatever happens below
+            let light_choice = sampler.get_1d();
+            let light_uv = sampler.get_2d();
+            // the light sample would be the path's last segment, beyond max_depth
+            let last_vertex = depth + 1 == self.max_depth;
+            if let Some((light, choice_pdf)) = lights.pick(light_choice).filter(|_| !rec.mat.is_specular() && !last_vertex) {
+                radiance = radiance + throughput * self.direct_light(&ray, &rec, (light, choice_pdf), light_uv, world, lights, rays);
+            }
 
-        let blur = Vec3::random_vector() * self.depth_of_field_blur;
+            let Some(scatter) = rec.mat.scatter(&ray, &rec, sampler) else {
+                break;
+            };
+            if scatter.pdf <= 0.0 {
+                break;
+            }
+            throughput = throughput * scatter.attenuation / scatter.pdf;
+            specular_bounce = scatter.specular;
+            bsdf_pdf = scatter.pdf;
+            ray = Ray { time: ray.time, ..scatter.scattered };
+        }
 
-        Ray::new(self.camera_center + blur, pixel_sample - self.camera_center - blur)
+        radiance
     }
 
-    fn ray_color(ray: &Ray, world: Arc<HittableList>, depth: usize) -> Color {
-        if depth == 0 {return Color::new(0.0, 0.0, 0.0)}
+    /// Light reaching `rec` from `light`, picked with probability
+    /// `choice_pdf`, and leaving towards `ray`'s origin
+    #[allow(clippy::too_many_arguments)]
+    fn direct_light(&self, ray: &Ray, rec: &HitRecord, (light, choice_pdf): (&Light, f64), uv: (f64, f64), world: &Bvh, lights: &LightSampler, rays: &mut u64) -> Color {
+        let black = Color::new(0.0, 0.0, 0.0);
+
+        if let Light::Area(emitter) = light {
+            let direction = emitter.sample(rec.p, uv).unit();
+            let f = rec.mat.eval(ray, rec, direction);
+            if f.luminance() <= 0.0 {
+                return black;
+            }
+            *rays += 1;
+            let shadow = Ray::with_time(rec.p, direction, ray.time);
+            let Some(light_rec) = world.hit(&shadow, Interval::new(0.001, f64::INFINITY)) else {
+                return black;
+            };
+            // counted only if the emitter isn't hidden, by anything including other
+            // emitters, so the density is that of the emitters found where the ray stops
+            if !is_hit_on(emitter, &shadow, &light_rec) {
+                return black;
+            }
+            let light_pdf = lights.pdf(&shadow, &light_rec);
+            if light_pdf <= 0.0 {
+                return black;
+            }
+            let weight = power_heuristic(light_pdf, rec.mat.pdf(ray, rec, direction));
+            let transmittance = self.transmittance(&shadow, light_rec.t);
+            return f * light_rec.mat.emitted(&light_rec) * (weight * transmittance / light_pdf);
+        }
 
-        if let Some(x) = world.hit(ray, Interval::new(0.001, f64::INFINITY)) {
-            let mat = x.mat.scatter(ray, &x);
-            return Self::ray_color(&mat.scattered, world, depth-1) * mat.attenuation;
+        // punctual lights can't be hit by BSDF samples, so no MIS
+        let Some(sample) = light.sample(rec.p, uv) else {
+            return black;
+        };
+        let f = rec.mat.eval(ray, rec, sample.direction);
+        if f.luminance() <= 0.0 {
+            return black;
+        }
+        *rays += 1;
+        let shadow = Ray::with_time(rec.p, sample.direction, ray.time);
+        if world.hit(&shadow, Interval::new(0.001, sample.distance * (1.0 - 1e-9))).is_some() {
+            return black;
         }
-        let hight = (ray.direction.unit().y + 1.0) * 0.5;
-        (Color::new(1.0, 1.0, 1.0) * (1.0-hight)) + (Color::new(0.5, 0.7, 1.0) * hight)
+        f * sample.radiance * (self.transmittance(&shadow, sample.distance) / choice_pdf)
+    }
+
+    /// Fraction of light the atmosphere lets through along `ray` up to `t_max`
+    fn transmittance(&self, ray: &Ray, t_max: f64) -> f64 {
+        self.atmosphere.as_ref().map_or(1.0, |atmosphere| atmosphere.transmittance(ray, t_max))
     }
 }
 
 
-#[derive(Default, Clone, Copy)]
+#[derive(Default, Clone)]
 pub struct CameraBuilder {
     pub image_width: usize,
     pub image_hight: usize,
@@ -119,13 +321,28 @@ pub struct CameraBuilder {
     pub camera_center: Vec3,
     pub look_at: Vec3,
     pub depth_of_field_blur: f64,
+    /// Times within the frame, from 0 to 1, between which the shutter is open.
+    /// Equal times render without motion blur.
+    pub shutter_open: f64,
+    pub shutter_close: f64,
+    pub background: Background,
+    /// Fog filling the scene, `None` for clear air
+    pub atmosphere: Option<Atmosphere>,
+    pub output_settings: OutputSettings,
+    /// Exposure adjustment in stops for LDR outputs
+    pub exposure: f64,
+    pub tone_mapper: ToneMapper,
 
     pub thread_count: usize,
     pub max_depth: usize,
     pub samples_per_pixel: usize,
+    pub sampler: SamplerKind,
+    /// Seed for the random number generators
+    pub seed: u64,
 }
 
 impl CameraBuilder {
+    #[allow(clippy::wrong_self_convention)]
     pub fn to_camera(self) -> Camera {
         if self.thread_count == 0 {
             panic!("`thread_count` cannot be zero");
@@ -136,6 +353,9 @@ impl CameraBuilder {
         if self.samples_per_pixel == 0 {
             panic!("`samples_per_pixel` cannot be zero");
         }
+        if !(0.0 <= self.shutter_open && self.shutter_open <= self.shutter_close && self.shutter_close <= 1.0) {
+            panic!("the shutter has to open and close within the frame, 0 <= `shutter_open` <= `shutter_close` <= 1");
+        }
 
         let zero_aspect_ratio = self.aspect_ratio == 0.0;
 
@@ -183,6 +403,154 @@ impl CameraBuilder {
         let veiwport_upper_left = self.camera_center - (w * focal_length) - (viewport_u/2.0) - (viewport_v/2.0); // top left of the screen
         let pixel00_loc = veiwport_upper_left + ((pixel_delta_u+pixel_delta_v) * 0.5); // top left pixel
 
-        Camera { image_width, image_hight, aspect_ratio, thread_count: self.thread_count, max_depth: self.max_depth, samples_per_pixel: self.samples_per_pixel, pixel00_loc, pixel_delta_u, pixel_delta_v, camera_center: self.camera_center, focal_length, veiwport_hight, veiwport_width, depth_of_field_blur: self.depth_of_field_blur }
+        Camera { image_width, image_hight, aspect_ratio, thread_count: self.thread_count, max_depth: self.max_depth, samples_per_pixel: self.samples_per_pixel, sampler: self.sampler, seed: self.seed, pixel00_loc, pixel_delta_u, pixel_delta_v, camera_center: self.camera_center, focal_length, veiwport_hight, veiwport_width, depth_of_field_blur: self.depth_of_field_blur, lens_u: u * self.depth_of_field_blur, lens_v: v * self.depth_of_field_blur, shutter_open: self.shutter_open, shutter_close: self.shutter_close, background: self.background, atmosphere: self.atmosphere, output_settings: self.output_settings, display: DisplayTransform { exposure: self.exposure, tone_mapper: self.tone_mapper } }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc, thread};
+
+    use super::*;
+    use crate::{material::{DiffuseLight, Lambertian}, quad::Quad, scenes, sphere::Sphere};
+
+    /// The renderer before tiles: one boxed closure per sample, each sending
+    /// its color through a channel to a single accumulating thread
+    fn render_per_sample(camera: Camera, world: HittableList) -> Vec<Color> {
+        let pool = ThreadPool::new(camera.thread_count);
+        let (sender, receiver) = mpsc::channel::<(Color, usize)>();
+        let receiver = Mutex::new(receiver);
+
+        let samples = camera.samples_per_pixel;
+        let camera = Arc::new(camera);
+        let lights = Arc::new(LightSampler::new(world.lights()));
+        let world = Arc::new(Bvh::new(world));
+
+        let mut image = vec![Color::new(0.0, 0.0, 0.0); camera.image_hight*camera.image_width];
+        let t = thread::spawn(move || {
+            for _ in 0..(samples * image.len()) {
+                let (c, pixel) = receiver.lock().unwrap().recv().unwrap();
+                image[pixel] = image[pixel] + c;
+            }
+            image.into_iter().map(|c| c / samples as f64).collect::<Vec<Color>>()
+        });
+
+        let mut pixel = 0;
+        for h in 0..camera.image_hight {
+            for w in 0..camera.image_width {
+                let mut que = Vec::with_capacity(samples);
+                for sample in 0..samples {
+                    let mut sampler = Sampler::new(camera.sampler, samples, camera.seed, pixel as u64);
+                    sampler.start_sample(sample);
+                    let ray = camera.get_ray(w as f64, h as f64, &mut sampler).unit();
+                    let camera = camera.clone();
+                    let world = world.clone();
+                    let lights = lights.clone();
+                    let sender = sender.clone();
+                    que.push(move || {
+                        let mut rays = 0;
+                        sender.send((camera.ray_color(ray, &world, &lights, &mut rays, &mut sampler), pixel)).unwrap();
+                    });
+                }
+                pool.execute(que);
+                pixel += 1;
+            }
+        }
+
+        t.join().unwrap()
+    }
+
+    fn bench_camera(samples_per_pixel: usize) -> (Camera, HittableList) {
+        let (mut camera, world) = scenes::spheres();
+        camera.image_width = 200;
+        camera.samples_per_pixel = samples_per_pixel;
+        camera.thread_count = thread::available_parallelism().map_or(1, |n| n.get());
+        (camera.to_camera(), world)
     }
-}
\ No newline at end of file
+
+    fn small_camera(thread_count: usize, seed: u64) -> (Camera, HittableList) {
+        let (mut camera, world) = scenes::spheres();
+        camera.image_width = 48;
+        camera.samples_per_pixel = 4;
+        camera.thread_count = thread_count;
+        camera.seed = seed;
+        (camera.to_camera(), world)
+    }
+
+    #[test]
+    fn thread_count_does_not_change_image() {
+        let (camera, world) = small_camera(1, 42);
+        let (single, _) = camera.render_pixels(world);
+        let (camera, world) = small_camera(4, 42);
+        let (multi, _) = camera.render_pixels(world);
+        assert!(single.iter().zip(&multi).all(|(a, b)| a.r.to_bits() == b.r.to_bits() && a.g.to_bits() == b.g.to_bits() && a.b.to_bits() == b.b.to_bits()));
+    }
+
+    #[test]
+    fn seed_changes_image() {
+        let (camera, world) = small_camera(2, 1);
+        let (first, _) = camera.render_pixels(world);
+        let (camera, world) = small_camera(2, 2);
+        let (second, _) = camera.render_pixels(world);
+        assert!(first.iter().zip(&second).any(|(a, b)| a.r != b.r || a.g != b.g || a.b != b.b));
+    }
+
+    /// Light sampling with MIS and BSDF sampling alone converge to the same
+    /// floor lit by a small light, light sampling with far less noise
+    #[test]
+    fn light_sampling_lowers_variance() {
+        let mut world = HittableList::new();
+        world.add(Quad::new(Vec3::new(-5.0, 0.0, 5.0), Vec3::new(10.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -10.0), Lambertian::new(Color::new(0.5, 0.5, 0.5))));
+        world.add(Sphere::new(Vec3::new(0.0, 0.5, 0.0), 0.05, DiffuseLight::new(Color::new(100.0, 100.0, 100.0))));
+        let camera = CameraBuilder {
+            image_width: 1, aspect_ratio: 1.0, vfov: 90.0, camera_center: Vec3::new(0.0, 1.0, 3.0),
+            background: Background::Solid(Color::new(0.0, 0.0, 0.0)), thread_count: 1, max_depth: 2, samples_per_pixel: 1,
+            ..Default::default()
+        }.to_camera();
+        let with_lights = LightSampler::new(world.lights());
+        let world = Bvh::new(world);
+
+        let samples = 1 << 16;
+        let moments = |lights: &LightSampler| {
+            let mut sampler = Sampler::new(SamplerKind::Independent, samples, 7, 0);
+            let (mut sum, mut squares) = (0.0, 0.0);
+            for i in 0..samples {
+                sampler.start_sample(i);
+                let c = camera.ray_color(Ray::new(camera.camera_center, -camera.camera_center), &world, lights, &mut 0, &mut sampler).luminance();
+                sum += c;
+                squares += c * c;
+            }
+            let mean = sum / samples as f64;
+            (mean, squares / samples as f64 - mean * mean)
+        };
+        let (mis_mean, mis_variance) = moments(&with_lights);
+        let (bsdf_mean, bsdf_variance) = moments(&LightSampler::new(&[]));
+
+        // the light covers a small cone almost straight above the floor, albedo / pi * radiance * pi * (0.05 / 0.5)^2
+        assert!((mis_mean - 0.5).abs() < 0.01, "{mis_mean}");
+        // BSDF sampling finds the light once in a hundred paths, its standard error is about 0.02
+        assert!((bsdf_mean - mis_mean).abs() < 0.08, "{bsdf_mean} {mis_mean}");
+        assert!(mis_variance * 100.0 < bsdf_variance, "{mis_variance} {bsdf_variance}");
+    }
+
+    /// Run with `cargo test --release -- --ignored --nocapture tile_speedup`
+    #[test]
+    #[ignore]
+    fn tile_speedup() {
+        for spp in [16, 64, 256] {
+            let (camera, world) = bench_camera(spp);
+            let start = Instant::now();
+            render_per_sample(camera, world);
+            let per_sample = start.elapsed();
+
+            let (camera, world) = bench_camera(spp);
+            let start = Instant::now();
+            camera.render_pixels(world);
+            let tiled = start.elapsed();
+
+            println!(
+                "{spp:>4} spp: per sample {:>9.2?}, tiled {:>9.2?}, {:.2}x speedup",
+                per_sample, tiled, per_sample.as_secs_f64() / tiled.as_secs_f64(),
+            );
+        }
+    }
+}
@@ -0,0 +1,360 @@
+use std::{path::{Path, PathBuf}, str::FromStr, thread};
+
+use crate::{background::{Background, EnvironmentMap}, camera::CameraBuilder, color::Color, output::{BitDepth, ExrPixelType}, sampler::SamplerKind, scenes::BUILTIN_SCENES, tonemap::ToneMapper, vec3::{cross, Vec3}};
+
+pub const USAGE: &str = "\
+Usage: renderer [SCENE] [OPTIONS]
+
+SCENE is a .toml scene file or the name of a built-in scene (spheres, cornell,
+materials).
+Without one the `spheres` scene is rendered.
+
+Options:
+  -o, --output <FILE>             Image to write, format from the extension
+                                  (.png .ppm .pfm .hdr .exr) [default: image.png]
+      --spp <N>                   Samples per pixel
+      --max-depth <N>             Maximum number of bounces
+  -j, --threads <N>               Worker threads [default: available cores]
+      --width <N>                 Image width, keeps the aspect ratio unless --height is given
+      --height <N>                Image height, keeps the aspect ratio unless --width is given
+      --aspect-ratio <R>          Width divided by height
+      --vfov <DEGREES>            Vertical field of view
+      --camera-center <X,Y,Z>     Camera position
+      --look-at <X,Y,Z>           Point the camera looks at
+      --depth-of-field-blur <R>   Lens radius, 0 for a pinhole camera
+      --shutter <OPEN,CLOSE>      Times within the frame from 0 to 1 the shutter is open,
+                                  equal ones turn motion blur off
+      --seed <N>                  Seed for the random number generators
+      --sampler <NAME>            independent, stratified, halton or sobol [default: sobol]
+      --background <BG>           `sky`, a color `R,G,B`, or an equirectangular .hdr file
+      --environment-rotation <DEGREES>
+      --environment-intensity <X>
+      --exposure <STOPS>          Exposure adjustment for .png and .ppm output
+      --tone-mapper <NAME>        clamp, reinhard, extended_reinhard:<WHITE>, aces, hable or agx
+      --bit-depth <8|16>          Bits per channel for .png and .ppm output
+      --ascii-ppm                 Write .ppm files as plain text P3
+      --exr-pixel-type <TYPE>     half or float
+  -h, --help                      Print this message
+";
+
+/// Where the scene comes from
+#[derive(Debug, Clone, PartialEq)]
+pub enum SceneSource {
+    Builtin(String),
+    File(PathBuf),
+}
+
+/// Parsed command line, every override is `None` unless given
+#[derive(Debug, Default)]
+pub struct Args {
+    pub scene: Option<SceneSource>,
+    pub output: Option<PathBuf>,
+    pub help: bool,
+
+    pub samples_per_pixel: Option<usize>,
+    pub max_depth: Option<usize>,
+    pub thread_count: Option<usize>,
+    pub image_width: Option<usize>,
+    pub image_hight: Option<usize>,
+    pub aspect_ratio: Option<f64>,
+    pub vfov: Option<f64>,
+    pub camera_center: Option<Vec3>,
+    pub look_at: Option<Vec3>,
+    pub depth_of_field_blur: Option<f64>,
+    pub shutter: Option<(f64, f64)>,
+    pub seed: Option<u64>,
+    pub sampler: Option<SamplerKind>,
+    pub background: Option<String>,
+    pub environment_rotation: Option<f64>,
+    pub environment_intensity: Option<f64>,
+    pub exposure: Option<f64>,
+    pub tone_mapper: Option<ToneMapper>,
+    pub bit_depth: Option<BitDepth>,
+    pub ascii_ppm: bool,
+    pub exr_pixel_type: Option<ExrPixelType>,
+}
+
+impl Args {
+    /// Parses the arguments after the program name
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut parsed = Args::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            if !arg.starts_with('-') {
+                if parsed.scene.is_some() {
+                    return Err(format!("unexpected argument `{arg}`, only one scene can be given"));
+                }
+                parsed.scene = Some(scene_source(&arg)?);
+                continue;
+            }
+
+            // both `--flag value` and `--flag=value` are accepted
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+                None => (arg.clone(), None),
+            };
+            let mut value = || inline.clone().or_else(|| args.next()).ok_or_else(|| format!("`{flag}` needs a value"));
+            // `--ascii-ppm=false` would otherwise read as turning it on
+            let switch = || if inline.is_some() {Err(format!("`{flag}` does not take a value"))} else {Ok(true)};
+
+            match flag.as_str() {
+                "-h" | "--help" => parsed.help = switch()?,
+                "-o" | "--output" => parsed.output = Some(PathBuf::from(value()?)),
+                "--spp" => parsed.samples_per_pixel = Some(positive(&flag, &value()?)?),
+                "--max-depth" => parsed.max_depth = Some(positive(&flag, &value()?)?),
+                "-j" | "--threads" => parsed.thread_count = Some(positive(&flag, &value()?)?),
+                "--width" => parsed.image_width = Some(positive(&flag, &value()?)?),
+                "--height" => parsed.image_hight = Some(positive(&flag, &value()?)?),
+                "--aspect-ratio" => parsed.aspect_ratio = Some(positive(&flag, &value()?)?),
+                "--vfov" => {
+                    let vfov: f64 = number(&flag, &value()?)?;
+                    if !(vfov > 0.0 && vfov < 180.0) {
+                        return Err(format!("`{flag}` must be between 0 and 180 degrees"));
+                    }
+                    parsed.vfov = Some(vfov);
+                },
+                "--camera-center" => parsed.camera_center = Some(vector(&flag, &value()?)?),
+                "--look-at" => parsed.look_at = Some(vector(&flag, &value()?)?),
+                "--depth-of-field-blur" => parsed.depth_of_field_blur = Some(non_negative(&flag, &value()?)?),
+                "--shutter" => parsed.shutter = Some(shutter(&flag, &value()?)?),
+                "--seed" => parsed.seed = Some(number(&flag, &value()?)?),
+                "--sampler" => {
+                    let name = value()?;
+                    parsed.sampler = Some(SamplerKind::from_name(&name)
+                        .ok_or_else(|| format!("`{flag}` must be one of {}, not `{name}`", SamplerKind::NAMES.join(", ")))?);
+                },
+                "--background" => parsed.background = Some(value()?),
+                "--environment-rotation" => parsed.environment_rotation = Some(number(&flag, &value()?)?),
+                "--environment-intensity" => parsed.environment_intensity = Some(non_negative(&flag, &value()?)?),
+                "--exposure" => parsed.exposure = Some(number(&flag, &value()?)?),
+                "--tone-mapper" => parsed.tone_mapper = Some(tone_mapper(&value()?)?),
+                "--bit-depth" => parsed.bit_depth = Some(match value()?.as_str() {
+                    "8" => BitDepth::Eight,
+                    "16" => BitDepth::Sixteen,
+                    other => return Err(format!("`{flag}` must be 8 or 16, not `{other}`")),
+                }),
+                "--ascii-ppm" => parsed.ascii_ppm = switch()?,
+                "--exr-pixel-type" => parsed.exr_pixel_type = Some(match value()?.as_str() {
+                    "half" => ExrPixelType::Half,
+                    "float" => ExrPixelType::Float,
+                    other => return Err(format!("`{flag}` must be half or float, not `{other}`")),
+                }),
+                _ => return Err(format!("unknown option `{flag}`")),
+            }
+        }
+
+        if parsed.image_width.is_some() && parsed.image_hight.is_some() && parsed.aspect_ratio.is_some() {
+            return Err(String::from("only two of `--width`, `--height` and `--aspect-ratio` can be given"));
+        }
+        if parsed.background.is_none() && (parsed.environment_rotation.is_some() || parsed.environment_intensity.is_some()) {
+            return Err(String::from("`--environment-rotation` and `--environment-intensity` need `--background <FILE.hdr>`"));
+        }
+
+        Ok(parsed)
+    }
+
+    /// Overrides the fields of `camera` that were given on the command line
+    pub fn apply(&self, camera: &mut CameraBuilder) -> Result<(), String> {
+        if let Some(n) = self.samples_per_pixel { camera.samples_per_pixel = n; }
+        if let Some(n) = self.max_depth { camera.max_depth = n; }
+        if let Some(n) = self.thread_count { camera.thread_count = n; }
+        if let Some(v) = self.vfov { camera.vfov = v; }
+        if let Some(v) = self.camera_center { camera.camera_center = v; }
+        if let Some(v) = self.look_at { camera.look_at = v; }
+        if let Some(v) = self.depth_of_field_blur { camera.depth_of_field_blur = v; }
+        if let Some((open, close)) = self.shutter {
+            camera.shutter_open = open;
+            camera.shutter_close = close;
+        }
+        if let Some(v) = self.seed { camera.seed = v; }
+        if let Some(v) = self.sampler { camera.sampler = v; }
+        if let Some(v) = self.exposure { camera.exposure = v; }
+        if let Some(v) = self.tone_mapper { camera.tone_mapper = v; }
+        if let Some(v) = self.bit_depth { camera.output_settings.bit_depth = v; }
+        if let Some(v) = self.exr_pixel_type { camera.output_settings.exr_pixel_type = v; }
+        if self.ascii_ppm { camera.output_settings.ascii_ppm = true; }
+
+        if self.camera_center.is_some() || self.look_at.is_some() {
+            let forward = camera.look_at - camera.camera_center;
+            if forward.near_zero() {
+                return Err(String::from("`--look-at` must be different from the camera center"));
+            }
+            if cross(Vec3::new(0.0, 1.0, 0.0), forward.unit()).near_zero() {
+                return Err(String::from("`--look-at` must not be straight above or below the camera center"));
+            }
+        }
+
+        if camera.thread_count == 0 {
+            camera.thread_count = thread::available_parallelism().map_or(1, |n| n.get());
+        }
+
+        // size overrides keep whatever the scene did not get overridden
+        let current_aspect = if camera.aspect_ratio != 0.0 {
+            camera.aspect_ratio
+        } else {
+            camera.image_width as f64 / camera.image_hight as f64
+        };
+        match (self.image_width, self.image_hight, self.aspect_ratio) {
+            (Some(w), Some(h), _) => {
+                camera.image_width = w;
+                camera.image_hight = h;
+                camera.aspect_ratio = 0.0;
+            },
+            (Some(w), None, aspect) => {
+                camera.image_width = w;
+                camera.image_hight = 0;
+                camera.aspect_ratio = aspect.unwrap_or(current_aspect);
+            },
+            (None, Some(h), aspect) => {
+                camera.image_width = 0;
+                camera.image_hight = h;
+                camera.aspect_ratio = aspect.unwrap_or(current_aspect);
+            },
+            (None, None, Some(aspect)) => {
+                // keep the width if there is one, otherwise the height
+                if camera.image_width != 0 {
+                    camera.image_hight = 0;
+                }
+                camera.aspect_ratio = aspect;
+            },
+            (None, None, None) => {},
+        }
+
+        if let Some(background) = &self.background {
+            camera.background = self.background(background)?;
+        }
+
+        Ok(())
+    }
+
+    fn background(&self, value: &str) -> Result<Background, String> {
+        if value == "sky" {
+            return Ok(Background::default());
+        }
+        if Path::new(value).extension().is_some_and(|e| e.eq_ignore_ascii_case("hdr")) {
+            let rotation = self.environment_rotation.unwrap_or(0.0);
+            let intensity = self.environment_intensity.unwrap_or(1.0);
+            return EnvironmentMap::load(value, rotation, intensity).map_err(|e| e.to_string());
+        }
+        let c = vector("--background", value)
+            .map_err(|_| format!("`--background` must be `sky`, a color R,G,B or a .hdr file, not `{value}`"))?;
+        Ok(Background::Solid(Color::new(c.x, c.y, c.z)))
+    }
+}
+
+fn scene_source(arg: &str) -> Result<SceneSource, String> {
+    if BUILTIN_SCENES.contains(&arg) {
+        return Ok(SceneSource::Builtin(arg.to_string()));
+    }
+    if Path::new(arg).extension().is_some_and(|e| e == "toml") || Path::new(arg).exists() {
+        return Ok(SceneSource::File(PathBuf::from(arg)));
+    }
+    Err(format!("`{arg}` is neither a scene file nor a built-in scene ({})", BUILTIN_SCENES.join(", ")))
+}
+
+fn number<T: FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("`{flag}` expects a number, not `{value}`"))
+}
+
+fn positive<T: FromStr + PartialOrd + Default>(flag: &str, value: &str) -> Result<T, String> {
+    let n: T = number(flag, value)?;
+    if n > T::default() {Ok(n)} else {Err(format!("`{flag}` must be greater than zero"))}
+}
+
+fn non_negative(flag: &str, value: &str) -> Result<f64, String> {
+    let n: f64 = number(flag, value)?;
+    if n >= 0.0 {Ok(n)} else {Err(format!("`{flag}` must not be negative"))}
+}
+
+fn vector(flag: &str, value: &str) -> Result<Vec3, String> {
+    let parts: Vec<&str> = value.split(',').map(str::trim).collect();
+    let [x, y, z] = parts[..] else {
+        return Err(format!("`{flag}` expects three comma separated numbers, not `{value}`"));
+    };
+    Ok(Vec3::new(number(flag, x)?, number(flag, y)?, number(flag, z)?))
+}
+
+fn shutter(flag: &str, value: &str) -> Result<(f64, f64), String> {
+    let Some((open, close)) = value.split_once(',') else {
+        return Err(format!("`{flag}` expects two comma separated times, not `{value}`"));
+    };
+    let (open, close): (f64, f64) = (number(flag, open.trim())?, number(flag, close.trim())?);
+    if !(0.0 <= open && open <= close && close <= 1.0) {
+        return Err(format!("`{flag}` needs 0 <= OPEN <= CLOSE <= 1, not `{value}`"));
+    }
+    Ok((open, close))
+}
+
+fn tone_mapper(value: &str) -> Result<ToneMapper, String> {
+    match value.split_once(':') {
+        Some(("extended_reinhard", white)) => {
+            let white = positive("--tone-mapper", white)?;
+            Ok(ToneMapper::ExtendedReinhard { white })
+        },
+        Some(_) => Err(format!("unknown tone mapper `{value}`")),
+        None => match value {
+            "clamp" => Ok(ToneMapper::Clamp),
+            "reinhard" => Ok(ToneMapper::Reinhard),
+            "extended_reinhard" => Err(String::from("extended_reinhard needs a white point, e.g. extended_reinhard:4.0")),
+            "aces" => Ok(ToneMapper::Aces),
+            "hable" => Ok(ToneMapper::Hable),
+            "agx" => Ok(ToneMapper::Agx),
+            _ => Err(format!("unknown tone mapper `{value}`")),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Args, String> {
+        Args::parse(args.split_whitespace().map(String::from))
+    }
+
+    /// Values can follow their flag as the next argument or after `=`
+    #[test]
+    fn values() {
+        for args in ["--spp 16 -o out.exr --camera-center 1,2,3 --seed 7", "--spp=16 --output=out.exr --camera-center=1,2,3 --seed=7"] {
+            let parsed = parse(args).unwrap();
+            assert_eq!(parsed.samples_per_pixel, Some(16));
+            assert_eq!(parsed.output, Some(PathBuf::from("out.exr")));
+            assert!((parsed.camera_center.unwrap() - Vec3::new(1.0, 2.0, 3.0)).near_zero());
+            assert_eq!(parsed.seed, Some(7));
+        }
+        // a value may start with a dash
+        assert_eq!(parse("--exposure -1.5").unwrap().exposure, Some(-1.5));
+        let parsed = parse("spheres --ascii-ppm -h --tone-mapper extended_reinhard:4").unwrap();
+        assert_eq!(parsed.scene, Some(SceneSource::Builtin(String::from("spheres"))));
+        assert!(parsed.ascii_ppm && parsed.help);
+        assert_eq!(parsed.tone_mapper, Some(ToneMapper::ExtendedReinhard { white: 4.0 }));
+    }
+
+    #[test]
+    fn errors() {
+        let cases = [
+            ("--fast", "unknown option `--fast`"),
+            ("--fast=yes", "unknown option `--fast`"),
+            ("--spp", "`--spp` needs a value"),
+            ("--width 10 --look-at", "`--look-at` needs a value"),
+            ("--ascii-ppm=false", "`--ascii-ppm` does not take a value"),
+            ("--help=yes", "`--help` does not take a value"),
+            ("--spp many", "`--spp` expects a number, not `many`"),
+            ("--spp 0", "`--spp` must be greater than zero"),
+            ("--threads -2", "`--threads` expects a number, not `-2`"),
+            ("--vfov 180", "`--vfov` must be between 0 and 180 degrees"),
+            ("--vfov=0", "`--vfov` must be between 0 and 180 degrees"),
+            ("--depth-of-field-blur -0.1", "`--depth-of-field-blur` must not be negative"),
+            ("--look-at 1,2", "`--look-at` expects three comma separated numbers, not `1,2`"),
+            ("--shutter 0.5,0.25", "`--shutter` needs 0 <= OPEN <= CLOSE <= 1, not `0.5,0.25`"),
+            ("--bit-depth 12", "`--bit-depth` must be 8 or 16, not `12`"),
+            ("--width 10 --height 10 --aspect-ratio 2", "only two of `--width`, `--height` and `--aspect-ratio` can be given"),
+            ("--environment-intensity 2", "`--environment-rotation` and `--environment-intensity` need `--background <FILE.hdr>`"),
+            ("spheres cornell", "unexpected argument `cornell`, only one scene can be given"),
+        ];
+        for (args, message) in cases {
+            assert_eq!(parse(args).unwrap_err(), message, "{args}");
+        }
+    }
+}
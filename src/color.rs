@@ -1,4 +1,4 @@
-use std::{fs::File, io::{BufWriter, Write}, ops::{Add, Div, Mul}};
+use std::ops::{Add, Div, Mul};
 
 use crate::interval::Interval;
 
@@ -14,20 +14,27 @@ impl Color {
         Self { r, g, b}
     }
 
-    fn to_gamma(linear: f64) -> f64 {
-        if linear > 0.0 {
-            return linear.powf(0.5);
-        }
+    /// Relative luminance of linear sRGB/Rec. 709 primaries
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+    }
 
-        return 0.0;
+    /// Quantises display encoded values for 8-bit output
+    pub fn to_rgb8(self) -> [u8; 3] {
+        let i = Interval::new(0.0, 1.0);
+        let r = (i.clamp(self.r) * 255.0).round() as u8;
+        let g = (i.clamp(self.g) * 255.0).round() as u8;
+        let b = (i.clamp(self.b) * 255.0).round() as u8;
+        [r, g, b]
     }
 
-    pub fn write(&self, file: &mut BufWriter<File>) {
-        let i = Interval::new(0.0, 0.999);
-        let r = (i.clamp(Self::to_gamma(self.r)) * 256.0) as u8;
-        let g = (i.clamp(Self::to_gamma(self.g)) * 256.0) as u8;
-        let b = (i.clamp(Self::to_gamma(self.b)) * 256.0) as u8;
-        file.write_all(format!("{r} {g} {b}\n").as_bytes()).expect("Could not write to file");
+    /// Quantises display encoded values for 16-bit output
+    pub fn to_rgb16(self) -> [u16; 3] {
+        let i = Interval::new(0.0, 1.0);
+        let r = (i.clamp(self.r) * 65535.0).round() as u16;
+        let g = (i.clamp(self.g) * 65535.0).round() as u16;
+        let b = (i.clamp(self.b) * 65535.0).round() as u16;
+        [r, g, b]
     }
 }
 
@@ -0,0 +1,216 @@
+use crate::{aabb::Aabb, hittable::{HitRecord, Hittable}, interval::Interval, ray::Ray};
+
+/// Most surfaces a ray is followed through when listing where it's inside a solid
+const MAX_CROSSINGS: usize = 64;
+
+/// Where a ray crosses the surface of a solid. Spans that never end, like
+/// the inside of a plane, start or stop at an infinite `t` without a record.
+#[derive(Debug)]
+pub struct Crossing<'a> {
+    pub t: f64,
+    pub rec: Option<HitRecord<'a>>,
+}
+
+impl<'a> Crossing<'a> {
+    const fn infinite(t: f64) -> Self {
+        Crossing { t, rec: None }
+    }
+    /// The same crossing as the way into (`true`) or out of the solid it now bounds
+    fn facing(mut self, front_face: bool) -> Self {
+        if let Some(rec) = &mut self.rec {
+            rec.front_face = front_face;
+        }
+        self
+    }
+}
+
+/// A stretch of a ray inside a solid
+#[derive(Debug)]
+pub struct Span<'a> {
+    pub enter: Crossing<'a>,
+    pub exit: Crossing<'a>,
+}
+
+/// Where `ray` is inside `object`, in order, found by following it from
+/// surface to surface. Front faces are taken as the way in and back faces as
+/// the way out, so a plane is a half space.
+pub fn crossings<'a>(object: &'a Hittable, ray: &Ray) -> Vec<Span<'a>> {
+    let mut spans = Vec::new();
+    let mut open = None;
+    let mut t_min = -f64::INFINITY;
+    for i in 0..MAX_CROSSINGS {
+        let Some(rec) = object.hit(ray, Interval::new(t_min, f64::INFINITY)) else {
+            break;
+        };
+        // step past the surface just found, far enough that it isn't found again
+        t_min = rec.t + 1e-9 * rec.t.abs().max(1.0);
+        let entering = rec.front_face;
+        let crossing = Crossing { t: rec.t, rec: Some(rec) };
+        match (entering, open.take()) {
+            (true, None) => open = Some(crossing),
+            (false, Some(enter)) => spans.push(Span { enter, exit: crossing }),
+            // leaving first means the ray was inside all along
+            (false, None) if i == 0 => spans.push(Span { enter: Crossing::infinite(-f64::INFINITY), exit: crossing }),
+            // unmatched surfaces of open or overlapping meshes
+            (true, Some(enter)) => open = Some(enter),
+            (false, None) => {},
+        }
+    }
+    if let Some(enter) = open {
+        spans.push(Span { enter, exit: Crossing::infinite(f64::INFINITY) });
+    }
+    spans
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsgOperation {
+    Union,
+    Intersection,
+    /// The left solid with the right one cut out of it
+    Difference,
+}
+
+impl CsgOperation {
+    fn contains(self, in_left: bool, in_right: bool) -> bool {
+        match self {
+            CsgOperation::Union => in_left || in_right,
+            CsgOperation::Intersection => in_left && in_right,
+            CsgOperation::Difference => in_left && !in_right,
+        }
+    }
+}
+
+/// Two solids combined by a boolean operation, like a lens from two spheres
+/// or a plate with holes drilled by cylinders.
+///
+/// Both sides have to be closed, or planes. Each surface keeps its own
+/// material, the walls of a hole get the material of what cut it.
+#[derive(Clone)]
+pub struct Csg {
+    pub operation: CsgOperation,
+    pub left: Box<Hittable>,
+    pub right: Box<Hittable>,
+    bbox: Aabb,
+}
+
+impl Csg {
+    pub fn new(operation: CsgOperation, left: Hittable, right: Hittable) -> Hittable {
+        let (a, b) = (left.bounding_box(), right.bounding_box());
+        let bbox = match operation {
+            CsgOperation::Union => a.union(b),
+            CsgOperation::Intersection => a.intersection(b),
+            CsgOperation::Difference => a,
+        };
+        Hittable::Csg(Box::new(Csg { operation, left: Box::new(left), right: Box::new(right), bbox }))
+    }
+    pub fn union(left: Hittable, right: Hittable) -> Hittable {
+        Self::new(CsgOperation::Union, left, right)
+    }
+    pub fn intersection(left: Hittable, right: Hittable) -> Hittable {
+        Self::new(CsgOperation::Intersection, left, right)
+    }
+    pub fn difference(left: Hittable, right: Hittable) -> Hittable {
+        Self::new(CsgOperation::Difference, left, right)
+    }
+}
+
+impl Csg {
+    pub fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+    /// The nearest end of a span within `ray_t`
+    pub fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        self.spans(ray)
+            .into_iter()
+            .flat_map(|span| [span.enter, span.exit])
+            .find(|crossing| crossing.rec.is_some() && ray_t.surrounds(crossing.t))
+            .and_then(|crossing| crossing.rec)
+    }
+    /// Merges the spans of both sides, sweeping over their ends in order
+    pub fn spans(&self, ray: &Ray) -> Vec<Span<'_>> {
+        let left = self.left.spans(ray);
+        let right = self.right.spans(ray);
+        if right.is_empty() && self.operation != CsgOperation::Intersection {
+            return left;
+        }
+
+        let mut ends: Vec<(bool, bool, Crossing<'_>)> = Vec::with_capacity(2 * (left.len() + right.len()));
+        for (is_left, spans) in [(true, left), (false, right)] {
+            for span in spans {
+                ends.push((is_left, true, span.enter));
+                ends.push((is_left, false, span.exit));
+            }
+        }
+        ends.sort_by(|a, b| a.2.t.total_cmp(&b.2.t));
+
+        let mut spans = Vec::new();
+        let (mut in_left, mut in_right) = (false, false);
+        let mut open = None;
+        for (is_left, entering, crossing) in ends {
+            let was_inside = self.operation.contains(in_left, in_right);
+            if is_left {in_left = entering} else {in_right = entering}
+            match (was_inside, self.operation.contains(in_left, in_right)) {
+                (false, true) => open = Some(crossing.facing(true)),
+                (true, false) => {
+                    if let Some(enter) = open.take() {
+                        spans.push(Span { enter, exit: crossing.facing(false) });
+                    }
+                },
+                _ => {},
+            }
+        }
+        spans
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{quad::Cuboid, quadric::Cylinder, sphere::Sphere, test_utils::{hit, material}, vec3::Vec3};
+
+    /// Two overlapping spheres make a lens where they intersect, and one
+    /// solid without the inner walls where they're joined
+    #[test]
+    fn lens_and_union() {
+        let a = Sphere::new(Vec3::new(0.0, 0.0, -0.6), 1.0, material());
+        let b = Sphere::new(Vec3::new(0.0, 0.0, 0.6), 1.0, material());
+
+        let lens = Csg::intersection(a.clone(), b.clone());
+        let spans = lens.spans(&Ray::new(Vec3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0)));
+        assert_eq!(spans.len(), 1);
+        assert!((spans[0].enter.t - 4.6).abs() < 1e-9 && (spans[0].exit.t - 5.4).abs() < 1e-9);
+        // the front of the lens is the back sphere's surface, facing out
+        let rec = hit(&lens, Vec3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0)).unwrap();
+        assert!(rec.front_face && (rec.normal - Vec3::new(0.0, 0.0, 1.0)).len() < 1e-9);
+        // outside the lens but inside one sphere
+        assert!(hit(&lens, Vec3::new(0.0, 0.9, 5.0), Vec3::new(0.0, 0.0, -1.0)).is_none());
+
+        let union = Csg::union(a, b);
+        let rec = hit(&union, Vec3::zero(), Vec3::new(0.0, 0.0, 1.0)).unwrap();
+        assert!((rec.t - 1.6).abs() < 1e-9 && !rec.front_face);
+        let bbox = union.bounding_box();
+        assert!((bbox.min.z + 1.6).abs() < 1e-9 && (bbox.max.z - 1.6).abs() < 1e-9);
+    }
+
+    /// A plate with a hole drilled through it lets rays through the hole, and
+    /// the hole's wall faces out of the plate
+    #[test]
+    fn drilled_plate() {
+        let plate = Cuboid::new(Vec3::new(-2.0, -0.25, -2.0), Vec3::new(2.0, 0.25, 2.0), material());
+        let drill = Cylinder::new(Vec3::new(0.0, -1.0, 0.0), Vec3::new(0.0, 1.0, 0.0), 0.5, material());
+        let drilled = Csg::difference(plate, drill);
+
+        assert!(hit(&drilled, Vec3::new(0.2, 3.0, 0.0), Vec3::new(0.0, -1.0, 0.0)).is_none());
+        let rec = hit(&drilled, Vec3::new(1.0, 3.0, 0.0), Vec3::new(0.0, -1.0, 0.0)).unwrap();
+        assert!((rec.t - 2.75).abs() < 1e-9 && rec.front_face);
+
+        // across the hole at the height of the plate, the far wall is the way in
+        let rec = hit(&drilled, Vec3::new(-0.2, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0)).unwrap();
+        assert!((rec.t - 0.7).abs() < 1e-9 && rec.front_face);
+        assert!((rec.normal - Vec3::new(-1.0, 0.0, 0.0)).len() < 1e-9);
+        // then back out through the side of the plate
+        let spans = drilled.spans(&Ray::new(Vec3::new(-0.2, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0)));
+        assert_eq!(spans.len(), 2);
+        assert!((spans[1].exit.t - 2.2).abs() < 1e-9 && !spans[1].exit.rec.as_ref().unwrap().front_face);
+    }
+}
@@ -0,0 +1,297 @@
+use std::{error::Error, fmt, fs, io, path::{Path, PathBuf}};
+
+use crate::color::Color;
+
+/// Linear floating point image
+#[derive(Clone)]
+pub struct HdrImage {
+    pub width: usize,
+    pub height: usize,
+    /// Row major, top row first
+    pub pixels: Vec<Color>,
+}
+
+impl fmt::Debug for HdrImage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "HdrImage({}x{})", self.width, self.height)
+    }
+}
+
+impl HdrImage {
+    pub fn get(&self, x: usize, y: usize) -> Color {
+        self.pixels[y * self.width + x]
+    }
+}
+
+#[derive(Debug)]
+pub enum HdrError {
+    Io { path: PathBuf, error: io::Error },
+    Format { path: PathBuf, message: String },
+}
+
+impl fmt::Display for HdrError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HdrError::Io { path, error } => write!(f, "{}: {error}", path.display()),
+            HdrError::Format { path, message } => write!(f, "{}: {message}", path.display()),
+        }
+    }
+}
+
+impl Error for HdrError {}
+
+/// Loads a Radiance `.hdr` (RGBE) file, flat or run length encoded in either
+/// the current or the old (pre 1991) way
+pub fn load_hdr(path: impl AsRef<Path>) -> Result<HdrImage, HdrError> {
+    let path = path.as_ref();
+    let data = fs::read(path).map_err(|error| HdrError::Io { path: path.to_path_buf(), error })?;
+    decode_hdr(&data).map_err(|message| HdrError::Format { path: path.to_path_buf(), message })
+}
+
+pub(crate) fn decode_hdr(data: &[u8]) -> Result<HdrImage, String> {
+    let mut pos = 0;
+    let mut next_line = || -> Result<&str, String> {
+        let start = pos;
+        let end = data[start..].iter().position(|&b| b == b'\n').ok_or("unexpected end of header")? + start;
+        pos = end + 1;
+        std::str::from_utf8(&data[start..end]).map_err(|_| String::from("header is not valid text"))
+    };
+
+    let magic = next_line()?;
+    if !magic.starts_with("#?") {
+        return Err(String::from("missing `#?RADIANCE` signature"));
+    }
+
+    // header variables end at the first blank line
+    loop {
+        let line = next_line()?.trim();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(format) = line.strip_prefix("FORMAT=") {
+            if format != "32-bit_rle_rgbe" {
+                return Err(format!("unsupported pixel format `{format}`"));
+            }
+        }
+    }
+
+    let resolution = next_line()?;
+    let (flip_y, height, width) = match resolution.split_whitespace().collect::<Vec<_>>()[..] {
+        [y, h, "+X", w] if y == "-Y" || y == "+Y" => {
+            let h = h.parse::<usize>().map_err(|_| format!("bad image height `{h}`"))?;
+            let w = w.parse::<usize>().map_err(|_| format!("bad image width `{w}`"))?;
+            if h == 0 || w == 0 {
+                return Err(String::from("image has no pixels"));
+            }
+            (y == "+Y", h, w)
+        },
+        _ => return Err(format!("unsupported resolution line `{resolution}`")),
+    };
+
+    // every scanline takes at least 4 bytes, and 4 bytes make at most
+    // 255 << 16 pixels, as the third of a row of old style runs
+    let available = data.len() - pos;
+    let widest = available / 4 * 0xFF_0000;
+    if width.checked_mul(height).is_none() || height > available / 4 || width > widest {
+        return Err(format!("{width}x{height} pixels can't fit in the file"));
+    }
+
+    let mut pixels = Vec::new();
+    let mut scanline = vec![[0u8; 4]; width];
+    for _ in 0..height {
+        pos = read_scanline(data, pos, &mut scanline)?;
+        pixels.extend(scanline.iter().map(|&rgbe| rgbe_to_color(rgbe)));
+    }
+
+    if flip_y {
+        let rows: Vec<&[Color]> = pixels.chunks(width).rev().collect();
+        pixels = rows.concat();
+    }
+
+    Ok(HdrImage { width, height, pixels })
+}
+
+/// Reads one scanline starting at `pos` and returns the position after it
+fn read_scanline(data: &[u8], mut pos: usize, scanline: &mut [[u8; 4]]) -> Result<usize, String> {
+    let width = scanline.len();
+    let eof = || String::from("unexpected end of pixel data");
+
+    let header = data.get(pos..pos + 4).ok_or_else(eof)?;
+    let is_rle = (8..0x8000).contains(&width) && header[0] == 2 && header[1] == 2 && header[2] & 0x80 == 0;
+
+    if !is_rle {
+        // flat pixels, where old style files repeat the previous pixel for
+        // each `1 1 1 n`, consecutive ones giving the next 8 bits of the count
+        let mut shift = 0;
+        let mut x = 0;
+        while x < width {
+            let pixel: [u8; 4] = data.get(pos..pos + 4).ok_or_else(eof)?.try_into().unwrap();
+            pos += 4;
+            if pixel[..3] != [1, 1, 1] {
+                scanline[x] = pixel;
+                x += 1;
+                shift = 0;
+                continue;
+            }
+            if x == 0 {
+                return Err(String::from("run at the start of a scanline"));
+            }
+            let count = (pixel[3] as usize) << shift;
+            if shift > 16 || x + count > width {
+                return Err(String::from("run overflows the scanline"));
+            }
+            let previous = scanline[x - 1];
+            scanline[x..x + count].fill(previous);
+            x += count;
+            shift += 8;
+        }
+        return Ok(pos);
+    }
+
+    if ((header[2] as usize) << 8 | header[3] as usize) != width {
+        return Err(String::from("scanline width does not match the image width"));
+    }
+    pos += 4;
+
+    // each of the four channels is stored separately as runs and literals
+    for channel in 0..4 {
+        let mut x = 0;
+        while x < width {
+            let count = *data.get(pos).ok_or_else(eof)? as usize;
+            pos += 1;
+            if count > 128 {
+                let count = count - 128;
+                let value = *data.get(pos).ok_or_else(eof)?;
+                pos += 1;
+                if x + count > width {
+                    return Err(String::from("run overflows the scanline"));
+                }
+                for pixel in &mut scanline[x..x + count] {
+                    pixel[channel] = value;
+                }
+                x += count;
+            } else {
+                if count == 0 || x + count > width {
+                    return Err(String::from("bad literal length in scanline"));
+                }
+                let values = data.get(pos..pos + count).ok_or_else(eof)?;
+                for (pixel, &value) in scanline[x..x + count].iter_mut().zip(values) {
+                    pixel[channel] = value;
+                }
+                pos += count;
+                x += count;
+            }
+        }
+    }
+
+    Ok(pos)
+}
+
+fn rgbe_to_color(rgbe: [u8; 4]) -> Color {
+    if rgbe[3] == 0 {
+        return Color::new(0.0, 0.0, 0.0);
+    }
+    let scale = 2f64.powi(rgbe[3] as i32 - 136);
+    Color::new(rgbe[0] as f64 * scale, rgbe[1] as f64 * scale, rgbe[2] as f64 * scale)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A file with the usual header around `resolution` and `pixels`
+    fn file(resolution: &str, pixels: &[u8]) -> Vec<u8> {
+        let mut data = format!("#?RADIANCE\n# made by hand\nFORMAT=32-bit_rle_rgbe\nEXPOSURE=1.0\n\n{resolution}\n").into_bytes();
+        data.extend_from_slice(pixels);
+        data
+    }
+
+    fn assert_pixels(image: &HdrImage, expected: &[(f64, f64, f64)]) {
+        assert_eq!(image.pixels.len(), expected.len());
+        for (pixel, &(r, g, b)) in image.pixels.iter().zip(expected) {
+            assert!((pixel.r, pixel.g, pixel.b) == (r, g, b), "{:?} != {expected:?}", image.pixels);
+        }
+    }
+
+    #[test]
+    fn header() {
+        let image = decode_hdr(&file("-Y 2 +X 1", &[128, 64, 32, 129, 0, 0, 0, 0])).unwrap();
+        assert_eq!((image.width, image.height), (1, 2));
+        assert_pixels(&image, &[(1.0, 0.5, 0.25), (0.0, 0.0, 0.0)]);
+        // bottom row first
+        let image = decode_hdr(&file("+Y 2 +X 1", &[128, 64, 32, 129, 0, 0, 0, 0])).unwrap();
+        assert_pixels(&image, &[(0.0, 0.0, 0.0), (1.0, 0.5, 0.25)]);
+
+        let errors = [
+            (b"P6\n".to_vec(), "missing `#?RADIANCE` signature"),
+            (b"#?RADIANCE\nFORMAT=32-bit_rle_xyze\n\n-Y 1 +X 1\n".to_vec(), "unsupported pixel format `32-bit_rle_xyze`"),
+            (b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n".to_vec(), "unexpected end of header"),
+            (file("+X 1 -Y 1", &[0; 4]), "unsupported resolution line `+X 1 -Y 1`"),
+            (file("-Y 1 +X wide", &[0; 4]), "bad image width `wide`"),
+            (file("-Y 0 +X 1", &[]), "image has no pixels"),
+            (file("-Y 1 +X 2", &[0; 4]), "unexpected end of pixel data"),
+        ];
+        for (data, message) in errors {
+            assert_eq!(decode_hdr(&data).unwrap_err(), message);
+        }
+    }
+
+    /// Resolutions no file of this length could hold are rejected before
+    /// anything is allocated for them
+    #[test]
+    fn oversized() {
+        let errors = [
+            (file("-Y 4000000000 +X 4000000000", &[0; 4]), "4000000000x4000000000 pixels can't fit in the file"),
+            (file("-Y 18446744073709551615 +X 2", &[0; 64]), "2x18446744073709551615 pixels can't fit in the file"),
+            (file("-Y 3 +X 1", &[0; 8]), "1x3 pixels can't fit in the file"),
+            (file("-Y 1 +X 66846721", &[0; 16]), "66846721x1 pixels can't fit in the file"),
+            (file("-Y 1 +X 1", &[0; 3]), "1x1 pixels can't fit in the file"),
+        ];
+        for (data, message) in errors {
+            assert_eq!(decode_hdr(&data).unwrap_err(), message);
+        }
+    }
+
+    /// Scanlines split into channels, each stored as runs and literals
+    #[test]
+    fn run_length_encoded() {
+        let pixels = [
+            2, 2, 0, 8,
+            // red: a run of 6, then 2 literals
+            128 + 6, 64, 2, 128, 32,
+            // green, blue and exponent all runs
+            128 + 8, 0,
+            128 + 8, 0,
+            128 + 5, 129, 128 + 3, 130,
+        ];
+        let image = decode_hdr(&file("-Y 1 +X 8", &pixels)).unwrap();
+        let mut expected = [(0.5, 0.0, 0.0); 8];
+        // the exponent goes up by one from the sixth pixel
+        expected[5] = (1.0, 0.0, 0.0);
+        expected[6] = (2.0, 0.0, 0.0);
+        assert_pixels(&image, &expected);
+
+        let mut long = pixels;
+        long[9] = 128 + 9;
+        assert_eq!(decode_hdr(&file("-Y 1 +X 8", &long)).unwrap_err(), "run overflows the scanline");
+        let mut narrow = pixels;
+        narrow[3] = 9;
+        assert_eq!(decode_hdr(&file("-Y 1 +X 8", &narrow)).unwrap_err(), "scanline width does not match the image width");
+    }
+
+    /// Old style runs repeat the previous pixel, consecutive ones making
+    /// up a longer count 8 bits at a time
+    #[test]
+    fn old_run_length_encoded() {
+        let mut pixels = vec![128, 64, 32, 129, 1, 1, 1, 2, 0, 0, 0, 0, 1, 1, 1, 4, 1, 1, 1, 1];
+        pixels.extend([64, 64, 64, 129]);
+        let image = decode_hdr(&file("-Y 1 +X 265", &pixels)).unwrap();
+        assert!(image.pixels[..3].iter().all(|p| p.r == 1.0 && p.b == 0.25));
+        // 4 + (1 << 8) black pixels
+        assert!(image.pixels[3..264].iter().all(|p| p.r == 0.0));
+        assert_eq!(image.pixels[264].g, 0.5);
+
+        assert_eq!(decode_hdr(&file("-Y 1 +X 4", &[1, 1, 1, 4])).unwrap_err(), "run at the start of a scanline");
+        assert_eq!(decode_hdr(&file("-Y 1 +X 4", &[0, 0, 0, 0, 1, 1, 1, 4])).unwrap_err(), "run overflows the scanline");
+    }
+}
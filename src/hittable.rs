@@ -1,23 +1,166 @@
-use crate::{interval::Interval, material::Material, ray::Ray, sphere::Sphere, vec3::Vec3};
+use std::sync::Arc;
+
+use crate::{aabb::Aabb, bvh::Bvh, csg::{crossings, Csg, Span}, instance::Instance, interval::Interval, material::Material, medium::ConstantMedium, mesh::MeshTriangle, plane::{Disk, Plane}, quad::{Cuboid, Quad}, quadric::{Capsule, Cone, Cylinder}, ray::Ray, sphere::Sphere, torus::Torus, triangle::Triangle, vec3::Vec3};
 
 #[derive(Debug)]
-pub struct HitRecord {
+pub struct HitRecord<'a> {
     pub p: Vec3,
     pub normal: Vec3,
     pub t: f64,
     pub front_face: bool,
-    pub mat: Material,
+    pub mat: &'a Material,
+    /// Surface coordinates. For triangles without texture coordinates these
+    /// are the barycentric weights of the second and third vertex.
+    pub u: f64,
+    pub v: f64,
 }
 
-#[derive(Clone, Copy)]
+/// Variants holding a `Material` are boxed, which keeps a `Hittable` as
+/// small as a `MeshTriangle` so the faces of big meshes stay compact
+#[derive(Clone)]
 pub enum Hittable {
-    Sphere(Sphere),
+    Sphere(Box<Sphere>),
+    Triangle(Box<Triangle>),
+    MeshTriangle(MeshTriangle),
+    Quad(Box<Quad>),
+    /// Unbounded, BVHs keep it out of their tree
+    Plane(Box<Plane>),
+    Disk(Box<Disk>),
+    Cuboid(Box<Cuboid>),
+    Cylinder(Box<Cylinder>),
+    Cone(Box<Cone>),
+    Capsule(Box<Capsule>),
+    Torus(Box<Torus>),
+    Csg(Box<Csg>),
+    ConstantMedium(Box<ConstantMedium>),
+    /// A group of objects, shared so instances can reuse it
+    Bvh(Arc<Bvh>),
+    Instance(Box<Instance>),
 }
 
 impl Hittable {
-    pub fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord> {
+    pub fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
         match self {
             Hittable::Sphere(sphere) => sphere.hit(ray, ray_t),
+            Hittable::Triangle(triangle) => triangle.hit(ray, ray_t),
+            Hittable::MeshTriangle(triangle) => triangle.hit(ray, ray_t),
+            Hittable::Quad(quad) => quad.hit(ray, ray_t),
+            Hittable::Plane(plane) => plane.hit(ray, ray_t),
+            Hittable::Disk(disk) => disk.hit(ray, ray_t),
+            Hittable::Cuboid(cuboid) => cuboid.hit(ray, ray_t),
+            Hittable::Cylinder(cylinder) => cylinder.hit(ray, ray_t),
+            Hittable::Cone(cone) => cone.hit(ray, ray_t),
+            Hittable::Capsule(capsule) => capsule.hit(ray, ray_t),
+            Hittable::Torus(torus) => torus.hit(ray, ray_t),
+            Hittable::Csg(csg) => csg.hit(ray, ray_t),
+            Hittable::ConstantMedium(medium) => medium.hit(ray, ray_t),
+            Hittable::Bvh(bvh) => bvh.hit(ray, ray_t),
+            Hittable::Instance(instance) => instance.hit(ray, ray_t),
+        }
+    }
+    /// Stretches of `ray` inside this object as a solid, in order, for CSG
+    pub fn spans(&self, ray: &Ray) -> Vec<Span<'_>> {
+        match self {
+            Hittable::Csg(csg) => csg.spans(ray),
+            _ => crossings(self, ray),
+        }
+    }
+    /// Whether direct lighting should sample this object. Moving objects,
+    /// planes, curved shapes other than spheres, CSG and the objects inside a
+    /// BVH never are, rays still find them.
+    pub fn is_emissive(&self) -> bool {
+        match self {
+            Hittable::Sphere(sphere) => sphere.mat.is_emissive() && !sphere.is_moving(),
+            Hittable::Triangle(triangle) => triangle.mat.is_emissive(),
+            Hittable::MeshTriangle(triangle) => triangle.material().is_emissive(),
+            Hittable::Quad(quad) => quad.mat.is_emissive(),
+            Hittable::Disk(disk) => disk.mat.is_emissive(),
+            Hittable::Cuboid(cuboid) => cuboid.material().is_emissive(),
+            Hittable::Plane(_) | Hittable::Cylinder(_) | Hittable::Cone(_) | Hittable::Capsule(_) | Hittable::Torus(_) => false,
+            Hittable::Csg(_) | Hittable::ConstantMedium(_) | Hittable::Bvh(_) => false,
+            Hittable::Instance(instance) => instance.object.is_emissive() && !instance.is_animated(),
+        }
+    }
+    /// What direct lighting samples for this object: itself if it is
+    /// emissive, otherwise the emissive objects in a group, each moved by the
+    /// transforms of the instances it sits in
+    pub fn emitters(&self) -> Vec<Hittable> {
+        match self {
+            _ if self.is_emissive() => vec![self.clone()],
+            Hittable::Bvh(bvh) => bvh.objects().flat_map(Hittable::emitters).collect(),
+            Hittable::Instance(instance) if !instance.is_animated() => {
+                instance.object.emitters().into_iter().map(|emitter| Instance::new(emitter, instance.transform())).collect()
+            },
+            _ => Vec::new(),
         }
     }
-}
\ No newline at end of file
+    /// The material of a single surface, `None` for groups and CSG
+    pub fn material(&self) -> Option<&Material> {
+        match self {
+            Hittable::Sphere(sphere) => Some(&sphere.mat),
+            Hittable::Triangle(triangle) => Some(&triangle.mat),
+            Hittable::MeshTriangle(triangle) => Some(triangle.material()),
+            Hittable::Quad(quad) => Some(&quad.mat),
+            Hittable::Plane(plane) => Some(&plane.mat),
+            Hittable::Disk(disk) => Some(&disk.mat),
+            Hittable::Cuboid(cuboid) => Some(cuboid.material()),
+            Hittable::Cylinder(cylinder) => Some(&cylinder.mat),
+            Hittable::Cone(cone) => Some(&cone.mat),
+            Hittable::Capsule(capsule) => Some(&capsule.mat),
+            Hittable::Torus(torus) => Some(&torus.mat),
+            Hittable::ConstantMedium(medium) => Some(&medium.phase),
+            Hittable::Csg(_) | Hittable::Bvh(_) => None,
+            Hittable::Instance(instance) => instance.object.material(),
+        }
+    }
+    /// Density, per solid angle at `origin`, with which `sample` picks `direction`
+    pub fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f64 {
+        match self {
+            Hittable::Sphere(sphere) => sphere.pdf_value(origin, direction),
+            Hittable::Triangle(triangle) => triangle.pdf_value(origin, direction),
+            Hittable::MeshTriangle(triangle) => triangle.pdf_value(origin, direction),
+            Hittable::Quad(quad) => quad.pdf_value(origin, direction),
+            Hittable::Plane(_) | Hittable::Cylinder(_) | Hittable::Cone(_) | Hittable::Capsule(_) | Hittable::Torus(_) | Hittable::Csg(_) => 0.0,
+            Hittable::Disk(disk) => disk.pdf_value(origin, direction),
+            Hittable::Cuboid(cuboid) => cuboid.pdf_value(origin, direction),
+            Hittable::ConstantMedium(medium) => medium.boundary.pdf_value(origin, direction),
+            Hittable::Bvh(_) => 0.0,
+            Hittable::Instance(instance) => instance.pdf_value(origin, direction),
+        }
+    }
+    /// Direction from `origin` towards a random point of the surface, not normalized
+    pub fn sample(&self, origin: Vec3, uv: (f64, f64)) -> Vec3 {
+        match self {
+            Hittable::Sphere(sphere) => sphere.sample(origin, uv),
+            Hittable::Triangle(triangle) => triangle.sample(origin, uv),
+            Hittable::MeshTriangle(triangle) => triangle.sample(origin, uv),
+            Hittable::Quad(quad) => quad.sample(origin, uv),
+            Hittable::Plane(plane) => plane.point - origin,
+            Hittable::Disk(disk) => disk.sample(origin, uv),
+            Hittable::Cuboid(cuboid) => cuboid.sample(origin, uv),
+            Hittable::Cylinder(_) | Hittable::Cone(_) | Hittable::Capsule(_) | Hittable::Torus(_) | Hittable::Csg(_) => self.bounding_box().centroid() - origin,
+            Hittable::ConstantMedium(medium) => medium.boundary.sample(origin, uv),
+            Hittable::Bvh(bvh) => bvh.bounding_box().centroid() - origin,
+            Hittable::Instance(instance) => instance.sample(origin, uv),
+        }
+    }
+    pub fn bounding_box(&self) -> Aabb {
+        match self {
+            Hittable::Sphere(sphere) => sphere.bounding_box(),
+            Hittable::Triangle(triangle) => triangle.bounding_box(),
+            Hittable::MeshTriangle(triangle) => triangle.bounding_box(),
+            Hittable::Quad(quad) => quad.bounding_box(),
+            Hittable::Plane(plane) => plane.bounding_box(),
+            Hittable::Disk(disk) => disk.bounding_box(),
+            Hittable::Cuboid(cuboid) => cuboid.bounding_box(),
+            Hittable::Cylinder(cylinder) => cylinder.bounding_box(),
+            Hittable::Cone(cone) => cone.bounding_box(),
+            Hittable::Capsule(capsule) => capsule.bounding_box(),
+            Hittable::Torus(torus) => torus.bounding_box(),
+            Hittable::Csg(csg) => csg.bounding_box(),
+            Hittable::ConstantMedium(medium) => medium.bounding_box(),
+            Hittable::Bvh(bvh) => bvh.bounding_box(),
+            Hittable::Instance(instance) => instance.bounding_box(),
+        }
+    }
+}
@@ -1,21 +1,49 @@
-use crate::{hittable::{HitRecord, Hittable}, interval::Interval, ray::Ray};
+use crate::{hittable::{HitRecord, Hittable}, interval::Interval, light::Light, ray::Ray};
 
 pub struct HittableList {
     list: Vec<Hittable>,
+    /// Punctual lights and copies of the objects with emissive materials,
+    /// including those inside instanced models, sampled for direct lighting
+    lights: Vec<Light>,
 }
 
 impl HittableList {
     pub fn new() -> Self {
-        Self {list: Vec::new()}
+        Self {list: Vec::new(), lights: Vec::new()}
     }
     pub fn add(&mut self, object: Hittable) {
+        self.lights.extend(object.emitters().into_iter().map(Light::Area));
         self.list.push(object);
     }
+    /// Adds a light without geometry, only direct lighting sees it
+    pub fn add_light(&mut self, light: Light) {
+        self.lights.push(light);
+    }
+    pub fn extend(&mut self, objects: impl IntoIterator<Item = Hittable>) {
+        for object in objects {
+            self.add(object);
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+    pub fn objects(&self) -> &[Hittable] {
+        &self.list
+    }
+    pub fn lights(&self) -> &[Light] {
+        &self.lights
+    }
+    pub fn into_objects(self) -> Vec<Hittable> {
+        self.list
+    }
 }
 
 impl HittableList {
-    pub fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord> {
-        let mut temp_rec = None::<HitRecord>;
+    pub fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let mut temp_rec = None::<HitRecord<'_>>;
         let mut closest = ray_t.max;
 
         for object in &self.list {
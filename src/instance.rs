@@ -0,0 +1,220 @@
+use std::sync::Arc;
+
+use crate::{aabb::Aabb, hittable::{HitRecord, Hittable}, interval::Interval, ray::Ray, transform::{interpolate, Keyframe, Mat4}, vec3::Vec3};
+
+/// Steps per keyframe interval when bounding an animated instance
+const BOUNDS_STEPS: usize = 32;
+
+/// A shared hittable placed in the world with an affine transform.
+///
+/// Rays are moved into the object's space instead of moving the object, so
+/// any number of instances of one mesh (usually a `Hittable::Bvh`) only keep
+/// one copy of it.
+#[derive(Clone)]
+pub struct Instance {
+    pub object: Arc<Hittable>,
+    to_world: Mat4,
+    to_object: Mat4,
+    bbox: Aabb,
+    /// Empty for a fixed transform
+    keyframes: Vec<Keyframe>,
+}
+
+impl Instance {
+    /// Panics if `transform` can't be inverted
+    pub fn new(object: impl Into<Arc<Hittable>>, transform: Mat4) -> Hittable {
+        let object = object.into();
+        let to_object = transform.inverse().expect("instance transform must be invertible");
+        let bbox = transform.transform_box(object.bounding_box());
+        Hittable::Instance(Box::new(Instance { object, to_world: transform, to_object, bbox, keyframes: Vec::new() }))
+    }
+    /// An instance moving through `keyframes`, the rays' time picks the
+    /// transform. Panics without keyframes.
+    pub fn animated(object: impl Into<Arc<Hittable>>, mut keyframes: Vec<Keyframe>) -> Hittable {
+        let object = object.into();
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+        let first = keyframes.first().expect("animated instance needs keyframes").transform.matrix();
+        let to_object = first.inverse().expect("instance transform must be invertible");
+        let bbox = Self::animated_bounds(&object.bounding_box(), &keyframes);
+        Hittable::Instance(Box::new(Instance { object, to_world: first, to_object, bbox, keyframes }))
+    }
+    /// Union of the boxes along the way, grown by the largest distance a
+    /// corner moves in one step so nothing sticks out in between
+    fn animated_bounds(bbox: &Aabb, keyframes: &[Keyframe]) -> Aabb {
+        let mut result = keyframes[0].transform.matrix().transform_box(*bbox);
+        let mut max_step: f64 = 0.0;
+        for pair in keyframes.windows(2) {
+            let mut previous = pair[0].transform.matrix();
+            for step in 1..=BOUNDS_STEPS {
+                let matrix = pair[0].transform.lerp(&pair[1].transform, step as f64 / BOUNDS_STEPS as f64).matrix();
+                result = result.union(matrix.transform_box(*bbox));
+                for corner in bbox.corners() {
+                    max_step = max_step.max((matrix.transform_point(corner) - previous.transform_point(corner)).len());
+                }
+                previous = matrix;
+            }
+        }
+        let d = Vec3::new(max_step, max_step, max_step);
+        Aabb::new(result.min - d, result.max + d)
+    }
+    pub fn is_animated(&self) -> bool {
+        !self.keyframes.is_empty()
+    }
+    pub fn transform(&self) -> Mat4 {
+        self.to_world
+    }
+    pub fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+    pub fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let animated;
+        let (to_world, to_object) = if self.keyframes.is_empty() {
+            (&self.to_world, &self.to_object)
+        } else {
+            let to_world = interpolate(&self.keyframes, ray.time).matrix();
+            animated = (to_world, to_world.inverse()?);
+            (&animated.0, &animated.1)
+        };
+
+        // not normalized, so `t` means the same in both spaces
+        let local = Ray::with_time(to_object.transform_point(ray.origin), to_object.transform_vector(ray.direction), ray.time);
+        let mut rec = self.object.hit(&local, ray_t)?;
+        rec.p = to_world.transform_point(rec.p);
+        // normals transform with the inverse transpose, which keeps them facing the ray
+        let m = &to_object.m;
+        let n = rec.normal;
+        rec.normal = Vec3::new(
+            m[0][0] * n.x + m[1][0] * n.y + m[2][0] * n.z,
+            m[0][1] * n.x + m[1][1] * n.y + m[2][1] * n.z,
+            m[0][2] * n.x + m[1][2] * n.y + m[2][2] * n.z,
+        ).unit();
+        Some(rec)
+    }
+    /// The object's density for the matching direction in object space, times
+    /// the change in solid angle the transform causes there. Light sampling
+    /// only looks at instances that don't move.
+    pub fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f64 {
+        let local = self.to_object.transform_vector(direction.unit());
+        let local_len = local.len();
+        let pdf = self.object.pdf_value(self.to_object.transform_point(origin), local);
+        // solid angle grows by |det M| / |M w|^3 mapping unit direction w through M
+        pdf * self.to_object.determinant3().abs() / local_len.powi(3)
+    }
+    pub fn sample(&self, origin: Vec3, uv: (f64, f64)) -> Vec3 {
+        self.to_world.transform_vector(self.object.sample(self.to_object.transform_point(origin), uv))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::*;
+    use crate::{bvh::Bvh, color::Color, hittable_list::HittableList, light::{is_hit_on, Light}, material::{DiffuseLight, Lambertian}, quad::Quad, sampler::{sample_unit_sphere, Sampler, SamplerKind}, sphere::Sphere, transform::Trs};
+
+    /// A unit sphere squashed and moved by an instance is hit where the
+    /// equivalent ellipsoid would be, with the ellipsoid's normal
+    #[test]
+    fn scaled_sphere() {
+        let sphere = Sphere::new(Vec3::zero(), 1.0, Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let transform = Mat4::translation(Vec3::new(0.0, 0.0, -5.0)) * Mat4::scale(Vec3::new(2.0, 1.0, 1.0));
+        let instance = Instance::new(sphere, transform);
+
+        let ray = Ray::new(Vec3::new(-10.0, 0.0, -5.0), Vec3::new(2.0, 0.0, 0.0));
+        let rec = instance.hit(&ray, Interval::new(0.001, f64::INFINITY)).unwrap();
+        assert!((rec.t - 4.0).abs() < 1e-9);
+        assert!((rec.p - Vec3::new(-2.0, 0.0, -5.0)).len() < 1e-9);
+        assert!((rec.normal - Vec3::new(-1.0, 0.0, 0.0)).len() < 1e-9);
+
+        // off axis the normal of x^2/4 + y^2 = 1 at (-sqrt 2, sqrt 0.5) is along (x/4, y)
+        let p = Vec3::new(-(2f64.sqrt()), 0.5f64.sqrt(), -5.0);
+        let rec = instance.hit(&Ray::new(Vec3::new(-10.0, p.y, -5.0), Vec3::new(1.0, 0.0, 0.0)), Interval::new(0.001, f64::INFINITY)).unwrap();
+        assert!((rec.p - p).len() < 1e-9, "{:?}", rec.p);
+        assert!((rec.normal - Vec3::new(p.x / 4.0, p.y, 0.0).unit()).len() < 1e-9);
+
+        let bbox = instance.bounding_box();
+        assert!((bbox.min - Vec3::new(-2.0, -1.0, -6.0)).len() < 1e-9 && (bbox.max - Vec3::new(2.0, 1.0, -4.0)).len() < 1e-9);
+    }
+
+    /// Keyframed instances and moving spheres are hit where they are at the
+    /// ray's time, and stay inside their bounding boxes all the while
+    #[test]
+    fn motion_follows_ray_time() {
+        let material = Lambertian::new(Color::new(0.5, 0.5, 0.5));
+        let keyframes = vec![
+            Keyframe { time: 1.0, transform: Trs { translate: Vec3::new(0.0, 2.0, -5.0), ..Trs::default() } },
+            Keyframe { time: 0.0, transform: Trs { translate: Vec3::new(0.0, 0.0, -5.0), ..Trs::default() } },
+        ];
+        let animated = Instance::animated(Sphere::new(Vec3::zero(), 1.0, material.clone()), keyframes);
+        let moving = Sphere::moving(Vec3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 2.0, -5.0), 1.0, material);
+
+        for object in [&animated, &moving] {
+            let bbox = object.bounding_box();
+            for time in [0.0, 0.25, 0.5, 1.0] {
+                let center = Vec3::new(0.0, 2.0 * time, -5.0);
+                let ray = Ray::with_time(Vec3::new(0.0, center.y, 0.0), Vec3::new(0.0, 0.0, -1.0), time);
+                let rec = object.hit(&ray, Interval::new(0.001, f64::INFINITY)).unwrap();
+                assert!((rec.p - (center + Vec3::new(0.0, 0.0, 1.0))).len() < 1e-9, "{time}: {:?}", rec.p);
+                assert!((rec.normal - Vec3::new(0.0, 0.0, 1.0)).len() < 1e-9);
+                assert!(bbox.min.y <= center.y - 1.0 && center.y + 1.0 <= bbox.max.y);
+            }
+            // the sphere has moved out of the way of this ray by the end of the frame
+            let ray = |time| Ray::with_time(Vec3::new(0.0, -0.5, 0.0), Vec3::new(0.0, 0.0, -1.0), time);
+            assert!(object.hit(&ray(0.0), Interval::new(0.001, f64::INFINITY)).is_some());
+            assert!(object.hit(&ray(1.0), Interval::new(0.001, f64::INFINITY)).is_none());
+        }
+    }
+
+    /// Light sampling through a non-uniform scale still has a density that
+    /// integrates to one and agrees with where the samples land
+    #[test]
+    fn pdf_integrates_to_one() {
+        let sphere = Sphere::new(Vec3::zero(), 1.0, Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let transform = Mat4::translation(Vec3::new(0.5, 1.0, -4.0)) * Mat4::rotation(Vec3::new(1.0, 1.0, 0.0), 40.0) * Mat4::scale(Vec3::new(2.0, 0.5, 1.0));
+        let instance = Instance::new(sphere, transform);
+        let origin = Vec3::new(0.0, 0.0, 0.0);
+
+        let mut sampler = Sampler::new(SamplerKind::Sobol, 1 << 16, 9, 0);
+        let mut integral = 0.0;
+        for i in 0..1 << 16 {
+            sampler.start_sample(i);
+            integral += instance.pdf_value(origin, sample_unit_sphere(sampler.get_2d())) * 4.0 * PI / (1 << 16) as f64;
+
+            let direction = instance.sample(origin, sampler.get_2d());
+            assert!(instance.hit(&Ray::new(origin, direction), Interval::new(0.001, f64::INFINITY)).is_some());
+        }
+        assert!((integral - 1.0).abs() < 0.01, "{integral}");
+    }
+
+    /// An emissive face inside a transformed group is a light where the
+    /// instance puts it, one that moves isn't
+    #[test]
+    fn emitters_in_groups() {
+        let mut model = HittableList::new();
+        model.add(Quad::new(Vec3::zero(), Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0), Lambertian::new(Color::new(0.5, 0.5, 0.5))));
+        model.add(Quad::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 0.0, 1.0), Vec3::new(1.0, 0.0, 0.0), DiffuseLight::new(Color::new(4.0, 4.0, 4.0))));
+        let model = Arc::new(Hittable::Bvh(Arc::new(Bvh::new(model))));
+
+        let mut world = HittableList::new();
+        world.add(Instance::new(model.clone(), Mat4::translation(Vec3::new(0.0, 2.0, 0.0)) * Mat4::scale(Vec3::new(2.0, 1.0, 2.0))));
+        let keyframes = [-5.0, -10.0].into_iter().enumerate()
+            .map(|(i, x)| Keyframe { time: i as f64, transform: Trs { translate: Vec3::new(x, 0.0, 0.0), ..Trs::default() } })
+            .collect();
+        world.add(Instance::animated(model, keyframes));
+        let lights = world.lights().to_vec();
+        let [Light::Area(emitter)] = lights.as_slice() else {
+            panic!("expected one light, found {}", lights.len());
+        };
+
+        // the light now spans x and z from 0 to 2 at a height of 3, above the floor at 2
+        let origin = Vec3::new(1.0, 2.5, 1.0);
+        let direction = emitter.sample(origin, (0.5, 0.5));
+        assert!((direction - Vec3::new(0.0, 0.5, 0.0)).len() < 1e-9, "{direction:?}");
+        let world = Bvh::new(world);
+        let ray = Ray::new(origin, direction);
+        let rec = world.hit(&ray, Interval::new(0.001, f64::INFINITY)).unwrap();
+        assert!(rec.mat.is_emissive() && is_hit_on(emitter, &ray, &rec));
+        // a 2 by 2 square half a unit away, straight on
+        assert!((emitter.pdf_value(origin, direction) - 0.25 / 4.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,345 @@
+use std::f64::consts::PI;
+
+use crate::{aabb::Aabb, color::Color, hittable::{HitRecord, Hittable}, interval::Interval, onb::Onb, ray::Ray, utils::degrees_to_radians, vec3::Vec3};
+
+/// Anything direct lighting samples
+#[derive(Clone)]
+pub enum Light {
+    /// An object with an emissive material, found by rays as well as sampled
+    Area(Hittable),
+    Point(PointLight),
+    Spot(SpotLight),
+    Directional(DirectionalLight),
+}
+
+/// Light arriving at a point from a punctual light
+pub struct LightSample {
+    /// Unit vector from the point towards the light
+    pub direction: Vec3,
+    /// How far the shadow ray has to reach, infinite for directional lights
+    pub distance: f64,
+    /// Incident light divided by the density of `direction`, assuming nothing is in the way
+    pub radiance: Color,
+}
+
+impl Light {
+    /// Samples a punctual light as seen from `origin`. Area lights are
+    /// sampled through their `Hittable` instead and return `None`, as do
+    /// points a light doesn't reach.
+    pub fn sample(&self, origin: Vec3, uv: (f64, f64)) -> Option<LightSample> {
+        match self {
+            Light::Area(_) => None,
+            Light::Point(light) => light.sample(origin),
+            Light::Spot(light) => light.sample(origin),
+            Light::Directional(light) => light.sample(uv),
+        }
+    }
+}
+
+const MAX_LEAF_SIZE: usize = 4;
+
+/// Picks the light direct lighting samples at each vertex.
+///
+/// Punctual lights get an equal share each, there is no telling how a
+/// directional light compares to the others without the size of the scene.
+/// Area lights split the rest in proportion to their power, so dim or small
+/// emitters don't take samples from the ones lighting the scene. They are
+/// also kept in a tree of their boxes, to find the one a ray hit without
+/// going through all of them.
+pub struct LightSampler {
+    lights: Vec<Light>,
+    /// Probability of picking each light
+    pmf: Vec<f64>,
+    /// Running sum of `pmf`
+    cdf: Vec<f64>,
+    nodes: Vec<LightNode>,
+    /// Indices of the area lights, in the order the leaves refer to them
+    areas: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct LightNode {
+    bbox: Aabb,
+    /// Leaf: index of the first area light. Interior: index of the second
+    /// child, the first child is always stored right after its parent.
+    offset: usize,
+    /// Number of area lights in a leaf, zero for interior nodes
+    count: usize,
+}
+
+impl LightSampler {
+    pub fn new(lights: &[Light]) -> Self {
+        let mut areas: Vec<usize> = (0..lights.len()).filter(|&i| matches!(lights[i], Light::Area(_))).collect();
+
+        // bounding boxes make a rough stand in for the surface area, half of
+        // a box's area is a flat shape's area or near a sphere's
+        let power: Vec<f64> = lights.iter()
+            .map(|light| match light {
+                Light::Area(emitter) => {
+                    let emission = emitter.material().map_or(0.0, |mat| mat.emission().luminance());
+                    emission * emitter.bounding_box().surface_area() / 2.0
+                },
+                _ => 0.0,
+            })
+            .collect();
+        let total_power: f64 = power.iter().sum();
+        let area_share = areas.len() as f64 / lights.len().max(1) as f64;
+        let pmf: Vec<f64> = lights.iter().zip(&power)
+            .map(|(light, &power)| match light {
+                Light::Area(_) if total_power > 0.0 => area_share * power / total_power,
+                _ => 1.0 / lights.len() as f64,
+            })
+            .collect();
+        let cdf = pmf.iter().scan(0.0, |sum, p| {
+            *sum += p;
+            Some(*sum)
+        }).collect();
+
+        let mut sampler = Self { lights: lights.to_vec(), pmf, cdf, nodes: Vec::new(), areas: Vec::new() };
+        if !areas.is_empty() {
+            sampler.build(&mut areas, 0);
+        }
+        sampler.areas = areas;
+        sampler
+    }
+
+    /// Builds the subtree for `areas`, which start at `first` in the final order
+    fn build(&mut self, areas: &mut [usize], first: usize) {
+        let bbox_of = |i: usize| match &self.lights[i] {
+            Light::Area(emitter) => emitter.bounding_box(),
+            _ => unreachable!(),
+        };
+        let bbox = areas.iter().fold(Aabb::empty(), |bbox, &i| bbox.union(bbox_of(i)));
+        // hit points are rounded, and may land just outside a tight box
+        let margin = Vec3::new(1.0, 1.0, 1.0) * ((bbox.max - bbox.min).len() * 1e-6);
+        let bbox = Aabb::new(bbox.min - margin, bbox.max + margin);
+        let node = self.nodes.len();
+        self.nodes.push(LightNode { bbox, offset: first, count: areas.len() });
+        if areas.len() <= MAX_LEAF_SIZE {
+            return;
+        }
+
+        let axis = areas.iter().fold(Aabb::empty(), |b, &i| b.grow(bbox_of(i).centroid())).longest_axis();
+        let mid = areas.len() / 2;
+        areas.select_nth_unstable_by(mid, |&a, &b| bbox_of(a).centroid()[axis].total_cmp(&bbox_of(b).centroid()[axis]));
+        let (left, right) = areas.split_at_mut(mid);
+        self.build(left, first);
+        self.nodes[node] = LightNode { bbox, offset: self.nodes.len(), count: 0 };
+        self.build(right, first + mid);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lights.is_empty()
+    }
+
+    /// Picks a light with `u` in [0, 1), returning it with the probability it had
+    pub fn pick(&self, u: f64) -> Option<(&Light, f64)> {
+        let index = self.cdf.partition_point(|&c| c <= u).min(self.lights.len().checked_sub(1)?);
+        Some((&self.lights[index], self.pmf[index]))
+    }
+
+    /// Density, per solid angle, of direct lighting sampling `ray`'s direction
+    /// from its origin and finding `rec`, counting only the area lights `rec` lies on
+    pub fn pdf(&self, ray: &Ray, rec: &HitRecord) -> f64 {
+        let mut pdf = 0.0;
+        let mut stack = Vec::new();
+        if !self.nodes.is_empty() {
+            stack.push(0);
+        }
+        while let Some(index) = stack.pop() {
+            let node = self.nodes[index];
+            if !node.bbox.contains(rec.p) {
+                continue;
+            }
+            if node.count == 0 {
+                stack.extend([index + 1, node.offset]);
+                continue;
+            }
+            for &light in &self.areas[node.offset..node.offset + node.count] {
+                let Light::Area(emitter) = &self.lights[light] else { unreachable!() };
+                if is_hit_on(emitter, ray, rec) {
+                    pdf += self.pmf[light] * emitter.pdf_value(ray.origin, ray.direction);
+                }
+            }
+        }
+        pdf
+    }
+}
+
+/// Whether `rec`, found along `ray`, lies on `emitter`
+pub fn is_hit_on(emitter: &Hittable, ray: &Ray, rec: &HitRecord) -> bool {
+    emitter.hit(ray, Interval::new(0.001, f64::INFINITY)).is_some_and(|hit| (hit.t - rec.t).abs() <= 1e-9 * rec.t.max(1.0))
+}
+
+/// Shines equally in all directions from a single point
+#[derive(Debug, Clone, Copy)]
+pub struct PointLight {
+    pub position: Vec3,
+    /// Radiant intensity, the light arriving one unit away
+    pub intensity: Color,
+}
+
+impl PointLight {
+    pub fn new(position: Vec3, intensity: Color) -> Light {
+        Light::Point(PointLight { position, intensity })
+    }
+    fn sample(&self, origin: Vec3) -> Option<LightSample> {
+        let to_light = self.position - origin;
+        let distance = to_light.len();
+        if distance == 0.0 {
+            return None;
+        }
+        Some(LightSample { direction: to_light / distance, distance, radiance: self.intensity / (distance * distance) })
+    }
+}
+
+/// A point light restricted to a cone, fading out between the inner and outer angle
+#[derive(Debug, Clone, Copy)]
+pub struct SpotLight {
+    pub position: Vec3,
+    /// Unit vector the cone is centered on
+    pub direction: Vec3,
+    /// Radiant intensity inside the inner cone
+    pub intensity: Color,
+    cos_inner: f64,
+    cos_outer: f64,
+}
+
+impl SpotLight {
+    /// The angles are measured from the cone axis to its edge, in degrees
+    pub fn new(position: Vec3, look_at: Vec3, intensity: Color, inner_angle: f64, outer_angle: f64) -> Light {
+        let cos_outer = degrees_to_radians(outer_angle).cos();
+        let cos_inner = degrees_to_radians(inner_angle.min(outer_angle)).cos();
+        Light::Spot(SpotLight { position, direction: (look_at - position).unit(), intensity, cos_inner, cos_outer })
+    }
+    fn sample(&self, origin: Vec3) -> Option<LightSample> {
+        let to_light = self.position - origin;
+        let distance = to_light.len();
+        if distance == 0.0 {
+            return None;
+        }
+        let direction = to_light / distance;
+
+        let falloff = self.falloff(-direction.dot(self.direction));
+        if falloff == 0.0 {
+            return None;
+        }
+        Some(LightSample { direction, distance, radiance: self.intensity * (falloff / (distance * distance)) })
+    }
+    /// Smoothstep from the outer to the inner cone
+    fn falloff(&self, cos_theta: f64) -> f64 {
+        if self.cos_inner <= self.cos_outer {
+            return if cos_theta >= self.cos_outer {1.0} else {0.0};
+        }
+        let t = ((cos_theta - self.cos_outer) / (self.cos_inner - self.cos_outer)).clamp(0.0, 1.0);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+/// Parallel light from very far away, like the sun.
+///
+/// With an angular diameter the light comes from a small disk of the sky and
+/// casts soft shadows. The disk itself is never seen by camera or reflected
+/// rays, it only lights the scene.
+#[derive(Debug, Clone, Copy)]
+pub struct DirectionalLight {
+    /// Unit vector pointing from the scene towards the light
+    pub to_light: Vec3,
+    /// Irradiance on a surface facing the light
+    pub irradiance: Color,
+    cos_half_angle: f64,
+}
+
+impl DirectionalLight {
+    /// `direction` is the way the light travels, `angular_diameter` in degrees (0.53 for the sun)
+    pub fn new(direction: Vec3, irradiance: Color, angular_diameter: f64) -> Light {
+        let cos_half_angle = degrees_to_radians(angular_diameter / 2.0).cos();
+        Light::Directional(DirectionalLight { to_light: -direction.unit(), irradiance, cos_half_angle })
+    }
+    fn sample(&self, (u, v): (f64, f64)) -> Option<LightSample> {
+        // uniform over the cone of the disk, irradiance / solid angle / pdf cancels to the irradiance
+        let z = 1.0 + u * (self.cos_half_angle - 1.0);
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        let phi = 2.0 * PI * v;
+        let direction = Onb::new(self.to_light).to_world(Vec3::new(r * phi.cos(), r * phi.sin(), z));
+        Some(LightSample { direction, distance: f64::INFINITY, radiance: self.irradiance })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn point_light_falloff() {
+        let light = PointLight::new(Vec3::new(0.0, 4.0, 0.0), Color::new(8.0, 4.0, 2.0));
+        let near = light.sample(Vec3::new(0.0, 2.0, 0.0), (0.5, 0.5)).unwrap();
+        assert!((near.direction - Vec3::new(0.0, 1.0, 0.0)).len() < 1e-12 && near.distance == 2.0);
+        assert_eq!((near.radiance.r, near.radiance.g, near.radiance.b), (2.0, 1.0, 0.5));
+
+        let far = light.sample(Vec3::new(3.0, 0.0, 0.0), (0.5, 0.5)).unwrap();
+        assert!((far.distance - 5.0).abs() < 1e-12);
+        assert!((far.radiance.r - 8.0 / 25.0).abs() < 1e-12);
+        assert!(light.sample(Vec3::new(0.0, 4.0, 0.0), (0.5, 0.5)).is_none());
+    }
+
+    /// Full intensity inside the inner cone, nothing outside the outer one and
+    /// a smoothstep in between
+    #[test]
+    fn spot_light_cone() {
+        let light = SpotLight::new(Vec3::zero(), Vec3::new(0.0, -1.0, 0.0), Color::new(1.0, 1.0, 1.0), 20.0, 40.0);
+        let at_angle = |degrees: f64| {
+            let (sin, cos) = degrees_to_radians(degrees).sin_cos();
+            light.sample(Vec3::new(sin, -cos, 0.0), (0.5, 0.5)).map_or(0.0, |sample| sample.radiance.r)
+        };
+        assert!((at_angle(0.0) - 1.0).abs() < 1e-12 && (at_angle(19.9) - 1.0).abs() < 1e-12);
+        assert_eq!(at_angle(40.1), 0.0);
+        assert_eq!(at_angle(90.0), 0.0);
+
+        let (cos_inner, cos_outer) = (degrees_to_radians(20.0).cos(), degrees_to_radians(40.0).cos());
+        let mut previous = 1.0;
+        for degrees in (21..40).map(f64::from) {
+            let t = (degrees_to_radians(degrees).cos() - cos_outer) / (cos_inner - cos_outer);
+            let value = at_angle(degrees);
+            assert!((value - t * t * (3.0 - 2.0 * t)).abs() < 1e-12, "{degrees}");
+            assert!(value < previous && value > 0.0);
+            previous = value;
+        }
+
+        // equal angles cut off hard
+        let hard = SpotLight::new(Vec3::zero(), Vec3::new(0.0, -1.0, 0.0), Color::new(1.0, 1.0, 1.0), 30.0, 30.0);
+        assert!(hard.sample(Vec3::new(0.0, -1.0, 0.0), (0.5, 0.5)).is_some());
+        let (sin, cos) = degrees_to_radians(30.5).sin_cos();
+        assert!(hard.sample(Vec3::new(sin, -cos, 0.0), (0.5, 0.5)).is_none());
+    }
+
+    /// Directions stay inside the sun's disk and spread across it, each
+    /// carrying the full irradiance with nothing in the way
+    #[test]
+    fn directional_light_cone() {
+        let irradiance = Color::new(3.0, 2.0, 1.0);
+        let light = DirectionalLight::new(Vec3::new(1.0, -1.0, 0.0), irradiance, 10.0);
+        let to_light = Vec3::new(-1.0, 1.0, 0.0).unit();
+        let cos_half_angle = degrees_to_radians(5.0).cos();
+
+        let mut widest: f64 = 1.0;
+        for i in 0..32 {
+            for j in 0..32 {
+                let uv = ((i as f64 + 0.5) / 32.0, (j as f64 + 0.5) / 32.0);
+                let sample = light.sample(Vec3::new(7.0, -3.0, 2.0), uv).unwrap();
+                assert!((sample.direction.len() - 1.0).abs() < 1e-12);
+                let cos = sample.direction.dot(to_light);
+                assert!(cos >= cos_half_angle - 1e-12, "{cos}");
+                widest = widest.min(cos);
+                assert!(sample.distance.is_infinite());
+                assert_eq!((sample.radiance.r, sample.radiance.g, sample.radiance.b), (irradiance.r, irradiance.g, irradiance.b));
+            }
+        }
+        assert!(widest < degrees_to_radians(4.5).cos());
+
+        // without a diameter every sample is the light's direction
+        let sharp = DirectionalLight::new(Vec3::new(1.0, -1.0, 0.0), irradiance, 0.0);
+        let sample = sharp.sample(Vec3::zero(), (0.3, 0.8)).unwrap();
+        assert!((sample.direction - to_light).len() < 1e-12);
+    }
+}
@@ -1,4 +1,5 @@
 #![allow(unused)]
+#![allow(clippy::new_ret_no_self)]
 
 mod vec3;
 mod color;
@@ -10,45 +11,88 @@ mod interval;
 mod camera;
 mod utils;
 mod material;
+mod aabb;
+mod bvh;
+mod triangle;
+mod mesh;
+mod obj;
+mod hdr;
+mod background;
+mod output;
+mod tonemap;
+mod scene;
+mod scenes;
+mod cli;
+mod sampler;
+mod onb;
+mod light;
+mod texture;
+mod microfacet;
+mod principled;
+mod medium;
+mod transform;
+mod instance;
+mod quad;
+mod plane;
+mod quadric;
+mod torus;
+mod csg;
+#[cfg(test)]
+mod test_utils;
 
-use color::Color;
-use hittable_list::HittableList;
-use material::{Dielectric, Lambertian, Material, Metal};
-use sphere::Sphere;
-use vec3::Vec3;
-use camera::CameraBuilder;
+use std::{env, path::PathBuf, process};
+
+use cli::{Args, SceneSource, USAGE};
+use scene::load_scene;
 
 
 fn main() {
-    let mut world = HittableList::new();
-    let matte_blue = Lambertian::new(Color::new(0.1, 0.2, 0.5));
-    let matte_yellow = Lambertian::new(Color::new(0.8, 0.7, 0.0));
-    let matte_white = Lambertian::new(Color::new(1.0, 1.0, 1.0));
-    let metal = Metal::new(Color::new(0.8, 0.8, 0.8), 0.0);
-    let metal_fuzzy = Metal::new(Color::new(0.8, 0.8, 0.8), 1.0);
-    let glass = Dielectric::new(1.5);
-    let glass_inside = Dielectric::new(0.75);
-
-    world.add(Sphere::new(Vec3::new( 0.0, -100.5, -1.0), 100.0, matte_yellow)); // floor
-    world.add(Sphere::new(Vec3::new( 0.0, 0.0,    -1.2), 0.5,   matte_blue));   // center
-    world.add(Sphere::new(Vec3::new(-1.0, 0.0,    -1.0), 0.5,   glass));        // left
-    world.add(Sphere::new(Vec3::new(-1.0, 0.0,    -1.0), 0.4,   glass_inside)); // left (inside)
-    world.add(Sphere::new(Vec3::new( 1.0, 0.0,    -1.0), 0.5,   metal_fuzzy));  // right
-
-    let camera = CameraBuilder {
-        camera_center: Vec3::new(-2.0, 2.0, 1.0),
-        look_at: Vec3::new(0.0, 0.0, -1.0),
-        image_width: 800,
-        aspect_ratio: 16.0/9.0,
-        vfov: 50.0,
-        depth_of_field_blur: 0.15,
-        thread_count: 32,
-        max_depth: 50,
-        samples_per_pixel: 100,
-        ..Default::default()
-    }.to_camera();
-
-    camera.render(world);
-
-    println!("Done!");
+    let args = match Args::parse(env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("error: {e}\n\n{USAGE}");
+            process::exit(2);
+        },
+    };
+    if args.help {
+        print!("{USAGE}");
+        return;
+    }
+
+    let scene = args.scene.clone().unwrap_or(SceneSource::Builtin(String::from("spheres")));
+    let (mut camera, world, scene_output) = match scene {
+        SceneSource::Builtin(name) => {
+            let (camera, world) = scenes::builtin(&name).unwrap();
+            (camera, world, None)
+        },
+        SceneSource::File(path) => match load_scene(&path) {
+            Ok(scene) => (scene.camera, scene.world, scene.output),
+            Err(e) => {
+                eprintln!("error: {e}");
+                process::exit(1);
+            },
+        },
+    };
+
+    if let Err(e) = args.apply(&mut camera) {
+        eprintln!("error: {e}");
+        process::exit(2);
+    }
+    let output = args.output.clone().or(scene_output).unwrap_or(PathBuf::from("image.png"));
+
+    let camera = camera.to_camera();
+    let (width, height, spp) = (camera.image_width(), camera.image_hight(), camera.samples_per_pixel());
+
+    match camera.render(world, &output) {
+        Ok(stats) => {
+            println!(
+                "Rendered {}x{} at {} spp to {} in {:.2?}, {} rays at {:.2} Mrays/s",
+                width, height, spp, output.display(), stats.elapsed, stats.rays, stats.rays_per_second() / 1e6,
+            );
+        },
+        Err(e) => {
+            eprintln!("error: {e}");
+            process::exit(1);
+        },
+    }
 }
@@ -0,0 +1,198 @@
+use std::f64::consts::PI;
+
+use crate::{aabb::Aabb, color::Color, hittable::{HitRecord, Hittable}, interval::Interval, material::{Material, Volume}, onb::Onb, ray::Ray, utils::mix_bits, vec3::Vec3};
+
+/// How the light scattered in a medium is spread over directions
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PhaseFunction {
+    Isotropic,
+    /// Asymmetry `g` in (-1, 1): positive scatters forward like haze, negative
+    /// backward, 0 is isotropic
+    HenyeyGreenstein(f64),
+}
+
+impl PhaseFunction {
+    /// Picks Henyey-Greenstein only when it isn't isotropic anyway
+    pub fn from_anisotropy(g: f64) -> Self {
+        if g.abs() < 1e-3 {PhaseFunction::Isotropic} else {PhaseFunction::HenyeyGreenstein(g.clamp(-0.99, 0.99))}
+    }
+    /// Density of scattering from travelling along `forward` to travelling along `direction`, both unit vectors
+    pub fn value(self, forward: Vec3, direction: Vec3) -> f64 {
+        match self {
+            PhaseFunction::Isotropic => 1.0 / (4.0 * PI),
+            PhaseFunction::HenyeyGreenstein(g) => {
+                let denom = 1.0 + g * g - 2.0 * g * forward.dot(direction);
+                (1.0 - g * g) / (4.0 * PI * denom * denom.sqrt())
+            },
+        }
+    }
+    /// Samples a new unit direction with density `value`
+    pub fn sample(self, forward: Vec3, (u, v): (f64, f64)) -> Vec3 {
+        let cos_theta = match self {
+            PhaseFunction::Isotropic => 1.0 - 2.0 * u,
+            PhaseFunction::HenyeyGreenstein(g) => {
+                let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * u);
+                ((1.0 + g * g - s * s) / (2.0 * g)).clamp(-1.0, 1.0)
+            },
+        };
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * v;
+        Onb::new(forward).to_world(Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta))
+    }
+}
+
+/// A value in [0, 1) that only depends on the ray and `salt`.
+///
+/// Hittables don't get a sampler, so media draw their free flight distances
+/// from the ray itself. The ray was built from sampler values, so the result
+/// is as deterministic as the rest of the image.
+fn ray_random(ray: &Ray, salt: u64) -> f64 {
+    let bits = [ray.origin.x, ray.origin.y, ray.origin.z, ray.direction.x, ray.direction.y, ray.direction.z]
+        .iter()
+        .fold(mix_bits(salt), |hash, x| mix_bits(hash ^ x.to_bits()));
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Distance in units of the ray's `t` travelled before the first collision in
+/// a medium of `density`, infinite without one
+fn free_flight(ray: &Ray, density: f64, u: f64) -> f64 {
+    let speed = ray.direction.len();
+    if density <= 0.0 || speed == 0.0 {
+        return f64::INFINITY;
+    }
+    -(1.0 - u).ln() / (density * speed)
+}
+
+/// A homogeneous cloud of particles filling a boundary, for smoke and
+/// subsurface-looking blobs. Rays passing through scatter at a random depth
+/// with a probability that grows with the distance travelled inside.
+///
+/// The boundary has to be convex, only the first stretch of a ray inside it is considered.
+#[derive(Clone)]
+pub struct ConstantMedium {
+    pub boundary: Box<Hittable>,
+    /// Collisions per unit length
+    pub density: f64,
+    /// A `Volume` material
+    pub phase: Material,
+}
+
+impl ConstantMedium {
+    pub fn new(boundary: Hittable, density: f64, albedo: Color, phase: PhaseFunction) -> Hittable {
+        Hittable::ConstantMedium(Box::new(ConstantMedium { boundary: Box::new(boundary), density, phase: Volume::new(albedo, phase) }))
+    }
+    pub fn bounding_box(&self) -> Aabb {
+        self.boundary.bounding_box()
+    }
+    pub fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let enter = self.boundary.hit(ray, Interval::everything())?;
+        let exit = self.boundary.hit(ray, Interval::new(enter.t + 0.0001, f64::INFINITY))?;
+
+        let t_enter = enter.t.max(ray_t.min).max(0.0);
+        let t_exit = exit.t.min(ray_t.max);
+        if t_enter >= t_exit {
+            return None;
+        }
+
+        let t = t_enter + free_flight(ray, self.density, ray_random(ray, self.density.to_bits()));
+        if t >= t_exit {
+            return None;
+        }
+        // the normal is arbitrary, phase functions don't use it
+        Some(HitRecord { p: ray.at(t), normal: -ray.direction.unit(), t, front_face: true, mat: &self.phase, u: 0.0, v: 0.0 })
+    }
+}
+
+/// A homogeneous medium filling the whole scene below `height`, for distance
+/// fog and light shafts. Rays leaving the layer upwards escape it, so
+/// directional lights need a finite `height` to shine through.
+#[derive(Debug, Clone)]
+pub struct Atmosphere {
+    /// Collisions per unit length
+    pub density: f64,
+    pub height: f64,
+    /// A `Volume` material
+    pub phase: Material,
+}
+
+impl Atmosphere {
+    pub fn new(density: f64, albedo: Color, phase: PhaseFunction, height: f64) -> Self {
+        Atmosphere { density, height, phase: Volume::new(albedo, phase) }
+    }
+
+    /// Part of `ray` between 0 and `t_max` that lies inside the layer
+    fn span(&self, ray: &Ray, t_max: f64) -> Option<(f64, f64)> {
+        let (start, end) = if ray.direction.y == 0.0 {
+            if ray.origin.y < self.height {(0.0, t_max)} else {return None}
+        } else {
+            let t = (self.height - ray.origin.y) / ray.direction.y;
+            if ray.direction.y > 0.0 {(0.0, t.min(t_max))} else {(t.max(0.0), t_max)}
+        };
+        (start < end).then_some((start, end))
+    }
+
+    /// Samples where `ray` collides with the atmosphere before `t_max`, `u` in [0, 1)
+    pub fn interaction(&self, ray: &Ray, t_max: f64, u: f64) -> Option<HitRecord<'_>> {
+        let (start, end) = self.span(ray, t_max)?;
+        let t = start + free_flight(ray, self.density, u);
+        if t >= end {
+            return None;
+        }
+        Some(HitRecord { p: ray.at(t), normal: -ray.direction.unit(), t, front_face: true, mat: &self.phase, u: 0.0, v: 0.0 })
+    }
+
+    /// Fraction of light making it along `ray` from 0 to `t_max` without colliding
+    pub fn transmittance(&self, ray: &Ray, t_max: f64) -> f64 {
+        match self.span(ray, t_max) {
+            Some((start, end)) => (-self.density * ray.direction.len() * (end - start)).exp(),
+            None => 1.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{sampler::{sample_unit_sphere, Sampler, SamplerKind}, sphere::Sphere};
+
+    /// Sampled directions follow `value`, compared on a histogram of the
+    /// cosine with the forward direction
+    #[test]
+    fn henyey_greenstein_sample_matches_value() {
+        const BINS: usize = 10;
+        const COUNT: usize = 1 << 16;
+        let forward = Vec3::new(0.0, 0.6, 0.8);
+        let bin = |w: Vec3| (((forward.dot(w) + 1.0) / 2.0 * BINS as f64) as usize).min(BINS - 1);
+
+        for phase in [PhaseFunction::HenyeyGreenstein(0.7), PhaseFunction::HenyeyGreenstein(-0.3), PhaseFunction::Isotropic] {
+            let mut sampled = [0.0; BINS];
+            let mut expected = [0.0; BINS];
+            let mut sampler = Sampler::new(SamplerKind::Sobol, COUNT, 3, 0);
+            for i in 0..COUNT {
+                sampler.start_sample(i);
+                sampled[bin(phase.sample(forward, sampler.get_2d()))] += 1.0 / COUNT as f64;
+                let uniform = sample_unit_sphere(sampler.get_2d());
+                expected[bin(uniform)] += phase.value(forward, uniform) * 4.0 * PI / COUNT as f64;
+            }
+            for (s, e) in sampled.iter().zip(&expected) {
+                assert!((s - e).abs() < 0.01, "{phase:?}: sampled {sampled:?}, expected {expected:?}");
+            }
+        }
+    }
+
+    /// The fraction of rays getting through a medium matches Beer-Lambert
+    #[test]
+    fn constant_medium_transmittance() {
+        let medium = ConstantMedium::new(Sphere::new(Vec3::zero(), 1.0, Volume::new(Color::new(1.0, 1.0, 1.0), PhaseFunction::Isotropic)), 0.5, Color::new(1.0, 1.0, 1.0), PhaseFunction::Isotropic);
+        let count = 20000;
+        let passed = (0..count)
+            .filter(|&i| {
+                // slightly different rays through the center, all crossing 2 units of medium
+                let ray = Ray::new(Vec3::new(0.0, 0.0, -5.0 - i as f64 * 1e-6), Vec3::new(0.0, 0.0, 2.0));
+                medium.hit(&ray, Interval::new(0.001, f64::INFINITY)).is_none()
+            })
+            .count();
+        let expected = (-0.5f64 * 2.0).exp();
+        assert!((passed as f64 / count as f64 - expected).abs() < 0.015, "{passed} of {count}, expected {expected}");
+    }
+}
@@ -0,0 +1,159 @@
+use std::sync::Arc;
+
+use crate::{aabb::Aabb, hittable::{HitRecord, Hittable}, interval::Interval, material::Material, ray::Ray, triangle::{face_forward, intersect, sample_point, solid_angle_pdf}, vec3::{cross, Vec3}};
+
+/// One triangle of a mesh, every field indexes into the matching buffer of the mesh
+#[derive(Debug, Clone, Copy)]
+pub struct Face {
+    pub positions: [usize; 3],
+    pub normals: Option<[usize; 3]>,
+    pub uvs: Option<[usize; 3]>,
+}
+
+/// Indexed triangle mesh.
+///
+/// The vertex buffers and the material are stored once and shared by every
+/// triangle through an `Arc`, so a face only costs a pointer and an index.
+#[derive(Debug)]
+pub struct Mesh {
+    pub positions: Vec<Vec3>,
+    pub normals: Vec<Vec3>,
+    pub uvs: Vec<(f64, f64)>,
+    pub faces: Vec<Face>,
+    pub mat: Material,
+}
+
+impl Mesh {
+    pub fn new(positions: Vec<Vec3>, normals: Vec<Vec3>, uvs: Vec<(f64, f64)>, faces: Vec<Face>, matterial: Material) -> Self {
+        for face in &faces {
+            assert!(face.positions.iter().all(|&i| i < positions.len()), "Face position index out of range");
+            assert!(face.normals.is_none_or(|n| n.iter().all(|&i| i < normals.len())), "Face normal index out of range");
+            assert!(face.uvs.is_none_or(|n| n.iter().all(|&i| i < uvs.len())), "Face uv index out of range");
+        }
+        Self { positions, normals, uvs, faces, mat: matterial }
+    }
+
+    /// Splits the mesh into one hittable per face, ready to be added to a `HittableList`
+    pub fn into_triangles(self) -> Vec<Hittable> {
+        let mesh = Arc::new(self);
+        (0..mesh.faces.len()).map(|face| Hittable::MeshTriangle(MeshTriangle { mesh: mesh.clone(), face })).collect()
+    }
+}
+
+#[derive(Clone)]
+pub struct MeshTriangle {
+    pub mesh: Arc<Mesh>,
+    pub face: usize,
+}
+
+impl MeshTriangle {
+    fn vertices(&self) -> (Vec3, Vec3, Vec3) {
+        let [a, b, c] = self.mesh.faces[self.face].positions;
+        (self.mesh.positions[a], self.mesh.positions[b], self.mesh.positions[c])
+    }
+
+    pub fn bounding_box(&self) -> Aabb {
+        let (v0, v1, v2) = self.vertices();
+        Aabb::from_points(v0, v1).grow(v2).pad(1e-4)
+    }
+
+    pub fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let (v0, v1, v2) = self.vertices();
+        let (t, b1, b2) = intersect(ray, ray_t, v0, v1, v2)?;
+        let b0 = 1.0 - b1 - b2;
+
+        let face = &self.mesh.faces[self.face];
+        let geometric = cross(v1 - v0, v2 - v0).unit();
+
+        // smooth shading, falling back to the flat normal for degenerate interpolations
+        let shading = match face.normals {
+            Some([a, b, c]) => {
+                let n = self.mesh.normals[a] * b0 + self.mesh.normals[b] * b1 + self.mesh.normals[c] * b2;
+                if n.near_zero() {geometric} else {n.unit()}
+            },
+            None => geometric,
+        };
+        let (normal, front_face) = face_forward(ray, geometric, shading);
+
+        let (u, v) = match face.uvs {
+            Some([a, b, c]) => {
+                let (uv0, uv1, uv2) = (self.mesh.uvs[a], self.mesh.uvs[b], self.mesh.uvs[c]);
+                (uv0.0 * b0 + uv1.0 * b1 + uv2.0 * b2, uv0.1 * b0 + uv1.1 * b1 + uv2.1 * b2)
+            },
+            None => (b1, b2),
+        };
+
+        Some(HitRecord {
+            p: ray.at(t),
+            normal,
+            t,
+            front_face,
+            mat: &self.mesh.mat,
+            u,
+            v,
+        })
+    }
+    pub fn material(&self) -> &Material {
+        &self.mesh.mat
+    }
+    pub fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f64 {
+        let (v0, v1, v2) = self.vertices();
+        solid_angle_pdf(origin, direction, v0, v1, v2)
+    }
+    pub fn sample(&self, origin: Vec3, uv: (f64, f64)) -> Vec3 {
+        let (v0, v1, v2) = self.vertices();
+        sample_point(v0, v1, v2, uv) - origin
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{hit, material};
+
+    /// Two faces over the triangle (0, 0, 0), (1, 0, 0), (0, 1, 0), the first
+    /// with a different normal and UV at every corner, the second with
+    /// normals that cancel out halfway between its corners
+    fn triangles() -> Vec<Hittable> {
+        let positions = vec![Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0)];
+        let normals = vec![Vec3::new(0.0, 0.0, 1.0), Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 0.0, -1.0)];
+        let uvs = vec![(0.0, 0.0), (2.0, 0.0), (0.0, 4.0)];
+        let faces = vec![
+            Face { positions: [0, 1, 2], normals: Some([0, 1, 2]), uvs: Some([0, 1, 2]) },
+            Face { positions: [0, 1, 2], normals: Some([0, 3, 3]), uvs: None },
+        ];
+        Mesh::new(positions, normals, uvs, faces, material()).into_triangles()
+    }
+
+    /// Normals and UVs are blended from the corners with the barycentric weights
+    #[test]
+    fn interpolation() {
+        let triangles = triangles();
+        let expected = Vec3::new(0.25, 0.5, 0.25).unit();
+
+        let rec = hit(&triangles[0], Vec3::new(0.25, 0.5, 1.0), Vec3::new(0.0, 0.0, -1.0)).unwrap();
+        assert!((rec.t - 1.0).abs() < 1e-12 && rec.front_face);
+        assert!((rec.normal - expected).len() < 1e-12, "{:?}", rec.normal);
+        assert!((rec.u - 0.5).abs() < 1e-12 && (rec.v - 2.0).abs() < 1e-12);
+
+        // from behind, the smooth normal is flipped along with the face
+        let rec = hit(&triangles[0], Vec3::new(0.25, 0.5, -1.0), Vec3::new(0.0, 0.0, 1.0)).unwrap();
+        assert!(!rec.front_face && (rec.normal + expected).len() < 1e-12);
+    }
+
+    /// Without UVs the barycentric weights stand in for them, and normals
+    /// that cancel out fall back to the flat one
+    #[test]
+    fn fallbacks() {
+        let triangles = triangles();
+        let rec = hit(&triangles[1], Vec3::new(0.25, 0.25, 1.0), Vec3::new(0.0, 0.0, -1.0)).unwrap();
+        assert!((rec.u - 0.25).abs() < 1e-12 && (rec.v - 0.25).abs() < 1e-12);
+        assert!(rec.front_face && (rec.normal - Vec3::new(0.0, 0.0, 1.0)).len() < 1e-12);
+    }
+
+    /// Big meshes are mostly faces, which must not pay for the larger shapes
+    #[test]
+    fn compact_faces() {
+        assert!(size_of::<Hittable>() <= size_of::<MeshTriangle>() + size_of::<usize>(), "{}", size_of::<Hittable>());
+    }
+}
@@ -0,0 +1,199 @@
+use std::f64::consts::PI;
+
+use crate::{color::Color, vec3::{cross, Vec3}};
+
+// All directions here are in the local shading frame, the normal is +z.
+
+/// Roughness below which a surface is treated as perfectly smooth
+const SMOOTH_ALPHA: f64 = 1e-3;
+
+/// Isotropic GGX / Trowbridge-Reitz microfacet distribution
+#[derive(Debug, Clone, Copy)]
+pub struct TrowbridgeReitz {
+    pub alpha: f64,
+}
+
+impl TrowbridgeReitz {
+    /// `roughness` in [0, 1] is squared into the distribution's alpha, which
+    /// makes the perceived roughness change roughly linearly
+    pub fn new(roughness: f64) -> Self {
+        Self { alpha: roughness.clamp(0.0, 1.0).powi(2) }
+    }
+    /// So close to a mirror that the distribution can't be evaluated reliably
+    pub fn is_smooth(&self) -> bool {
+        self.alpha < SMOOTH_ALPHA
+    }
+    /// Density of microfacet normals `wm` per projected area
+    pub fn d(&self, wm: Vec3) -> f64 {
+        let cos2 = wm.z * wm.z;
+        if cos2 <= 0.0 {
+            return 0.0;
+        }
+        let alpha2 = self.alpha * self.alpha;
+        let denom = cos2 * (alpha2 - 1.0) + 1.0;
+        alpha2 / (PI * denom * denom)
+    }
+    fn lambda(&self, w: Vec3) -> f64 {
+        let cos2 = w.z * w.z;
+        if cos2 <= 0.0 {
+            return 0.0;
+        }
+        let tan2 = (1.0 - cos2).max(0.0) / cos2;
+        ((1.0 + self.alpha * self.alpha * tan2).sqrt() - 1.0) / 2.0
+    }
+    /// Smith masking of a single direction
+    pub fn g1(&self, w: Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+    /// Height correlated Smith masking-shadowing
+    pub fn g(&self, wo: Vec3, wi: Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+    /// Density of the normals `sample_wm` returns for `w`, the normals visible from `w`
+    pub fn pdf(&self, w: Vec3, wm: Vec3) -> f64 {
+        if w.z == 0.0 {
+            return 0.0;
+        }
+        self.g1(w) / w.z.abs() * self.d(wm) * w.dot(wm).abs()
+    }
+    /// Samples a microfacet normal visible from `w` (Heitz 2018)
+    pub fn sample_wm(&self, w: Vec3, (u, v): (f64, f64)) -> Vec3 {
+        // stretch to the configuration where the distribution is a hemisphere
+        let mut wh = Vec3::new(self.alpha * w.x, self.alpha * w.y, w.z).unit();
+        if wh.z < 0.0 {
+            wh = -wh;
+        }
+        let t1 = if wh.z < 0.99999 {cross(Vec3::new(0.0, 0.0, 1.0), wh).unit()} else {Vec3::new(1.0, 0.0, 0.0)};
+        let t2 = cross(wh, t1);
+
+        // uniform disk, warped towards the part of the hemisphere seen from `wh`
+        let r = u.sqrt();
+        let phi = 2.0 * PI * v;
+        let px = r * phi.cos();
+        let py = r * phi.sin();
+        let h = (1.0 - px * px).sqrt();
+        let s = (1.0 + wh.z) / 2.0;
+        let py = (1.0 - s) * h + s * py;
+        let pz = (1.0 - px * px - py * py).max(0.0).sqrt();
+        let nh = t1 * px + t2 * py + wh * pz;
+
+        Vec3::new(self.alpha * nh.x, self.alpha * nh.y, nh.z.max(1e-6)).unit()
+    }
+}
+
+/// Mirrors `wo` around `n`
+pub fn reflect(wo: Vec3, n: Vec3) -> Vec3 {
+    -wo + n * (2.0 * wo.dot(n))
+}
+
+/// Refracts `wi` through a surface with normal `n` and relative index of
+/// refraction `eta` (inside over outside). Returns `None` on total internal reflection.
+pub fn refract(wi: Vec3, n: Vec3, eta: f64) -> Option<Vec3> {
+    let (n, eta, cos_i) = match wi.dot(n) {
+        cos if cos < 0.0 => (-n, 1.0 / eta, -cos),
+        cos => (n, eta, cos),
+    };
+    let sin2_i = (1.0 - cos_i * cos_i).max(0.0);
+    let sin2_t = sin2_i / (eta * eta);
+    if sin2_t >= 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    Some(-wi / eta + n * (cos_i / eta - cos_t))
+}
+
+/// Unpolarized Fresnel reflectance of a dielectric interface, `eta` is inside over outside
+pub fn fresnel_dielectric(cos_i: f64, eta: f64) -> f64 {
+    let (cos_i, eta) = if cos_i < 0.0 {(-cos_i, 1.0 / eta)} else {(cos_i, eta)};
+    let cos_i = cos_i.min(1.0);
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let parallel = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let perpendicular = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    (parallel * parallel + perpendicular * perpendicular) / 2.0
+}
+
+/// Fresnel reflectance of a conductor with complex index of refraction `eta + ik`, per channel
+pub fn fresnel_conductor(cos_i: f64, eta: Color, k: Color) -> Color {
+    let channel = |eta: f64, k: f64| {
+        let cos2 = cos_i.clamp(0.0, 1.0).powi(2);
+        let sin2 = 1.0 - cos2;
+        let t0 = eta * eta - k * k - sin2;
+        let a2b2 = (t0 * t0 + 4.0 * eta * eta * k * k).sqrt();
+        let t1 = a2b2 + cos2;
+        let a = (0.5 * (a2b2 + t0)).max(0.0).sqrt();
+        let t2 = 2.0 * a * cos2.sqrt();
+        let rs = (t1 - t2) / (t1 + t2);
+        let t3 = cos2 * a2b2 + sin2 * sin2;
+        let t4 = t2 * sin2;
+        let rp = rs * (t3 - t4) / (t3 + t4);
+        (rs + rp) / 2.0
+    };
+    Color::new(channel(eta.r, k.r), channel(eta.g, k.g), channel(eta.b, k.b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler::{sample_unit_sphere, Sampler, SamplerKind};
+
+    /// The projected microfacet area has to add up to the macro surface
+    #[test]
+    fn distribution_is_normalized() {
+        for roughness in [0.2, 0.5, 1.0] {
+            let distribution = TrowbridgeReitz::new(roughness);
+            let mut sampler = Sampler::new(SamplerKind::Sobol, 1 << 16, 1, 0);
+            let mut sum = 0.0;
+            for i in 0..1 << 16 {
+                sampler.start_sample(i);
+                let wm = sample_unit_sphere(sampler.get_2d());
+                if wm.z > 0.0 {
+                    sum += distribution.d(wm) * wm.z;
+                }
+            }
+            let integral = sum * 4.0 * PI / (1 << 16) as f64;
+            assert!((integral - 1.0).abs() < 0.02, "roughness {roughness}: {integral}");
+        }
+    }
+
+    /// Visible normals sampled from `w` are distributed as `pdf` says,
+    /// compared on a histogram of the normals' z component
+    #[test]
+    fn visible_normals_match_pdf() {
+        const BINS: usize = 8;
+        const COUNT: usize = 1 << 16;
+        let distribution = TrowbridgeReitz::new(0.6);
+        let w = Vec3::new(0.6, 0.0, 0.8);
+        let bin = |wm: Vec3| ((wm.z * BINS as f64) as usize).min(BINS - 1);
+
+        let mut sampled = [0.0; BINS];
+        let mut expected = [0.0; BINS];
+        let mut sampler = Sampler::new(SamplerKind::Sobol, COUNT, 2, 0);
+        for i in 0..COUNT {
+            sampler.start_sample(i);
+            let wm = distribution.sample_wm(w, sampler.get_2d());
+            sampled[bin(wm)] += 1.0 / COUNT as f64;
+
+            let uniform = sample_unit_sphere(sampler.get_2d());
+            if uniform.z > 0.0 && uniform.dot(w) > 0.0 {
+                expected[bin(uniform)] += distribution.pdf(w, uniform) * 4.0 * PI / COUNT as f64;
+            }
+        }
+        for (s, e) in sampled.iter().zip(&expected) {
+            assert!((s - e).abs() < 0.01, "sampled {sampled:?}, expected {expected:?}");
+        }
+    }
+
+    #[test]
+    fn fresnel_limits() {
+        assert!((fresnel_dielectric(1.0, 1.5) - 0.04).abs() < 1e-12);
+        assert_eq!(fresnel_dielectric(0.1, 1.0 / 1.5), 1.0);
+        // a conductor without absorption is a dielectric
+        let conductor = fresnel_conductor(0.7, Color::new(1.5, 1.5, 1.5), Color::new(0.0, 0.0, 0.0));
+        assert!((conductor.r - fresnel_dielectric(0.7, 1.5)).abs() < 1e-12);
+        assert!((fresnel_conductor(0.0, Color::new(0.2, 0.2, 0.2), Color::new(3.0, 3.0, 3.0)).g - 1.0).abs() < 1e-12);
+    }
+}
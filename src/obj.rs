@@ -0,0 +1,521 @@
+use std::{collections::{HashMap, HashSet}, error::Error, fmt, fs, io, path::{Path, PathBuf}};
+
+use crate::{color::Color, hittable::Hittable, material::{Dielectric, DiffuseLight, Lambertian, Material, Metal}, mesh::{Face, Mesh}, texture::{ImageTexture, Texture, WrapMode}, vec3::{cross, Vec3}};
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io { path: PathBuf, error: io::Error },
+    Parse { path: PathBuf, line: usize, message: String },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjError::Io { path, error } => write!(f, "{}: {error}", path.display()),
+            ObjError::Parse { path, line, message } => write!(f, "{}:{line}: {message}", path.display()),
+        }
+    }
+}
+
+impl Error for ObjError {}
+
+/// One `g`/`o` group of an OBJ file using a single material
+pub struct ObjGroup {
+    pub name: String,
+    pub material: String,
+    pub mesh: Mesh,
+}
+
+pub struct ObjModel {
+    pub groups: Vec<ObjGroup>,
+    /// Statements that were skipped and materials that weren't found, the
+    /// model loads without them
+    pub warnings: Vec<ObjError>,
+}
+
+impl ObjModel {
+    /// All triangles of every group, ready to be added to a `HittableList`
+    pub fn into_hittables(self) -> Vec<Hittable> {
+        self.groups.into_iter().flat_map(|group| group.mesh.into_triangles()).collect()
+    }
+}
+
+/// Used for faces before any `usemtl` and for materials missing from the MTL files
+fn default_material() -> Material {
+    Lambertian::new(Color::new(0.8, 0.8, 0.8))
+}
+
+#[derive(Clone, Copy)]
+struct Corner {
+    position: usize,
+    uv: Option<usize>,
+    normal: Option<usize>,
+}
+
+struct RawFace {
+    corners: [Corner; 3],
+    smooth: bool,
+}
+
+struct RawGroup {
+    name: String,
+    material: String,
+    /// Line of the `usemtl` that chose the material
+    material_line: usize,
+    faces: Vec<RawFace>,
+}
+
+/// Loads a Wavefront OBJ file along with any MTL libraries it references.
+///
+/// Polygons are triangulated as fans. Faces without `vn` normals that are in a
+/// smoothing group (`s 1`, ...) get area weighted vertex normals.
+///
+/// Malformed statements are errors, statements we don't know and materials
+/// missing from the MTL files are only reported in `ObjModel::warnings`.
+pub fn load_obj(path: impl AsRef<Path>) -> Result<ObjModel, ObjError> {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path).map_err(|error| ObjError::Io { path: path.to_path_buf(), error })?;
+
+    let mut positions = Vec::new();
+    let mut uvs = Vec::new();
+    let mut normals = Vec::new();
+    let mut materials = HashMap::new();
+    let mut warnings = Vec::new();
+
+    let mut groups = vec![RawGroup { name: String::from("default"), material: String::new(), material_line: 0, faces: Vec::new() }];
+    let mut smooth = false;
+
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let err = |message: String| ObjError::Parse { path: path.to_path_buf(), line: line_number, message };
+
+        let line = line.split('#').next().unwrap().trim();
+        let mut words = line.split_whitespace();
+        let Some(keyword) = words.next() else {
+            continue;
+        };
+        let args: Vec<&str> = words.collect();
+
+        match keyword {
+            "v" => positions.push(parse_vec3(&args).map_err(err)?),
+            "vn" => normals.push(parse_vec3(&args).map_err(err)?),
+            "vt" => {
+                if args.is_empty() || args.len() > 3 {
+                    return Err(err(format!("expected 1 to 3 texture coordinates, found {}", args.len())));
+                }
+                let u = parse_f64(args[0]).map_err(err)?;
+                let v = match args.get(1) {
+                    Some(v) => parse_f64(v).map_err(err)?,
+                    None => 0.0,
+                };
+                uvs.push((u, v));
+            },
+            "f" => {
+                if args.len() < 3 {
+                    return Err(err(format!("a face needs at least 3 vertices, found {}", args.len())));
+                }
+                let corners = args.iter()
+                    .map(|arg| parse_corner(arg, positions.len(), uvs.len(), normals.len()))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(err)?;
+
+                let group = groups.last_mut().unwrap();
+                for i in 1..corners.len() - 1 {
+                    group.faces.push(RawFace { corners: [corners[0], corners[i], corners[i + 1]], smooth });
+                }
+            },
+            "g" | "o" => {
+                let name = if args.is_empty() {String::from("default")} else {args.join(" ")};
+                let last = groups.last().unwrap();
+                let (material, material_line) = (last.material.clone(), last.material_line);
+                groups.push(RawGroup { name, material, material_line, faces: Vec::new() });
+            },
+            "usemtl" => {
+                let Some(&material) = args.first() else {
+                    return Err(err(String::from("`usemtl` needs a material name")));
+                };
+                let name = groups.last().unwrap().name.clone();
+                groups.push(RawGroup { name, material: material.to_string(), material_line: line_number, faces: Vec::new() });
+            },
+            "mtllib" => {
+                if args.is_empty() {
+                    return Err(err(String::from("`mtllib` needs a file name")));
+                }
+                for file in &args {
+                    let mtl_path = path.parent().unwrap_or(Path::new("")).join(file);
+                    materials.extend(load_mtl(&mtl_path, &mut warnings)?);
+                }
+            },
+            "s" => {
+                smooth = match args.first() {
+                    Some(&"off") | Some(&"0") => false,
+                    Some(_) => true,
+                    None => return Err(err(String::from("`s` needs a smoothing group or `off`"))),
+                };
+            },
+            // lines, points and free-form geometry are not renderable
+            "l" | "p" | "vp" | "cstype" | "deg" | "curv" | "surf" | "parm" | "end" | "mg" => {},
+            other => warnings.push(err(format!("unknown statement `{other}` ignored"))),
+        }
+    }
+
+    // groups split by `g` share their `usemtl` line, which is reported once
+    let mut reported = HashSet::new();
+    let groups = groups.into_iter()
+        .filter(|group| !group.faces.is_empty())
+        .map(|group| {
+            let mat = match materials.get(&group.material) {
+                Some(mat) => mat.clone(),
+                None => {
+                    if !group.material.is_empty() && reported.insert(group.material_line) {
+                        let message = format!("material `{}` is not defined, using the default", group.material);
+                        warnings.push(ObjError::Parse { path: path.to_path_buf(), line: group.material_line, message });
+                    }
+                    default_material()
+                },
+            };
+            let mesh = build_mesh(&group.faces, &positions, &uvs, &normals, mat);
+            ObjGroup { name: group.name, material: group.material, mesh }
+        })
+        .collect();
+
+    Ok(ObjModel { groups, warnings })
+}
+
+/// Builds a mesh holding only the vertices `faces` reference
+fn build_mesh(faces: &[RawFace], positions: &[Vec3], uvs: &[(f64, f64)], normals: &[Vec3], mat: Material) -> Mesh {
+    let mut position_map = HashMap::new();
+    let mut uv_map = HashMap::new();
+    let mut normal_map = HashMap::new();
+    let mut mesh_positions = Vec::new();
+    let mut mesh_uvs = Vec::new();
+    let mut mesh_normals = Vec::new();
+
+    fn remap<T: Copy>(index: usize, source: &[T], map: &mut HashMap<usize, usize>, out: &mut Vec<T>) -> usize {
+        *map.entry(index).or_insert_with(|| {
+            out.push(source[index]);
+            out.len() - 1
+        })
+    }
+
+    // smoothed normals are accumulated per position before being normalized
+    let mut smooth_normals: HashMap<usize, Vec3> = HashMap::new();
+    for face in faces.iter().filter(|f| f.smooth && f.corners.iter().any(|c| c.normal.is_none())) {
+        let [a, b, c] = face.corners.map(|c| positions[c.position]);
+        let area_weighted = cross(b - a, c - a);
+        for corner in face.corners {
+            let n = smooth_normals.entry(corner.position).or_insert(Vec3::zero());
+            *n = *n + area_weighted;
+        }
+    }
+    let mut smooth_map = HashMap::new();
+
+    let mut mesh_faces = Vec::with_capacity(faces.len());
+    for face in faces {
+        let positions_index = face.corners.map(|c| remap(c.position, positions, &mut position_map, &mut mesh_positions));
+
+        let uvs_index = if face.corners.iter().all(|c| c.uv.is_some()) {
+            Some(face.corners.map(|c| remap(c.uv.unwrap(), uvs, &mut uv_map, &mut mesh_uvs)))
+        } else {
+            None
+        };
+
+        let normals_index = if face.corners.iter().all(|c| c.normal.is_some()) {
+            Some(face.corners.map(|c| remap(c.normal.unwrap(), normals, &mut normal_map, &mut mesh_normals)))
+        } else if face.smooth {
+            Some(face.corners.map(|c| *smooth_map.entry(c.position).or_insert_with(|| {
+                let n = smooth_normals[&c.position];
+                mesh_normals.push(if n.near_zero() {n} else {n.unit()});
+                mesh_normals.len() - 1
+            })))
+        } else {
+            None
+        };
+
+        mesh_faces.push(Face { positions: positions_index, normals: normals_index, uvs: uvs_index });
+    }
+
+    Mesh::new(mesh_positions, mesh_normals, mesh_uvs, mesh_faces, mat)
+}
+
+#[derive(Default)]
+struct RawMaterial {
+    diffuse: Option<Color>,
+    /// `map_Kd`, replaces `Kd` when present
+    diffuse_map: Option<Texture>,
+    emission: Option<Color>,
+    specular: Option<Color>,
+    shininess: Option<f64>,
+    roughness: Option<f64>,
+    metallic: Option<f64>,
+    ior: Option<f64>,
+    dissolve: Option<f64>,
+    illum: Option<u32>,
+}
+
+impl RawMaterial {
+    /// Maps MTL parameters onto the closest of our material types
+    fn to_material(&self) -> Material {
+        let diffuse = match &self.diffuse_map {
+            Some(map) => map.clone(),
+            None => Texture::from(self.diffuse.unwrap_or(Color::new(0.8, 0.8, 0.8))),
+        };
+        let transparent = matches!(self.illum, Some(4 | 6 | 7 | 9)) || self.dissolve.is_some_and(|d| d < 1.0);
+        let metallic = matches!(self.illum, Some(3 | 5)) || self.metallic.is_some_and(|m| m >= 0.5);
+
+        if let Some(ke) = self.emission.filter(|ke| ke.r + ke.g + ke.b > 0.0) {
+            DiffuseLight::new(ke)
+        } else if transparent {
+            Dielectric::new(self.ior.unwrap_or(1.5))
+        } else if metallic {
+            let albedo = match self.specular {
+                Some(ks) if ks.r + ks.g + ks.b > 0.0 && self.metallic.is_none() => Texture::from(ks),
+                _ => diffuse,
+            };
+            // Phong exponent to roughness, the usual Blinn-Phong/Beckmann correspondence
+            let fuzz = self.roughness.unwrap_or_else(|| (2.0 / (self.shininess.unwrap_or(0.0) + 2.0)).sqrt());
+            Metal::new(albedo, fuzz.clamp(0.0, 1.0))
+        } else {
+            Lambertian::new(diffuse)
+        }
+    }
+}
+
+fn load_mtl(path: &Path, warnings: &mut Vec<ObjError>) -> Result<HashMap<String, Material>, ObjError> {
+    let source = fs::read_to_string(path).map_err(|error| ObjError::Io { path: path.to_path_buf(), error })?;
+
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, RawMaterial)> = None;
+
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let err = |message: String| ObjError::Parse { path: path.to_path_buf(), line: line_number, message };
+
+        let line = line.split('#').next().unwrap().trim();
+        let mut words = line.split_whitespace();
+        let Some(keyword) = words.next() else {
+            continue;
+        };
+        let args: Vec<&str> = words.collect();
+
+        if keyword == "newmtl" {
+            let Some(&name) = args.first() else {
+                return Err(err(String::from("`newmtl` needs a material name")));
+            };
+            if let Some((name, raw)) = current.take() {
+                materials.insert(name, raw.to_material());
+            }
+            current = Some((name.to_string(), RawMaterial::default()));
+            continue;
+        }
+
+        let Some((_, raw)) = current.as_mut() else {
+            return Err(err(format!("`{keyword}` before any `newmtl`")));
+        };
+        match keyword {
+            "Kd" => raw.diffuse = Some(parse_color(&args).map_err(err)?),
+            "Ke" => raw.emission = Some(parse_color(&args).map_err(err)?),
+            "Ks" => raw.specular = Some(parse_color(&args).map_err(err)?),
+            "Ns" => raw.shininess = Some(parse_single(&args).map_err(err)?),
+            "Ni" => raw.ior = Some(parse_single(&args).map_err(err)?),
+            "d" => raw.dissolve = Some(parse_single(&args).map_err(err)?),
+            "Tr" => raw.dissolve = Some(1.0 - parse_single(&args).map_err(err)?),
+            "Pr" => raw.roughness = Some(parse_single(&args).map_err(err)?),
+            "Pm" => raw.metallic = Some(parse_single(&args).map_err(err)?),
+            "illum" => {
+                let illum = args.first().and_then(|a| a.parse().ok());
+                raw.illum = Some(illum.ok_or_else(|| err(String::from("`illum` needs an integer")))?);
+            },
+            "map_Kd" => {
+                // options like `-s 1 1 1` come before the file name, which may not contain spaces
+                let Some(file) = args.last() else {
+                    return Err(err(String::from("`map_Kd` needs a file name")));
+                };
+                let texture_path = path.parent().unwrap_or(Path::new("")).join(file);
+                raw.diffuse_map = Some(ImageTexture::load(texture_path, WrapMode::Repeat).map_err(|e| err(e.to_string()))?);
+            },
+            // texture maps and parameters we have no equivalent for
+            k if k.starts_with("map_") || k.starts_with("refl") || k == "bump" || k == "disp" || k == "decal" => {},
+            "Ka" | "Tf" | "Ps" | "Pc" | "Pcr" | "aniso" | "anisor" | "norm" | "sharpness" => {},
+            other => warnings.push(err(format!("unknown statement `{other}` ignored"))),
+        }
+    }
+
+    if let Some((name, raw)) = current {
+        materials.insert(name, raw.to_material());
+    }
+
+    Ok(materials)
+}
+
+fn parse_f64(word: &str) -> Result<f64, String> {
+    word.parse().map_err(|_| format!("`{word}` is not a number"))
+}
+
+fn parse_single(args: &[&str]) -> Result<f64, String> {
+    match args {
+        [value] => parse_f64(value),
+        _ => Err(format!("expected 1 number, found {}", args.len())),
+    }
+}
+
+fn parse_vec3(args: &[&str]) -> Result<Vec3, String> {
+    // `v` may carry an optional w or vertex color after x y z
+    if args.len() < 3 {
+        return Err(format!("expected 3 numbers, found {}", args.len()));
+    }
+    Ok(Vec3::new(parse_f64(args[0])?, parse_f64(args[1])?, parse_f64(args[2])?))
+}
+
+fn parse_color(args: &[&str]) -> Result<Color, String> {
+    match args {
+        [v] => {
+            let v = parse_f64(v)?;
+            Ok(Color::new(v, v, v))
+        },
+        [r, g, b] => Ok(Color::new(parse_f64(r)?, parse_f64(g)?, parse_f64(b)?)),
+        _ => Err(format!("expected 1 or 3 color components, found {}", args.len())),
+    }
+}
+
+/// Parses one `v`, `v/vt`, `v//vn` or `v/vt/vn` face corner into zero based indices
+fn parse_corner(word: &str, positions: usize, uvs: usize, normals: usize) -> Result<Corner, String> {
+    let mut parts = word.split('/');
+    let position = resolve_index(parts.next().unwrap(), positions, "vertex")?;
+    let uv = match parts.next() {
+        Some("") | None => None,
+        Some(index) => Some(resolve_index(index, uvs, "texture coordinate")?),
+    };
+    let normal = match parts.next() {
+        Some("") | None => None,
+        Some(index) => Some(resolve_index(index, normals, "normal")?),
+    };
+    if parts.next().is_some() {
+        return Err(format!("malformed face vertex `{word}`"));
+    }
+    Ok(Corner { position, uv, normal })
+}
+
+/// OBJ indices are one based, negative ones count back from the latest element
+fn resolve_index(word: &str, count: usize, kind: &str) -> Result<usize, String> {
+    let index: i64 = word.parse().map_err(|_| format!("`{word}` is not a valid {kind} index"))?;
+    let resolved = if index < 0 {count as i64 + index} else {index - 1};
+    if index == 0 || resolved < 0 || resolved >= count as i64 {
+        return Err(format!("{kind} index {index} out of range, {count} defined so far"));
+    }
+    Ok(resolved as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process;
+
+    use super::*;
+    use crate::texture::Texture;
+
+    /// Writes `files` to a fresh directory named after the test and loads `model.obj` from it
+    fn load(test: &str, files: &[(&str, &str)]) -> (PathBuf, Result<ObjModel, ObjError>) {
+        let dir = std::env::temp_dir().join(format!("obj-{test}-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for (name, source) in files {
+            fs::write(dir.join(name), source).unwrap();
+        }
+        let model = load_obj(dir.join("model.obj"));
+        fs::remove_dir_all(&dir).unwrap();
+        (dir, model)
+    }
+
+    fn load_ok(test: &str, files: &[(&str, &str)]) -> ObjModel {
+        load(test, files).1.unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    const TRIANGLE: &str = "v 0 0 0\nv 1 0 0\nv 0 1 0\n";
+
+    /// Malformed statements are errors naming the file and line
+    #[test]
+    fn errors() {
+        let cases = [
+            (format!("{TRIANGLE}f 1 2 4\n"), "model.obj:4: vertex index 4 out of range, 3 defined so far"),
+            (format!("{TRIANGLE}f 1 2 0\n"), "model.obj:4: vertex index 0 out of range, 3 defined so far"),
+            (format!("{TRIANGLE}f 1/1 2 3\n"), "model.obj:4: texture coordinate index 1 out of range, 0 defined so far"),
+            (format!("{TRIANGLE}f 1 2 x\n"), "model.obj:4: `x` is not a valid vertex index"),
+            (format!("{TRIANGLE}f 1 2\n"), "model.obj:4: a face needs at least 3 vertices, found 2"),
+            (String::from("v 0 0\n"), "model.obj:1: expected 3 numbers, found 2"),
+            (String::from("v 0 zero 0\n"), "model.obj:1: `zero` is not a number"),
+            (format!("{TRIANGLE}usemtl\n"), "model.obj:4: `usemtl` needs a material name"),
+        ];
+        for (i, (source, expected)) in cases.iter().enumerate() {
+            let (dir, model) = load(&format!("errors{i}"), &[("model.obj", source)]);
+            let error = model.err().unwrap().to_string();
+            assert_eq!(error, format!("{}{}{expected}", dir.display(), std::path::MAIN_SEPARATOR));
+        }
+
+        let (dir, model) = load("mtl-errors", &[("model.obj", "mtllib a.mtl\n"), ("a.mtl", "# colors\nKd 1 0 0\nnewmtl red\n")]);
+        assert_eq!(model.err().unwrap().to_string(), format!("{}{}a.mtl:2: `Kd` before any `newmtl`", dir.display(), std::path::MAIN_SEPARATOR));
+        let (_, model) = load("mtl-number", &[("model.obj", "mtllib a.mtl\n"), ("a.mtl", "newmtl red\nKd 1 zero 0\n")]);
+        assert!(model.err().unwrap().to_string().ends_with("a.mtl:2: `zero` is not a number"));
+    }
+
+    /// Polygons become fans around their first corner, and negative indices
+    /// count back from the vertices defined so far
+    #[test]
+    fn fans_and_negative_indices() {
+        let source = "v 0 0 0\nv 1 0 0\nv 2 1 0\nv 1 2 0\nv 0 1 0\nf -5 -4 -3 -2 -1\nv 5 5 5\nf -1 -2 -3\n";
+        let model = load_ok("fans", &[("model.obj", source)]);
+        assert!(model.warnings.is_empty());
+        let mesh = &model.groups[0].mesh;
+        let faces: Vec<_> = mesh.faces.iter().map(|face| face.positions.map(|i| mesh.positions[i])).collect();
+        assert_eq!(faces.len(), 4);
+        let corners = [Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), Vec3::new(2.0, 1.0, 0.0), Vec3::new(1.0, 2.0, 0.0), Vec3::new(0.0, 1.0, 0.0)];
+        for (i, face) in faces[..3].iter().enumerate() {
+            for (got, expected) in face.iter().zip([corners[0], corners[i + 1], corners[i + 2]]) {
+                assert!((*got - expected).len() == 0.0, "face {i}: {face:?}");
+            }
+        }
+        for (got, expected) in faces[3].iter().zip([Vec3::new(5.0, 5.0, 5.0), corners[4], corners[3]]) {
+            assert!((*got - expected).len() == 0.0, "{:?}", faces[3]);
+        }
+    }
+
+    /// Faces in a smoothing group share area weighted vertex normals, faces
+    /// with `vn` keep theirs, and `s off` leaves faces flat
+    #[test]
+    fn smoothing() {
+        let source = "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 0 0 1\nvn 0 1 0\ns 1\nf 1 2 3\nf 1 3 4\nf 1//1 2//1 3//1\ns off\nf 2 3 4\n";
+        let model = load_ok("smoothing", &[("model.obj", source)]);
+        let mesh = &model.groups[0].mesh;
+        let normal = |face: usize, corner: usize| mesh.faces[face].normals.map(|n| mesh.normals[n[corner]]);
+
+        let shared = Vec3::new(1.0, 0.0, 1.0).unit();
+        for (corner, expected) in [shared, Vec3::new(0.0, 0.0, 1.0), shared].into_iter().enumerate() {
+            assert!((normal(0, corner).unwrap() - expected).len() < 1e-12, "{:?}", normal(0, corner));
+        }
+        assert!((normal(1, 2).unwrap() - Vec3::new(1.0, 0.0, 0.0)).len() < 1e-12);
+        assert!((normal(2, 0).unwrap() - Vec3::new(0.0, 1.0, 0.0)).len() < 1e-12);
+        assert!(normal(3, 0).is_none());
+    }
+
+    /// MTL parameters pick the closest material, unknown statements and
+    /// undefined materials are warnings
+    #[test]
+    fn materials() {
+        let mtl = "newmtl red\nKd 1 0 0\nKm 0.5\n\nnewmtl lamp\nKe 4 4 4\n\nnewmtl glass\nillum 7\nNi 1.3\n\nnewmtl steel\nillum 3\nKs 0.9 0.9 0.9\nNs 98\n";
+        let obj = "mtllib a.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemap none\nusemtl red\nf 1 2 3\nusemtl lamp\nf 1 2 3\nusemtl glass\nf 1 2 3\nusemtl steel\nf 1 2 3\nusemtl missing\nf 1 2 3\ng other\nf 1 2 3\n";
+        let model = load_ok("materials", &[("model.obj", obj), ("a.mtl", mtl)]);
+
+        let names: Vec<_> = model.groups.iter().map(|group| group.material.as_str()).collect();
+        assert_eq!(names, ["red", "lamp", "glass", "steel", "missing", "missing"]);
+        let mats: Vec<_> = model.groups.iter().map(|group| &group.mesh.mat).collect();
+        assert!(matches!(mats[0], Material::Lambertian(Lambertian { albedo: Texture::Solid(c) }) if (c.r, c.g, c.b) == (1.0, 0.0, 0.0)));
+        assert!(matches!(mats[1], Material::DiffuseLight(DiffuseLight { emit: Texture::Solid(c) }) if c.r == 4.0));
+        assert!(matches!(mats[2], Material::Dielectric(Dielectric { refraction_index: 1.3 })));
+        assert!(matches!(mats[3], Material::Metal(Metal { albedo: Texture::Solid(c), fuzz }) if c.r == 0.9 && (fuzz - 0.02f64.sqrt()).abs() < 1e-12));
+        assert!(matches!(mats[4], Material::Lambertian(_)));
+
+        let warnings: Vec<_> = model.warnings.iter().map(|w| w.to_string()).collect();
+        assert_eq!(warnings.len(), 3, "{warnings:?}");
+        assert!(warnings[0].ends_with("a.mtl:3: unknown statement `Km` ignored"));
+        assert!(warnings[1].ends_with("model.obj:5: unknown statement `usemap` ignored"));
+        assert!(warnings[2].ends_with("model.obj:14: material `missing` is not defined, using the default"));
+    }
+}
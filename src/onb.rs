@@ -0,0 +1,31 @@
+use crate::vec3::Vec3;
+
+/// Orthonormal basis with `w` along a given direction, used to move
+/// directions between world space and a local frame where `w` is up
+#[derive(Debug, Clone, Copy)]
+pub struct Onb {
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3,
+}
+
+impl Onb {
+    /// `w` must be a unit vector
+    pub fn new(w: Vec3) -> Self {
+        // branchless construction from Duff et al. 2017, no special case near the poles
+        let sign = 1f64.copysign(w.z);
+        let a = -1.0 / (sign + w.z);
+        let b = w.x * w.y * a;
+        let u = Vec3::new(1.0 + sign * w.x * w.x * a, sign * b, -sign * w.x);
+        let v = Vec3::new(b, sign + w.y * w.y * a, -w.y);
+        Self { u, v, w }
+    }
+    /// Local coordinates to world space
+    pub fn to_world(self, local: Vec3) -> Vec3 {
+        self.u * local.x + self.v * local.y + self.w * local.z
+    }
+    /// World space to local coordinates
+    pub fn to_local(self, world: Vec3) -> Vec3 {
+        Vec3::new(world.dot(self.u), world.dot(self.v), world.dot(self.w))
+    }
+}
@@ -0,0 +1,593 @@
+use std::{error::Error, fmt, fs::File, io::{self, BufWriter, Write}, path::{Path, PathBuf}};
+
+use crate::{color::Color, tonemap::DisplayTransform};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum BitDepth {
+    #[default]
+    Eight,
+    Sixteen,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageFormat {
+    /// Plain text `P3` PPM
+    PpmAscii,
+    /// Binary `P6` PPM
+    PpmBinary,
+    Png,
+    /// Portable float map, linear 32-bit float
+    Pfm,
+    /// Radiance RGBE, linear
+    Hdr,
+    /// Scanline OpenEXR, linear half or float
+    Exr,
+}
+
+impl ImageFormat {
+    /// Whether pixels are written as linear floats rather than tone mapped integers
+    pub fn is_hdr(&self) -> bool {
+        matches!(self, ImageFormat::Pfm | ImageFormat::Hdr | ImageFormat::Exr)
+    }
+    /// Picks the format from the file extension, `.ppm` files are written as binary `P6`
+    pub fn from_path(path: &Path) -> Result<Self, OutputError> {
+        let extension = path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("ppm") => Ok(ImageFormat::PpmBinary),
+            Some("png") => Ok(ImageFormat::Png),
+            Some("pfm") => Ok(ImageFormat::Pfm),
+            Some("hdr") => Ok(ImageFormat::Hdr),
+            Some("exr") => Ok(ImageFormat::Exr),
+            _ => Err(OutputError::UnknownFormat(path.to_path_buf())),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum ExrPixelType {
+    #[default]
+    Half,
+    Float,
+}
+
+/// How the final image is encoded, set from `CameraBuilder`
+#[derive(Debug, Default, Clone, Copy)]
+pub struct OutputSettings {
+    /// Used by PPM and PNG
+    pub bit_depth: BitDepth,
+    pub exr_pixel_type: ExrPixelType,
+    /// Write `.ppm` files as `P3` text instead of binary `P6`
+    pub ascii_ppm: bool,
+}
+
+#[derive(Debug)]
+pub enum OutputError {
+    UnknownFormat(PathBuf),
+    Io { path: PathBuf, error: io::Error },
+    Encode { path: PathBuf, message: String },
+}
+
+impl fmt::Display for OutputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutputError::UnknownFormat(path) => write!(f, "{}: unknown image format, expected a .ppm, .png, .pfm, .hdr or .exr extension", path.display()),
+            OutputError::Io { path, error } => write!(f, "{}: {error}", path.display()),
+            OutputError::Encode { path, message } => write!(f, "{}: {message}", path.display()),
+        }
+    }
+}
+
+impl Error for OutputError {}
+
+/// Writes `pixels` (row major, top row first) to `path` in the format given by its extension.
+///
+/// HDR formats get the linear values as they are, LDR formats go through `display` first.
+pub fn write_image(path: &Path, width: usize, height: usize, pixels: &[Color], settings: OutputSettings, display: DisplayTransform) -> Result<(), OutputError> {
+    let io_err = |error| OutputError::Io { path: path.to_path_buf(), error };
+
+    let format = match ImageFormat::from_path(path)? {
+        ImageFormat::PpmBinary if settings.ascii_ppm => ImageFormat::PpmAscii,
+        format => format,
+    };
+
+    let display_pixels: Vec<Color>;
+    let pixels = if format.is_hdr() {
+        pixels
+    } else {
+        display_pixels = pixels.iter().map(|&c| display.apply(c)).collect();
+        &display_pixels
+    };
+
+    let mut file_buf = BufWriter::new(File::create(path).map_err(io_err)?);
+    match format {
+        ImageFormat::PpmAscii => write_ppm_ascii(&mut file_buf, width, height, pixels, settings.bit_depth).map_err(io_err)?,
+        ImageFormat::PpmBinary => write_ppm_binary(&mut file_buf, width, height, pixels, settings.bit_depth).map_err(io_err)?,
+        ImageFormat::Png => write_png(&mut file_buf, width, height, pixels, settings.bit_depth)
+            .map_err(|e| OutputError::Encode { path: path.to_path_buf(), message: e.to_string() })?,
+        ImageFormat::Pfm => write_pfm(&mut file_buf, width, height, pixels).map_err(io_err)?,
+        ImageFormat::Hdr => write_rgbe(&mut file_buf, width, height, pixels).map_err(io_err)?,
+        ImageFormat::Exr => write_exr(&mut file_buf, width, height, pixels, settings.exr_pixel_type).map_err(io_err)?,
+    }
+    file_buf.flush().map_err(io_err)
+}
+
+fn max_value(bit_depth: BitDepth) -> u32 {
+    match bit_depth {
+        BitDepth::Eight => 255,
+        BitDepth::Sixteen => 65535,
+    }
+}
+
+fn write_ppm_ascii(out: &mut impl Write, width: usize, height: usize, pixels: &[Color], bit_depth: BitDepth) -> io::Result<()> {
+    out.write_all(format!("P3\n{} {}\n{}\n", width, height, max_value(bit_depth)).as_bytes())?;
+    for c in pixels {
+        match bit_depth {
+            BitDepth::Eight => {
+                let [r, g, b] = c.to_rgb8();
+                out.write_all(format!("{r} {g} {b}\n").as_bytes())?;
+            },
+            BitDepth::Sixteen => {
+                let [r, g, b] = c.to_rgb16();
+                out.write_all(format!("{r} {g} {b}\n").as_bytes())?;
+            },
+        }
+    }
+    Ok(())
+}
+
+fn write_ppm_binary(out: &mut impl Write, width: usize, height: usize, pixels: &[Color], bit_depth: BitDepth) -> io::Result<()> {
+    out.write_all(format!("P6\n{} {}\n{}\n", width, height, max_value(bit_depth)).as_bytes())?;
+    out.write_all(&encode_samples(pixels, bit_depth))
+}
+
+fn write_png(out: &mut impl Write, width: usize, height: usize, pixels: &[Color], bit_depth: BitDepth) -> Result<(), png::EncodingError> {
+    let mut encoder = png::Encoder::new(out, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(match bit_depth {
+        BitDepth::Eight => png::BitDepth::Eight,
+        BitDepth::Sixteen => png::BitDepth::Sixteen,
+    });
+    encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&encode_samples(pixels, bit_depth))?;
+    writer.finish()
+}
+
+/// Interleaved RGB samples, 16-bit samples are big endian as both PPM and PNG expect
+fn encode_samples(pixels: &[Color], bit_depth: BitDepth) -> Vec<u8> {
+    match bit_depth {
+        BitDepth::Eight => pixels.iter().flat_map(|c| c.to_rgb8()).collect(),
+        BitDepth::Sixteen => pixels.iter().flat_map(|c| c.to_rgb16()).flat_map(u16::to_be_bytes).collect(),
+    }
+}
+
+fn write_pfm(out: &mut impl Write, width: usize, height: usize, pixels: &[Color]) -> io::Result<()> {
+    // a negative scale marks little endian data
+    out.write_all(format!("PF\n{} {}\n-1.0\n", width, height).as_bytes())?;
+    // rows are stored bottom to top
+    for row in pixels.chunks(width).rev() {
+        for c in row {
+            for v in [c.r, c.g, c.b] {
+                out.write_all(&(v as f32).to_le_bytes())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_rgbe(out: &mut impl Write, width: usize, height: usize, pixels: &[Color]) -> io::Result<()> {
+    out.write_all(format!("#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n", height, width).as_bytes())?;
+
+    let mut channels: [Vec<u8>; 4] = Default::default();
+    for row in pixels.chunks(width) {
+        // run length encoding only exists for these widths, everything else is written flat
+        if !(8..0x8000).contains(&width) {
+            for &c in row {
+                out.write_all(&to_rgbe(c))?;
+            }
+            continue;
+        }
+
+        for channel in &mut channels {
+            channel.clear();
+        }
+        for &c in row {
+            for (channel, value) in channels.iter_mut().zip(to_rgbe(c)) {
+                channel.push(value);
+            }
+        }
+
+        out.write_all(&[2, 2, (width >> 8) as u8, (width & 0xff) as u8])?;
+        for channel in &channels {
+            write_rle_channel(out, channel)?;
+        }
+    }
+    Ok(())
+}
+
+/// Encodes one channel of a scanline as runs (count > 128) and literals (count <= 128)
+fn write_rle_channel(out: &mut impl Write, data: &[u8]) -> io::Result<()> {
+    const MIN_RUN: usize = 4;
+    let mut pos = 0;
+    while pos < data.len() {
+        // find the next run worth encoding
+        let mut run_start = pos;
+        let mut run_len = 0;
+        while run_start < data.len() {
+            run_len = data[run_start..].iter().take(127).take_while(|&&v| v == data[run_start]).count();
+            if run_len >= MIN_RUN {
+                break;
+            }
+            run_start += run_len;
+        }
+        if run_len < MIN_RUN {
+            run_start = data.len();
+        }
+
+        for literal in data[pos..run_start].chunks(128) {
+            out.write_all(&[literal.len() as u8])?;
+            out.write_all(literal)?;
+        }
+        if run_start < data.len() {
+            out.write_all(&[128 + run_len as u8, data[run_start]])?;
+        }
+        pos = run_start + run_len;
+    }
+    Ok(())
+}
+
+fn to_rgbe(c: Color) -> [u8; 4] {
+    // `max` skips NaN channels, only an all NaN pixel is left with NaN
+    let v = c.r.max(c.g).max(c.b);
+    if v.is_nan() || v < 1e-32 {
+        return [0, 0, 0, 0];
+    }
+    // v = m * 2^e with m in [0.5, 1). Past the largest exponent, infinity
+    // included, the mantissa saturates instead.
+    let e = v.log2().floor().clamp(-129.0, 126.0) as i32 + 1;
+    let scale = 256.0 / 2f64.powi(e);
+    let q = |x: f64| (x.max(0.0) * scale).min(255.0) as u8;
+    [q(c.r), q(c.g), q(c.b), (e + 128) as u8]
+}
+
+fn write_exr(out: &mut impl Write, width: usize, height: usize, pixels: &[Color], pixel_type: ExrPixelType) -> io::Result<()> {
+    fn attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+        header.extend_from_slice(name.as_bytes());
+        header.push(0);
+        header.extend_from_slice(kind.as_bytes());
+        header.push(0);
+        header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+        header.extend_from_slice(value);
+    }
+    let (type_id, sample_size) = match pixel_type {
+        ExrPixelType::Half => (1i32, 2),
+        ExrPixelType::Float => (2i32, 4),
+    };
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&[0x76, 0x2f, 0x31, 0x01]); // magic number
+    header.extend_from_slice(&[2, 0, 0, 0]); // version 2, single part scanline file
+
+    // channels must be listed, and stored, in alphabetical order
+    let mut channels = Vec::new();
+    for name in ["B", "G", "R"] {
+        channels.extend_from_slice(name.as_bytes());
+        channels.push(0);
+        channels.extend_from_slice(&type_id.to_le_bytes());
+        channels.extend_from_slice(&[0, 0, 0, 0]); // pLinear and reserved
+        channels.extend_from_slice(&1i32.to_le_bytes()); // x sampling
+        channels.extend_from_slice(&1i32.to_le_bytes()); // y sampling
+    }
+    channels.push(0);
+    attribute(&mut header, "channels", "chlist", &channels);
+    attribute(&mut header, "compression", "compression", &[0]);
+
+    let window: Vec<u8> = [0, 0, width as i32 - 1, height as i32 - 1].iter().flat_map(|v| v.to_le_bytes()).collect();
+    attribute(&mut header, "dataWindow", "box2i", &window);
+    attribute(&mut header, "displayWindow", "box2i", &window);
+    attribute(&mut header, "lineOrder", "lineOrder", &[0]); // increasing y
+    attribute(&mut header, "pixelAspectRatio", "float", &1f32.to_le_bytes());
+    attribute(&mut header, "screenWindowCenter", "v2f", &[0u8; 8]);
+    attribute(&mut header, "screenWindowWidth", "float", &1f32.to_le_bytes());
+    header.push(0);
+
+    // uncompressed files store one scanline per chunk, each found through the offset table
+    let chunk_size = 8 + width * 3 * sample_size;
+    let first_chunk = header.len() + height * 8;
+    out.write_all(&header)?;
+    for y in 0..height {
+        out.write_all(&((first_chunk + y * chunk_size) as u64).to_le_bytes())?;
+    }
+
+    let mut chunk = Vec::with_capacity(chunk_size);
+    for (y, row) in pixels.chunks(width).enumerate() {
+        chunk.clear();
+        chunk.extend_from_slice(&(y as i32).to_le_bytes());
+        chunk.extend_from_slice(&((width * 3 * sample_size) as i32).to_le_bytes());
+        for channel in [|c: &Color| c.b, |c: &Color| c.g, |c: &Color| c.r] {
+            for c in row {
+                let v = channel(c) as f32;
+                match pixel_type {
+                    ExrPixelType::Half => chunk.extend_from_slice(&f32_to_f16(v).to_le_bytes()),
+                    ExrPixelType::Float => chunk.extend_from_slice(&v.to_le_bytes()),
+                }
+            }
+        }
+        out.write_all(&chunk)?;
+    }
+    Ok(())
+}
+
+/// Converts to IEEE 754 half precision, rounding to nearest even
+fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+
+    if exponent == 0xff {
+        // infinity stays infinity, NaN stays NaN
+        return sign | 0x7c00 | if mantissa != 0 {0x200} else {0};
+    }
+
+    let half_exponent = exponent - 127 + 15;
+    if half_exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+
+    if half_exponent <= 0 {
+        // subnormal half, or too small and flushed to zero
+        if half_exponent < -10 {
+            return sign;
+        }
+        let m = mantissa | 0x80_0000;
+        let shift = (14 - half_exponent) as u32;
+        let round_bit = 1 << (shift - 1);
+        let mut half_mantissa = m >> shift;
+        if m & round_bit != 0 && m & (3 * round_bit - 1) != 0 {
+            half_mantissa += 1;
+        }
+        return sign | half_mantissa as u16;
+    }
+
+    let mut half = ((half_exponent as u32) << 10) | (mantissa >> 13);
+    let rest = mantissa & 0x1fff;
+    if rest > 0x1000 || (rest == 0x1000 && half & 1 == 1) {
+        // may carry into the exponent, which correctly rounds up to infinity
+        half += 1;
+    }
+    sign | half as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{hdr::decode_hdr, texture::{decode_png, decode_ppm, srgb_to_linear}};
+
+    /// Two display encoded pixels that round to different 8 and 16-bit values
+    fn pixels() -> [Color; 2] {
+        [Color::new(1.0, 0.0, 0.5), Color::new(-0.5, 0.25, 2.0)]
+    }
+
+    #[test]
+    fn format_from_extension() {
+        let formats = [
+            ("image.ppm", ImageFormat::PpmBinary),
+            ("image.PNG", ImageFormat::Png),
+            ("out/image.pfm", ImageFormat::Pfm),
+            ("image.hdr", ImageFormat::Hdr),
+            ("image.tar.exr", ImageFormat::Exr),
+        ];
+        for (path, format) in formats {
+            assert_eq!(ImageFormat::from_path(Path::new(path)).unwrap(), format, "{path}");
+        }
+        for path in ["image.jpg", "image", "exr"] {
+            let error = ImageFormat::from_path(Path::new(path)).unwrap_err();
+            assert!(matches!(&error, OutputError::UnknownFormat(p) if p == Path::new(path)), "{path}: {error}");
+        }
+    }
+
+    /// Out of range values clamp, 16-bit samples are big endian
+    #[test]
+    fn sample_encoding() {
+        assert_eq!(encode_samples(&pixels(), BitDepth::Eight), [255, 0, 128, 0, 64, 255]);
+        assert_eq!(encode_samples(&pixels(), BitDepth::Sixteen), [0xff, 0xff, 0, 0, 0x80, 0x00, 0, 0, 0x40, 0x00, 0xff, 0xff]);
+    }
+
+    /// The text and binary PPMs differ only in the magic number and body
+    #[test]
+    fn ppm_ascii_and_binary() {
+        let mut ascii = Vec::new();
+        write_ppm_ascii(&mut ascii, 2, 1, &pixels(), BitDepth::Eight).unwrap();
+        assert_eq!(String::from_utf8(ascii).unwrap(), "P3\n2 1\n255\n255 0 128\n0 64 255\n");
+        let mut binary = Vec::new();
+        write_ppm_binary(&mut binary, 2, 1, &pixels(), BitDepth::Eight).unwrap();
+        assert_eq!(binary, [b"P6\n2 1\n255\n".as_slice(), &[255, 0, 128, 0, 64, 255]].concat());
+
+        let mut ascii = Vec::new();
+        write_ppm_ascii(&mut ascii, 1, 2, &pixels(), BitDepth::Sixteen).unwrap();
+        assert_eq!(String::from_utf8(ascii).unwrap(), "P3\n1 2\n65535\n65535 0 32768\n0 16384 65535\n");
+        let mut binary = Vec::new();
+        write_ppm_binary(&mut binary, 1, 2, &pixels(), BitDepth::Sixteen).unwrap();
+        let (header, body) = binary.split_at(13);
+        assert_eq!((header, body), (b"P6\n1 2\n65535\n".as_slice(), encode_samples(&pixels(), BitDepth::Sixteen).as_slice()));
+    }
+
+    /// PNG and both kinds of PPM decode to the quantized values, turned
+    /// back into linear light by the texture loader
+    #[test]
+    fn png_and_ppm_round_trip() {
+        let pixels: Vec<Color> = (0..12).map(|i| Color::new(i as f64 / 11.0, 1.0 - i as f64 / 13.0, (i as f64 * 0.37).fract())).collect();
+        for bit_depth in [BitDepth::Eight, BitDepth::Sixteen] {
+            let max = max_value(bit_depth) as f64;
+            let expected: Vec<Color> = pixels.iter()
+                .map(|c| {
+                    let [r, g, b] = match bit_depth {
+                        BitDepth::Eight => c.to_rgb8().map(u16::from),
+                        BitDepth::Sixteen => c.to_rgb16(),
+                    };
+                    Color::new(srgb_to_linear(r as f64 / max), srgb_to_linear(g as f64 / max), srgb_to_linear(b as f64 / max))
+                })
+                .collect();
+
+            let mut png = Vec::new();
+            write_png(&mut png, 4, 3, &pixels, bit_depth).unwrap();
+            let mut ascii = Vec::new();
+            write_ppm_ascii(&mut ascii, 4, 3, &pixels, bit_depth).unwrap();
+            let mut binary = Vec::new();
+            write_ppm_binary(&mut binary, 4, 3, &pixels, bit_depth).unwrap();
+
+            for image in [decode_png(png.as_slice()), decode_ppm(&ascii), decode_ppm(&binary)] {
+                let image = image.unwrap();
+                assert_eq!((image.width, image.height), (4, 3));
+                for (got, expected) in image.pixels.iter().zip(&expected) {
+                    assert!((got.r, got.g, got.b) == (expected.r, expected.g, expected.b), "{bit_depth:?}: {got:?} != {expected:?}");
+                }
+            }
+        }
+    }
+
+    /// Radiance files read back within the precision of their shared
+    /// exponent, whether the scanlines are run length encoded or flat
+    #[test]
+    fn rgbe_round_trip() {
+        for width in [3, 9] {
+            // runs of equal pixels and literals of changing ones
+            let pixels: Vec<Color> = (0..width * 2)
+                .map(|i| match i % 9 {
+                    0..=4 => Color::new(0.25, 1.0, 4.0),
+                    5 => Color::new(0.0, 0.0, 0.0),
+                    n => Color::new(n as f64 * 10.0, 0.01, 1e-5),
+                })
+                .collect();
+            let mut data = Vec::new();
+            write_rgbe(&mut data, width, 2, &pixels).unwrap();
+
+            let resolution = format!("-Y 2 +X {width}\n");
+            let header_len = data.windows(resolution.len()).position(|w| w == resolution.as_bytes()).unwrap() + resolution.len();
+            let encoded = data[header_len..header_len + 2] == [2, 2];
+            assert_eq!(encoded, width >= 8, "width {width}");
+            if encoded {
+                assert!(data.len() - header_len < pixels.len() * 4);
+            }
+
+            let image = decode_hdr(&data).unwrap();
+            assert_eq!((image.width, image.height), (width, 2));
+            for (got, expected) in image.pixels.iter().zip(&pixels) {
+                let tolerance = expected.r.max(expected.g).max(expected.b) / 128.0;
+                for (a, b) in [(got.r, expected.r), (got.g, expected.g), (got.b, expected.b)] {
+                    assert!(a <= b && b - a <= tolerance, "{got:?} != {expected:?}");
+                }
+            }
+        }
+
+        // too bright for the exponent, the brightest value there is instead of wrapping around to a dim one
+        let brightest = 255.0 * 2f64.powi(255 - 136);
+        let pixels = [Color::new(f64::INFINITY, 1.0, 0.0), Color::new(1e40, 2.0, 0.0), Color::new(f64::NAN, f64::NAN, f64::NAN)];
+        assert_eq!(pixels.map(to_rgbe), [[255, 0, 0, 255], [255, 0, 0, 255], [0, 0, 0, 0]]);
+        let mut data = Vec::new();
+        write_rgbe(&mut data, 3, 1, &pixels).unwrap();
+        let image = decode_hdr(&data).unwrap();
+        assert!(image.pixels[..2].iter().all(|p| p.r == brightest && p.g == 0.0));
+        assert_eq!(image.pixels[2].r, 0.0);
+    }
+
+    #[test]
+    fn half_floats() {
+        let cases = [
+            (0.0, 0x0000),
+            (-0.0, 0x8000),
+            (1.0, 0x3c00),
+            (-2.0, 0xc000),
+            (65504.0, 0x7bff),
+            // halfway to the next power of two rounds up to infinity
+            (65519.0, 0x7bff),
+            (65520.0, 0x7c00),
+            (1e6, 0x7c00),
+            (f32::INFINITY, 0x7c00),
+            (f32::NEG_INFINITY, 0xfc00),
+            // subnormals, the smallest and largest, and flushing to zero
+            (2f32.powi(-24), 0x0001),
+            (2f32.powi(-14) - 2f32.powi(-24), 0x03ff),
+            (2f32.powi(-14), 0x0400),
+            (2f32.powi(-26), 0x0000),
+            (-(2f32.powi(-26)), 0x8000),
+            // ties go to the even neighbour, in normals and subnormals
+            (1.0 + 2f32.powi(-11), 0x3c00),
+            (1.0 + 3.0 * 2f32.powi(-11), 0x3c02),
+            (1.0 + 2f32.powi(-11) + 2f32.powi(-20), 0x3c01),
+            (2f32.powi(-25), 0x0000),
+            (3.0 * 2f32.powi(-25), 0x0002),
+            (5.0 * 2f32.powi(-25), 0x0002),
+            (3.0 * 2f32.powi(-26), 0x0001),
+            (2f32.powi(-14) - 2f32.powi(-25), 0x0400),
+        ];
+        for (value, half) in cases {
+            assert_eq!(f32_to_f16(value), half, "{value:e}");
+        }
+        let nan = f32_to_f16(f32::NAN);
+        assert!(nan & 0x7c00 == 0x7c00 && nan & 0x3ff != 0, "{nan:#x}");
+    }
+
+    /// A 2x2 image has the required header attributes, its channels in
+    /// alphabetical order, and an offset table pointing at each scanline
+    #[test]
+    fn exr_layout() {
+        let pixels = [Color::new(1.0, 2.0, 3.0), Color::new(4.0, 5.0, 6.0), Color::new(7.0, 8.0, 9.0), Color::new(0.5, 0.25, 0.125)];
+        for (pixel_type, type_id, sample_size) in [(ExrPixelType::Half, 1, 2), (ExrPixelType::Float, 2, 4)] {
+            let mut data = Vec::new();
+            write_exr(&mut data, 2, 2, &pixels, pixel_type).unwrap();
+            assert_eq!(data[..8], [0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0]);
+
+            let i32_at = |pos: usize| i32::from_le_bytes(data[pos..pos + 4].try_into().unwrap());
+            let string_at = |pos: usize| {
+                let end = pos + data[pos..].iter().position(|&b| b == 0).unwrap();
+                (std::str::from_utf8(&data[pos..end]).unwrap(), end + 1)
+            };
+            let mut attributes = Vec::new();
+            let mut pos = 8;
+            while data[pos] != 0 {
+                let (name, next) = string_at(pos);
+                let (kind, next) = string_at(next);
+                let size = i32_at(next) as usize;
+                attributes.push((name, kind, &data[next + 4..next + 4 + size]));
+                pos = next + 4 + size;
+            }
+            let header_len = pos + 1;
+
+            let names: Vec<_> = attributes.iter().map(|(name, kind, _)| format!("{name}:{kind}")).collect();
+            assert_eq!(names, [
+                "channels:chlist", "compression:compression", "dataWindow:box2i", "displayWindow:box2i",
+                "lineOrder:lineOrder", "pixelAspectRatio:float", "screenWindowCenter:v2f", "screenWindowWidth:float",
+            ]);
+            let channels = attributes[0].2;
+            assert_eq!(channels.len(), 3 * 18 + 1);
+            for (i, name) in [b'B', b'G', b'R'].into_iter().enumerate() {
+                let channel = &channels[i * 18..(i + 1) * 18];
+                assert_eq!(channel[..2], [name, 0]);
+                assert_eq!(i32::from_le_bytes(channel[2..6].try_into().unwrap()), type_id);
+            }
+            assert_eq!(attributes[1].2, [0]);
+            let window: Vec<i32> = attributes[2].2.chunks(4).map(|b| i32::from_le_bytes(b.try_into().unwrap())).collect();
+            assert_eq!(window, [0, 0, 1, 1]);
+
+            let scanline_len = 2 * 3 * sample_size;
+            let offsets: Vec<usize> = (0..2).map(|y| u64::from_le_bytes(data[header_len + y * 8..header_len + y * 8 + 8].try_into().unwrap()) as usize).collect();
+            assert_eq!(offsets, [header_len + 16, header_len + 16 + 8 + scanline_len]);
+            assert_eq!(data.len(), offsets[1] + 8 + scanline_len);
+            for (y, &offset) in offsets.iter().enumerate() {
+                assert_eq!((i32_at(offset), i32_at(offset + 4)), (y as i32, scanline_len as i32));
+                // a whole row of blue, then green, then red
+                let row = &pixels[y * 2..y * 2 + 2];
+                let expected = row.iter().map(|c| c.b).chain(row.iter().map(|c| c.g)).chain(row.iter().map(|c| c.r));
+                for (i, value) in expected.enumerate() {
+                    let sample = &data[offset + 8 + i * sample_size..offset + 8 + (i + 1) * sample_size];
+                    match pixel_type {
+                        ExrPixelType::Half => assert_eq!(u16::from_le_bytes(sample.try_into().unwrap()), f32_to_f16(value as f32)),
+                        ExrPixelType::Float => assert_eq!(f32::from_le_bytes(sample.try_into().unwrap()), value as f32),
+                    }
+                }
+            }
+        }
+    }
+}
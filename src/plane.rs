@@ -0,0 +1,110 @@
+use std::f64::consts::PI;
+
+use crate::{aabb::Aabb, hittable::{HitRecord, Hittable}, interval::Interval, material::Material, onb::Onb, ray::Ray, sampler::sample_unit_disk, triangle::face_forward, vec3::Vec3};
+
+/// Where `ray` crosses the plane through `point` with unit `normal`, `None`
+/// if that's outside `ray_t` or the ray runs along the plane
+pub(crate) fn intersect_plane(ray: &Ray, ray_t: Interval, point: Vec3, normal: Vec3) -> Option<f64> {
+    let denom = normal.dot(ray.direction);
+    if denom.abs() <= ray.direction.len() * 1e-12 {
+        return None;
+    }
+    let t = normal.dot(point - ray.origin) / denom;
+    ray_t.surrounds(t).then_some(t)
+}
+
+/// Density per solid angle of a point picked uniformly on a flat surface of
+/// `area` with unit `normal`, seen `distance` away along unit `direction`
+pub(crate) fn area_pdf(distance: f64, direction: Vec3, normal: Vec3, area: f64) -> f64 {
+    let cosine = direction.dot(normal).abs();
+    if cosine <= 0.0 {
+        return 0.0;
+    }
+    distance * distance / (cosine * area)
+}
+
+/// An infinite plane, for floors and walls without the curvature of a huge
+/// sphere. Its UVs are distances along two directions in the plane, so
+/// textures tile once per unit.
+#[derive(Clone)]
+pub struct Plane {
+    pub point: Vec3,
+    pub normal: Vec3,
+    pub mat: Material,
+    frame: Onb,
+}
+
+impl Plane {
+    /// `normal` doesn't have to be a unit vector, it picks the front face
+    pub fn new(point: Vec3, normal: Vec3, matterial: Material) -> Hittable {
+        let normal = normal.unit();
+        Hittable::Plane(Box::new(Self { point, normal, mat: matterial, frame: Onb::new(normal) }))
+    }
+}
+
+impl Plane {
+    pub fn bounding_box(&self) -> Aabb {
+        Aabb::everything()
+    }
+    pub fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let t = intersect_plane(ray, ray_t, self.point, self.normal)?;
+        let p = ray.at(t);
+        let (normal, front_face) = face_forward(ray, self.normal, self.normal);
+        let local = p - self.point;
+        Some(HitRecord { p, normal, t, front_face, mat: &self.mat, u: local.dot(self.frame.u), v: local.dot(self.frame.v) })
+    }
+}
+
+/// A flat disk, `u` goes around it and `v` from the center to the rim
+#[derive(Clone)]
+pub struct Disk {
+    pub center: Vec3,
+    pub normal: Vec3,
+    pub radius: f64,
+    pub mat: Material,
+    frame: Onb,
+}
+
+impl Disk {
+    /// `normal` doesn't have to be a unit vector, it picks the front face
+    pub fn new(center: Vec3, normal: Vec3, radius: f64, matterial: Material) -> Hittable {
+        let normal = normal.unit();
+        Hittable::Disk(Box::new(Self { center, normal, radius, mat: matterial, frame: Onb::new(normal) }))
+    }
+}
+
+impl Disk {
+    pub fn bounding_box(&self) -> Aabb {
+        // the rim reaches radius * sin(angle between the normal and the axis) along each axis
+        let n = self.normal;
+        let extent = |c: f64| self.radius * (1.0 - c * c).max(0.0).sqrt();
+        let e = Vec3::new(extent(n.x), extent(n.y), extent(n.z));
+        Aabb::new(self.center - e, self.center + e).pad(1e-4)
+    }
+    pub fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let t = intersect_plane(ray, ray_t, self.center, self.normal)?;
+        let p = ray.at(t);
+        let local = self.frame.to_local(p - self.center);
+        let r = (local.x * local.x + local.y * local.y).sqrt();
+        if r > self.radius {
+            return None;
+        }
+        let (normal, front_face) = face_forward(ray, self.normal, self.normal);
+        let u = (local.y.atan2(local.x) + PI) / (2.0 * PI);
+        Some(HitRecord { p, normal, t, front_face, mat: &self.mat, u, v: r / self.radius })
+    }
+    pub fn area(&self) -> f64 {
+        PI * self.radius * self.radius
+    }
+    pub fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f64 {
+        let direction = direction.unit();
+        match self.hit(&Ray::new(origin, direction), Interval::new(0.001, f64::INFINITY)) {
+            Some(rec) => area_pdf(rec.t, direction, self.normal, self.area()),
+            None => 0.0,
+        }
+    }
+    pub fn sample(&self, origin: Vec3, uv: (f64, f64)) -> Vec3 {
+        let (x, y) = sample_unit_disk(uv);
+        self.center + self.frame.to_world(Vec3::new(x, y, 0.0)) * self.radius - origin
+    }
+}
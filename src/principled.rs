@@ -0,0 +1,285 @@
+use std::f64::consts::PI;
+
+use crate::{color::Color, hittable::HitRecord, material::{local_frame, Material, MaterialRecord, RoughDielectric}, microfacet::{reflect, TrowbridgeReitz}, ray::Ray, sampler::{sample_cosine_hemisphere, Sampler}, texture::Texture, vec3::Vec3};
+
+/// Lower bound for the roughness of every lobe, smoother surfaces would need
+/// the specular code path the mixture can't take
+const MIN_ROUGHNESS: f64 = 0.04;
+
+/// Reflectance of the clear coat at normal incidence, a polyurethane varnish
+const CLEARCOAT_F0: f64 = 0.04;
+
+/// One material for most surfaces, loosely following the Disney principled
+/// BSDF (Burley 2012, 2015).
+///
+/// The parameters blend a diffuse base with sheen, a GGX specular lobe that
+/// turns into a metal with `metallic`, a rough glass lobe for `transmission`
+/// and a clear coat layered on top. The lobe weights add up to at most one,
+/// and light reflected by the specular layers is taken away from the layers
+/// below, so no combination of parameters creates energy.
+#[derive(Debug, Clone)]
+pub struct Principled {
+    pub base_color: Texture,
+    /// 0 for dielectrics, 1 for metals tinted by `base_color`
+    pub metallic: Texture,
+    /// From 0 (polished) to 1, shared by the specular and transmission lobes
+    pub roughness: Texture,
+    /// Dielectric reflectance at normal incidence, 0.5 is the usual 4%
+    pub specular: f64,
+    /// Soft grazing highlight for cloth
+    pub sheen: f64,
+    /// Blends the sheen from white to `base_color`
+    pub sheen_tint: f64,
+    pub clearcoat: f64,
+    pub clearcoat_roughness: f64,
+    /// 0 is opaque, 1 is glass tinted by `base_color`
+    pub transmission: f64,
+    /// Index of refraction of the transmission lobe
+    pub ior: f64,
+    /// Radiance given off by the front face
+    pub emission: Texture,
+}
+
+impl Default for Principled {
+    /// Grey plastic
+    fn default() -> Self {
+        Principled {
+            base_color: Texture::from(Color::new(0.8, 0.8, 0.8)),
+            metallic: scalar_texture(0.0),
+            roughness: scalar_texture(0.5),
+            specular: 0.5,
+            sheen: 0.0,
+            sheen_tint: 0.5,
+            clearcoat: 0.0,
+            clearcoat_roughness: 0.03,
+            transmission: 0.0,
+            ior: 1.5,
+            emission: Texture::from(Color::new(0.0, 0.0, 0.0)),
+        }
+    }
+}
+
+impl From<Principled> for Material {
+    fn from(principled: Principled) -> Self {
+        Material::Principled(Box::new(principled))
+    }
+}
+
+/// A texture returning `value` in every channel, for the scalar parameters
+pub fn scalar_texture(value: f64) -> Texture {
+    Texture::from(Color::new(value, value, value))
+}
+
+/// `(1 - cos)^5`, the interpolation weight of Schlick's Fresnel approximation
+fn schlick_weight(cos: f64) -> f64 {
+    (1.0 - cos.clamp(0.0, 1.0)).powi(5)
+}
+
+fn schlick(f0: f64, cos: f64) -> f64 {
+    f0 + (1.0 - f0) * schlick_weight(cos)
+}
+
+/// The parameters evaluated at one hit, with the lobe weights derived from them
+struct Lobes {
+    base_color: Color,
+    metallic: f64,
+    /// Weight of the diffuse, sheen and dielectric specular part
+    dielectric: f64,
+    transmission: f64,
+    /// Dielectric reflectance at normal incidence
+    f0: f64,
+    sheen: Color,
+    clearcoat: f64,
+    specular: TrowbridgeReitz,
+    coat: TrowbridgeReitz,
+    glass: RoughDielectric,
+    /// Relative index of refraction across the surface, as seen from `wo`
+    eta: f64,
+    front_face: bool,
+}
+
+impl Lobes {
+    /// Share of light the clear coat lets through in direction `w`, twice for the way in and out
+    fn coat_transmittance(&self, w: Vec3) -> f64 {
+        1.0 - self.clearcoat * schlick(CLEARCOAT_F0, w.z.abs())
+    }
+    /// Probabilities of sampling the diffuse, specular, transmission and clear coat lobes
+    fn sampling_weights(&self, wo: Vec3) -> [f64; 4] {
+        let fresnel = schlick(self.f0, wo.z);
+        let weights = [
+            self.dielectric * (1.0 - fresnel),
+            self.metallic + self.dielectric * fresnel.max(0.1),
+            self.transmission,
+            self.clearcoat * schlick(CLEARCOAT_F0, wo.z).max(0.1),
+        ];
+        let total: f64 = weights.iter().sum();
+        weights.map(|w| w / total)
+    }
+
+    fn eval(&self, wo: Vec3, wi: Vec3) -> Color {
+        let mut value = Color::new(0.0, 0.0, 0.0);
+        let mut coat = Color::new(0.0, 0.0, 0.0);
+
+        if wi.z > 0.0 {
+            let wh = (wo + wi).unit();
+            let cos_d = wi.dot(wh);
+
+            // diffuse and sheen, under the dielectric specular layer
+            let diffuse = (1.0 - schlick(self.f0, wo.z)) * (1.0 - schlick(self.f0, wi.z)) / PI;
+            value = value + (self.base_color * diffuse + self.sheen * schlick_weight(cos_d)) * (self.dielectric * wi.z);
+
+            let metal = Color::new(
+                schlick(self.base_color.r, wo.dot(wh)),
+                schlick(self.base_color.g, wo.dot(wh)),
+                schlick(self.base_color.b, wo.dot(wh)),
+            );
+            let fresnel = metal * self.metallic + Color::new(1.0, 1.0, 1.0) * (schlick(self.f0, wo.dot(wh)) * self.dielectric);
+            value = value + fresnel * (self.specular.d(wh) * self.specular.g(wo, wi) / (4.0 * wo.z));
+
+            let coat_value = self.clearcoat * schlick(CLEARCOAT_F0, wo.dot(wh)) * self.coat.d(wh) * self.coat.g(wo, wi) / (4.0 * wo.z);
+            coat = Color::new(coat_value, coat_value, coat_value);
+        }
+
+        if self.transmission > 0.0 {
+            let glass = self.glass.eval_local(wo, wi, self.eta) * self.transmission;
+            // only light entering the surface picks up the base color
+            value = value + if wi.z < 0.0 && self.front_face {glass * self.base_color} else {glass};
+        }
+
+        value * (self.coat_transmittance(wo) * self.coat_transmittance(wi)) + coat
+    }
+
+    fn pdf(&self, wo: Vec3, wi: Vec3) -> f64 {
+        let [diffuse, specular, transmission, clearcoat] = self.sampling_weights(wo);
+        let mut pdf = 0.0;
+        if wi.z > 0.0 {
+            let wh = (wo + wi).unit();
+            let reflection = 1.0 / (4.0 * wo.dot(wh).abs());
+            pdf += diffuse * wi.z / PI;
+            pdf += specular * self.specular.pdf(wo, wh) * reflection;
+            pdf += clearcoat * self.coat.pdf(wo, wh) * reflection;
+        }
+        if transmission > 0.0 {
+            pdf += transmission * self.glass.pdf_local(wo, wi, self.eta);
+        }
+        pdf
+    }
+}
+
+impl Principled {
+    fn lobes(&self, rec: &HitRecord) -> Lobes {
+        let scalar = |texture: &Texture| {
+            let c = texture.value(rec.u, rec.v, rec.p);
+            ((c.r + c.g + c.b) / 3.0).clamp(0.0, 1.0)
+        };
+        let base_color = self.base_color.value(rec.u, rec.v, rec.p);
+        let metallic = scalar(&self.metallic);
+        let roughness = scalar(&self.roughness).max(MIN_ROUGHNESS);
+        let transmission = self.transmission.clamp(0.0, 1.0);
+        let tint = if base_color.luminance() > 0.0 {base_color / base_color.luminance()} else {Color::new(1.0, 1.0, 1.0)};
+        let sheen_color = Color::new(1.0, 1.0, 1.0) * (1.0 - self.sheen_tint) + tint * self.sheen_tint;
+
+        let glass = RoughDielectric { refraction_index: self.ior, distribution: TrowbridgeReitz::new(roughness) };
+        Lobes {
+            base_color,
+            metallic,
+            dielectric: (1.0 - metallic) * (1.0 - transmission),
+            transmission: (1.0 - metallic) * transmission,
+            f0: (0.08 * self.specular).clamp(0.0, 1.0),
+            sheen: sheen_color * self.sheen,
+            clearcoat: self.clearcoat.clamp(0.0, 1.0),
+            specular: TrowbridgeReitz::new(roughness),
+            coat: TrowbridgeReitz::new(self.clearcoat_roughness.max(MIN_ROUGHNESS)),
+            eta: glass.eta(rec),
+            glass,
+            front_face: rec.front_face,
+        }
+    }
+
+    pub(crate) fn scatter(&self, ray: &Ray, rec: &HitRecord, sampler: &mut Sampler) -> Option<MaterialRecord> {
+        let (frame, wo) = local_frame(ray, rec);
+        let uv = sampler.get_2d();
+        let lobe_choice = sampler.get_1d();
+        let fresnel_choice = sampler.get_1d();
+        if wo.z <= 0.0 {
+            return None;
+        }
+
+        let lobes = self.lobes(rec);
+        let [diffuse, specular, transmission, _] = lobes.sampling_weights(wo);
+        let wi = if lobe_choice < diffuse {
+            sample_cosine_hemisphere(uv)
+        } else if lobe_choice < diffuse + specular {
+            reflect(wo, lobes.specular.sample_wm(wo, uv))
+        } else if lobe_choice < diffuse + specular + transmission {
+            lobes.glass.sample_local(wo, lobes.eta, uv, fresnel_choice)?
+        } else {
+            reflect(wo, lobes.coat.sample_wm(wo, uv))
+        };
+
+        let pdf = lobes.pdf(wo, wi);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(MaterialRecord { attenuation: lobes.eval(wo, wi), scattered: Ray::new(rec.p, frame.to_world(wi)), pdf, specular: false })
+    }
+    pub(crate) fn eval(&self, ray: &Ray, rec: &HitRecord, direction: Vec3) -> Color {
+        let (frame, wo) = local_frame(ray, rec);
+        if wo.z <= 0.0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        self.lobes(rec).eval(wo, frame.to_local(direction.unit()))
+    }
+    pub(crate) fn pdf(&self, ray: &Ray, rec: &HitRecord, direction: Vec3) -> f64 {
+        let (frame, wo) = local_frame(ray, rec);
+        if wo.z <= 0.0 {
+            return 0.0;
+        }
+        self.lobes(rec).pdf(wo, frame.to_local(direction.unit()))
+    }
+    pub(crate) fn is_emissive(&self) -> bool {
+        !matches!(self.emission, Texture::Solid(color) if color.luminance() <= 0.0)
+    }
+    pub(crate) fn emitted(&self, rec: &HitRecord) -> Color {
+        if rec.front_face {self.emission.value(rec.u, rec.v, rec.p)} else {Color::new(0.0, 0.0, 0.0)}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler::SamplerKind;
+
+    /// Lit evenly from every direction, no parameter combination may reflect
+    /// more than it receives (a white furnace test)
+    #[test]
+    fn conserves_energy() {
+        let normal = Vec3::new(0.0, 0.0, 1.0);
+        let white = Color::new(1.0, 1.0, 1.0);
+        let variants = [
+            Principled { base_color: Texture::from(white), roughness: scalar_texture(0.2), ..Default::default() },
+            Principled { base_color: Texture::from(white), metallic: scalar_texture(1.0), roughness: scalar_texture(0.6), ..Default::default() },
+            Principled { base_color: Texture::from(white), sheen: 1.0, clearcoat: 1.0, specular: 1.0, ..Default::default() },
+            Principled { base_color: Texture::from(white), transmission: 1.0, ior: 1.33, roughness: scalar_texture(0.3), ..Default::default() },
+        ];
+        for principled in variants {
+            let mat = Material::from(principled);
+            let rec = HitRecord { p: Vec3::zero(), normal, t: 1.0, front_face: true, mat: &mat, u: 0.0, v: 0.0 };
+            for cos_o in [0.2_f64, 0.6, 1.0] {
+                let wo = Vec3::new((1.0 - cos_o * cos_o).sqrt(), 0.0, cos_o);
+                let ray = Ray::new(wo, -wo);
+                let mut sampler = Sampler::new(SamplerKind::Sobol, 1 << 14, 7, 0);
+                let mut albedo = 0.0;
+                for i in 0..1 << 14 {
+                    sampler.start_sample(i);
+                    if let Some(record) = mat.scatter(&ray, &rec, &mut sampler) {
+                        assert!((record.pdf - mat.pdf(&ray, &rec, record.scattered.direction)).abs() <= 1e-9 * record.pdf);
+                        albedo += record.attenuation.g / record.pdf / (1 << 14) as f64;
+                    }
+                }
+                assert!(albedo < 1.01, "{mat:?} at cos {cos_o}: {albedo}");
+                assert!(albedo > 0.4, "{mat:?} at cos {cos_o}: {albedo}");
+            }
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use crate::{aabb::Aabb, hittable::{HitRecord, Hittable}, interval::Interval, material::Material, plane::{area_pdf, intersect_plane}, ray::Ray, triangle::face_forward, vec3::{cross, Vec3}};
+
+/// A parallelogram with corner `q` and edges `u` and `v`. The front face is
+/// the one `cross(u, v)` points out of, and the UVs run along the edges.
+#[derive(Clone)]
+pub struct Quad {
+    pub q: Vec3,
+    pub u: Vec3,
+    pub v: Vec3,
+    pub mat: Material,
+    normal: Vec3,
+    /// `n / (n . n)` for the unnormalized normal n, turns a point in the plane into edge coordinates
+    w: Vec3,
+    area: f64,
+}
+
+impl Quad {
+    pub fn new(q: Vec3, u: Vec3, v: Vec3, matterial: Material) -> Hittable {
+        Hittable::Quad(Box::new(Self::build(q, u, v, matterial)))
+    }
+    fn build(q: Vec3, u: Vec3, v: Vec3, matterial: Material) -> Self {
+        let n = cross(u, v);
+        Self { q, u, v, mat: matterial, normal: n.unit(), w: n / n.len_squared(), area: n.len() }
+    }
+}
+
+impl Quad {
+    pub fn bounding_box(&self) -> Aabb {
+        Aabb::from_points(self.q, self.q + self.u + self.v).union(Aabb::from_points(self.q + self.u, self.q + self.v)).pad(1e-4)
+    }
+    pub fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let t = intersect_plane(ray, ray_t, self.q, self.normal)?;
+        let p = ray.at(t);
+        let planar = p - self.q;
+        let alpha = self.w.dot(cross(planar, self.v));
+        let beta = self.w.dot(cross(self.u, planar));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+        let (normal, front_face) = face_forward(ray, self.normal, self.normal);
+        Some(HitRecord { p, normal, t, front_face, mat: &self.mat, u: alpha, v: beta })
+    }
+    pub fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f64 {
+        let direction = direction.unit();
+        match self.hit(&Ray::new(origin, direction), Interval::new(0.001, f64::INFINITY)) {
+            Some(rec) => area_pdf(rec.t, direction, self.normal, self.area),
+            None => 0.0,
+        }
+    }
+    pub fn sample(&self, origin: Vec3, (a, b): (f64, f64)) -> Vec3 {
+        self.q + self.u * a + self.v * b - origin
+    }
+}
+
+/// A box made of six quads facing outwards, axis aligned or turned any way
+#[derive(Clone)]
+pub struct Cuboid {
+    faces: [Quad; 6],
+    bbox: Aabb,
+}
+
+impl Cuboid {
+    /// The axis aligned box between two opposite corners
+    pub fn new(a: Vec3, b: Vec3, matterial: Material) -> Hittable {
+        let (min, max) = (a.min(b), a.max(b));
+        let d = max - min;
+        Self::oriented(min, [Vec3::new(d.x, 0.0, 0.0), Vec3::new(0.0, d.y, 0.0), Vec3::new(0.0, 0.0, d.z)], matterial)
+    }
+    /// The box spanned by three edges leaving `corner`, normally at right angles
+    pub fn oriented(corner: Vec3, edges: [Vec3; 3], matterial: Material) -> Hittable {
+        // right handed edges make every cross product below point out of the box
+        let [a, b, c] = if edges[0].dot(cross(edges[1], edges[2])) < 0.0 {[edges[1], edges[0], edges[2]]} else {edges};
+        let faces = [
+            Quad::build(corner, b, a, matterial.clone()),
+            Quad::build(corner + c, a, b, matterial.clone()),
+            Quad::build(corner, c, b, matterial.clone()),
+            Quad::build(corner + a, b, c, matterial.clone()),
+            Quad::build(corner, a, c, matterial.clone()),
+            Quad::build(corner + b, c, a, matterial),
+        ];
+        let bbox = faces.iter().fold(Aabb::empty(), |bbox, face| bbox.union(face.bounding_box()));
+        Hittable::Cuboid(Box::new(Cuboid { faces, bbox }))
+    }
+    pub fn material(&self) -> &Material {
+        &self.faces[0].mat
+    }
+}
+
+impl Cuboid {
+    pub fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+    pub fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let mut temp_rec = None::<HitRecord<'_>>;
+        let mut closest = ray_t.max;
+        for face in &self.faces {
+            if let Some(x) = face.hit(ray, Interval::new(ray_t.min, closest)) {
+                closest = x.t;
+                temp_rec = Some(x);
+            }
+        }
+        temp_rec
+    }
+    /// `sample` picks one of the faces at random, so this is their average
+    pub fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f64 {
+        self.faces.iter().map(|face| face.pdf_value(origin, direction)).sum::<f64>() / 6.0
+    }
+    pub fn sample(&self, origin: Vec3, (u, v): (f64, f64)) -> Vec3 {
+        let scaled = u * 6.0;
+        let index = (scaled as usize).min(5);
+        self.faces[index].sample(origin, (scaled - index as f64, v))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::*;
+    use crate::{plane::{Disk, Plane}, sampler::{sample_unit_sphere, Sampler, SamplerKind}, test_utils::{hit, material}};
+
+    /// Hits on flat shapes land where expected, with the right UVs and a
+    /// normal facing the ray
+    #[test]
+    fn flat_shapes() {
+        let quad = Quad::new(Vec3::new(-1.0, -1.0, -3.0), Vec3::new(2.0, 0.0, 0.0), Vec3::new(0.0, 4.0, 0.0), material());
+        let rec = hit(&quad, Vec3::new(0.5, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0)).unwrap();
+        assert!((rec.t - 3.0).abs() < 1e-12 && rec.front_face);
+        assert!((rec.u - 0.75).abs() < 1e-12 && (rec.v - 0.25).abs() < 1e-12);
+        assert!(hit(&quad, Vec3::new(1.5, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0)).is_none());
+
+        let plane = Plane::new(Vec3::new(0.0, -1.0, 0.0), Vec3::new(0.0, 1.0, 0.0), material());
+        let rec = hit(&plane, Vec3::new(0.0, -3.0, 0.0), Vec3::new(1.0, 1.0, 0.0)).unwrap();
+        assert!((rec.p - Vec3::new(2.0, -1.0, 0.0)).len() < 1e-12);
+        assert!(!rec.front_face && (rec.normal - Vec3::new(0.0, -1.0, 0.0)).len() < 1e-12);
+        assert!(hit(&plane, Vec3::zero(), Vec3::new(1.0, 0.0, 0.0)).is_none());
+
+        let disk = Disk::new(Vec3::new(0.0, 0.0, -2.0), Vec3::new(0.0, 0.0, 1.0), 1.0, material());
+        let rec = hit(&disk, Vec3::new(0.0, 0.5, 0.0), Vec3::new(0.0, 0.0, -1.0)).unwrap();
+        assert!((rec.v - 0.5).abs() < 1e-12 && rec.front_face);
+        assert!(hit(&disk, Vec3::new(0.8, 0.8, 0.0), Vec3::new(0.0, 0.0, -1.0)).is_none());
+        let bbox = disk.bounding_box();
+        assert!(bbox.max.x >= 1.0 && bbox.max.y >= 1.0 && bbox.max.z - bbox.min.z < 1e-3);
+    }
+
+    /// A turned box is entered from outside through a face pointing at the
+    /// ray, and left from inside through a back face
+    #[test]
+    fn oriented_box() {
+        let (sin, cos) = (PI / 4.0).sin_cos();
+        // a unit cube turned 45 degrees around y, centered at the origin, with left handed edges
+        let edges = [Vec3::new(cos, 0.0, -sin), Vec3::new(sin, 0.0, cos), Vec3::new(0.0, 1.0, 0.0)];
+        let cuboid = Cuboid::oriented(Vec3::new(-cos, -0.5, 0.0), edges, material());
+
+        let rec = hit(&cuboid, Vec3::new(0.1, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0)).unwrap();
+        assert!((rec.t - (5.1 - 2f64.sqrt() / 2.0)).abs() < 1e-9 && rec.front_face);
+        assert!(rec.normal.dot(Vec3::new(0.0, 0.0, 1.0)) > 0.7);
+
+        let rec = hit(&cuboid, Vec3::zero(), Vec3::new(0.0, 1.0, 0.0)).unwrap();
+        assert!((rec.t - 0.5).abs() < 1e-9 && !rec.front_face);
+        assert!((rec.normal - Vec3::new(0.0, -1.0, 0.0)).len() < 1e-9);
+
+        let bbox = cuboid.bounding_box();
+        assert!((bbox.max.x - 2f64.sqrt() / 2.0).abs() < 1e-3 && (bbox.max.y - 0.5).abs() < 1e-3);
+    }
+
+    /// Light sampling densities integrate to one over the directions they cover
+    #[test]
+    fn pdfs_integrate_to_one() {
+        let shapes = [
+            Quad::new(Vec3::new(-1.0, 2.0, -1.0), Vec3::new(2.0, 0.0, 0.0), Vec3::new(0.0, 0.5, 1.5), material()),
+            Disk::new(Vec3::new(0.5, -2.0, 1.0), Vec3::new(1.0, 2.0, 0.0), 0.8, material()),
+            Cuboid::new(Vec3::new(1.0, 1.0, 1.0), Vec3::new(2.0, 1.5, 3.0), material()),
+        ];
+        let origin = Vec3::zero();
+        for shape in &shapes {
+            let mut sampler = Sampler::new(SamplerKind::Sobol, 1 << 16, 5, 0);
+            let mut integral = 0.0;
+            for i in 0..1 << 16 {
+                sampler.start_sample(i);
+                integral += shape.pdf_value(origin, sample_unit_sphere(sampler.get_2d())) * 4.0 * PI / (1 << 16) as f64;
+                let direction = shape.sample(origin, sampler.get_2d());
+                assert!(shape.pdf_value(origin, direction) > 0.0);
+            }
+            assert!((integral - 1.0).abs() < 0.02, "{integral}");
+        }
+    }
+}
@@ -0,0 +1,315 @@
+use std::f64::consts::PI;
+
+use crate::{aabb::Aabb, hittable::{HitRecord, Hittable}, interval::Interval, material::Material, onb::Onb, ray::Ray, triangle::face_forward, vec3::Vec3};
+
+/// A frame with `w` along a shape's axis. Shapes around an axis are
+/// intersected in it, rotating the ray keeps `t` the same.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct AxisFrame {
+    origin: Vec3,
+    onb: Onb,
+}
+
+impl AxisFrame {
+    pub(crate) fn new(origin: Vec3, axis: Vec3) -> Self {
+        Self { origin, onb: Onb::new(axis.unit()) }
+    }
+    /// Origin and direction of `ray` in the frame
+    pub(crate) fn local_ray(&self, ray: &Ray) -> (Vec3, Vec3) {
+        (self.onb.to_local(ray.origin - self.origin), self.onb.to_local(ray.direction))
+    }
+    /// The world box around the frame's box from `min` to `max`
+    pub(crate) fn bounding_box(&self, min: Vec3, max: Vec3) -> Aabb {
+        Aabb::new(min, max).corners().iter().fold(Aabb::empty(), |bbox, &corner| bbox.grow(self.origin + self.onb.to_world(corner)))
+    }
+    pub(crate) fn record<'a>(&self, ray: &Ray, hit: LocalHit, mat: &'a Material) -> HitRecord<'a> {
+        let outward = self.onb.to_world(hit.normal).unit();
+        let (normal, front_face) = face_forward(ray, outward, outward);
+        HitRecord { p: ray.at(hit.t), normal, t: hit.t, front_face, mat, u: hit.uv.0, v: hit.uv.1 }
+    }
+}
+
+/// A hit found in an `AxisFrame`, with the outward normal in the frame
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct LocalHit {
+    pub t: f64,
+    pub normal: Vec3,
+    pub uv: (f64, f64),
+}
+
+/// Keeps the nearest of the hits offered to it that lie within `ray_t`
+pub(crate) struct Nearest {
+    ray_t: Interval,
+    pub hit: Option<LocalHit>,
+}
+
+impl Nearest {
+    pub(crate) fn new(ray_t: Interval) -> Self {
+        Self { ray_t, hit: None }
+    }
+    pub(crate) fn offer(&mut self, t: f64, normal: Vec3, uv: (f64, f64)) {
+        if self.ray_t.surrounds(t) && self.hit.is_none_or(|hit| t < hit.t) {
+            self.hit = Some(LocalHit { t, normal, uv });
+        }
+    }
+}
+
+/// Real roots of `a t^2 + b t + c` in increasing order, computed without
+/// cancellation. A linear equation gives its root twice.
+pub(crate) fn solve_quadratic(a: f64, b: f64, c: f64) -> Option<(f64, f64)> {
+    if a == 0.0 {
+        return (b != 0.0).then(|| (-c / b, -c / b));
+    }
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let q = -0.5 * (b + discriminant.sqrt().copysign(b));
+    if q == 0.0 {
+        return Some((0.0, 0.0));
+    }
+    let (t0, t1) = (q / a, c / q);
+    Some(if t0 < t1 {(t0, t1)} else {(t1, t0)})
+}
+
+/// Angle around the frame's axis in [0, 1], starting at -u
+pub(crate) fn around(p: Vec3) -> f64 {
+    (p.y.atan2(p.x) + PI) / (2.0 * PI)
+}
+
+/// Where a ray crosses the plane `z = height` within `radius` of the axis
+fn cap(o: Vec3, d: Vec3, height: f64, radius: f64) -> Option<(f64, Vec3)> {
+    if d.z == 0.0 {
+        return None;
+    }
+    let t = (height - o.z) / d.z;
+    let p = o + d * t;
+    (p.x * p.x + p.y * p.y <= radius * radius).then_some((t, p))
+}
+
+/// Hits on the infinite cylinder of `radius` around the z axis between z = 0 and `height`
+fn tube(nearest: &mut Nearest, o: Vec3, d: Vec3, radius: f64, height: f64, v: impl Fn(f64) -> f64) {
+    let Some((t0, t1)) = solve_quadratic(d.x * d.x + d.y * d.y, 2.0 * (o.x * d.x + o.y * d.y), o.x * o.x + o.y * o.y - radius * radius) else {
+        return;
+    };
+    for t in [t0, t1] {
+        let p = o + d * t;
+        if (0.0..=height).contains(&p.z) {
+            nearest.offer(t, Vec3::new(p.x, p.y, 0.0), (around(p), v(p.z)));
+        }
+    }
+}
+
+/// A closed cylinder. On the side `u` goes around the axis and `v` from the
+/// bottom to the top, on the caps `v` goes from the center to the rim.
+#[derive(Clone)]
+pub struct Cylinder {
+    pub radius: f64,
+    pub height: f64,
+    pub mat: Material,
+    frame: AxisFrame,
+}
+
+impl Cylinder {
+    /// The cylinder from the center of its bottom cap `base` to the center of
+    /// its top cap `top`, which must differ
+    pub fn new(base: Vec3, top: Vec3, radius: f64, matterial: Material) -> Hittable {
+        Hittable::Cylinder(Box::new(Self { radius, height: (top - base).len(), mat: matterial, frame: AxisFrame::new(base, top - base) }))
+    }
+}
+
+impl Cylinder {
+    pub fn bounding_box(&self) -> Aabb {
+        let r = self.radius;
+        self.frame.bounding_box(Vec3::new(-r, -r, 0.0), Vec3::new(r, r, self.height))
+    }
+    pub fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let (o, d) = self.frame.local_ray(ray);
+        let mut nearest = Nearest::new(ray_t);
+        tube(&mut nearest, o, d, self.radius, self.height, |z| z / self.height);
+        for (height, normal) in [(0.0, -1.0), (self.height, 1.0)] {
+            if let Some((t, p)) = cap(o, d, height, self.radius) {
+                let r = (p.x * p.x + p.y * p.y).sqrt();
+                nearest.offer(t, Vec3::new(0.0, 0.0, normal), (around(p), r / self.radius));
+            }
+        }
+        Some(self.frame.record(ray, nearest.hit?, &self.mat))
+    }
+}
+
+/// A cone closed by a disk at its base. On the side `u` goes around the
+/// axis and `v` from the base to the apex, on the base from the center to the rim.
+#[derive(Clone)]
+pub struct Cone {
+    pub radius: f64,
+    pub height: f64,
+    pub mat: Material,
+    frame: AxisFrame,
+}
+
+impl Cone {
+    /// The cone with a base of `radius` centered on `base`, narrowing to
+    /// `apex`, which must differ from it
+    pub fn new(base: Vec3, apex: Vec3, radius: f64, matterial: Material) -> Hittable {
+        Hittable::Cone(Box::new(Self { radius, height: (apex - base).len(), mat: matterial, frame: AxisFrame::new(base, apex - base) }))
+    }
+}
+
+impl Cone {
+    pub fn bounding_box(&self) -> Aabb {
+        let r = self.radius;
+        self.frame.bounding_box(Vec3::new(-r, -r, 0.0), Vec3::new(r, r, self.height))
+    }
+    pub fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let (o, d) = self.frame.local_ray(ray);
+        let (h, k) = (self.height, self.radius / self.height);
+        let k2 = k * k;
+        let mut nearest = Nearest::new(ray_t);
+
+        // x^2 + y^2 = k^2 (h - z)^2, the double cone, cut to 0 <= z <= h
+        let a = d.x * d.x + d.y * d.y - k2 * d.z * d.z;
+        let b = 2.0 * (o.x * d.x + o.y * d.y + k2 * (h - o.z) * d.z);
+        let c = o.x * o.x + o.y * o.y - k2 * (h - o.z) * (h - o.z);
+        if let Some((t0, t1)) = solve_quadratic(a, b, c) {
+            for t in [t0, t1] {
+                let p = o + d * t;
+                if (0.0..=h).contains(&p.z) {
+                    let r = (p.x * p.x + p.y * p.y).sqrt();
+                    // the apex has no normal, point it along the axis
+                    let normal = if r > 0.0 {Vec3::new(p.x, p.y, k * r)} else {Vec3::new(0.0, 0.0, 1.0)};
+                    nearest.offer(t, normal, (around(p), p.z / h));
+                }
+            }
+        }
+        if let Some((t, p)) = cap(o, d, 0.0, self.radius) {
+            let r = (p.x * p.x + p.y * p.y).sqrt();
+            nearest.offer(t, Vec3::new(0.0, 0.0, -1.0), (around(p), r / self.radius));
+        }
+        Some(self.frame.record(ray, nearest.hit?, &self.mat))
+    }
+}
+
+/// All points within `radius` of a segment, a cylinder with hemispheres for
+/// caps. `u` goes around the axis and `v` from one end to the other.
+#[derive(Clone)]
+pub struct Capsule {
+    pub radius: f64,
+    /// Length of the segment, without the caps
+    pub height: f64,
+    pub mat: Material,
+    frame: AxisFrame,
+}
+
+impl Capsule {
+    /// The capsule around the segment from `a` to `b`, a sphere if they are the same point
+    pub fn new(a: Vec3, b: Vec3, radius: f64, matterial: Material) -> Hittable {
+        let axis = if (b - a).near_zero() {Vec3::new(0.0, 1.0, 0.0)} else {b - a};
+        Hittable::Capsule(Box::new(Self { radius, height: (b - a).len(), mat: matterial, frame: AxisFrame::new(a, axis) }))
+    }
+}
+
+impl Capsule {
+    pub fn bounding_box(&self) -> Aabb {
+        let r = self.radius;
+        self.frame.bounding_box(Vec3::new(-r, -r, -r), Vec3::new(r, r, self.height + r))
+    }
+    pub fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let (o, d) = self.frame.local_ray(ray);
+        let (r, h) = (self.radius, self.height);
+        let v = |z: f64| (z + r) / (h + 2.0 * r);
+        let mut nearest = Nearest::new(ray_t);
+
+        tube(&mut nearest, o, d, r, h, v);
+        // each hemisphere only counts beyond its end of the segment
+        for (center, outside) in [(0.0, -1.0), (h, 1.0)] {
+            let oc = o - Vec3::new(0.0, 0.0, center);
+            let Some((t0, t1)) = solve_quadratic(d.len_squared(), 2.0 * oc.dot(d), oc.len_squared() - r * r) else {
+                continue;
+            };
+            for t in [t0, t1] {
+                let p = o + d * t;
+                if (p.z - center) * outside >= 0.0 {
+                    nearest.offer(t, p - Vec3::new(0.0, 0.0, center), (around(p), v(p.z)));
+                }
+            }
+        }
+        Some(self.frame.record(ray, nearest.hit?, &self.mat))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{hit, material};
+
+    /// A cylinder standing on the origin along y is hit on its side, its
+    /// caps, from inside, and only just by rays grazing it
+    #[test]
+    fn cylinder() {
+        let cylinder = Cylinder::new(Vec3::zero(), Vec3::new(0.0, 2.0, 0.0), 1.0, material());
+
+        let rec = hit(&cylinder, Vec3::new(0.0, 1.5, 5.0), Vec3::new(0.0, 0.0, -1.0)).unwrap();
+        assert!((rec.t - 4.0).abs() < 1e-12 && rec.front_face);
+        assert!((rec.normal - Vec3::new(0.0, 0.0, 1.0)).len() < 1e-12 && (rec.v - 0.75).abs() < 1e-12);
+
+        let rec = hit(&cylinder, Vec3::new(0.5, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0)).unwrap();
+        assert!((rec.t - 3.0).abs() < 1e-12 && (rec.normal - Vec3::new(0.0, 1.0, 0.0)).len() < 1e-12);
+
+        // from inside the nearest surface is a back face, along the axis it's a cap
+        let rec = hit(&cylinder, Vec3::new(0.0, 1.0, 0.0), Vec3::new(1.0, 0.0, 0.0)).unwrap();
+        assert!((rec.t - 1.0).abs() < 1e-12 && !rec.front_face);
+        assert!((rec.normal - Vec3::new(-1.0, 0.0, 0.0)).len() < 1e-12);
+        let rec = hit(&cylinder, Vec3::new(0.3, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0)).unwrap();
+        assert!((rec.t - 1.0).abs() < 1e-12 && !rec.front_face);
+
+        // rays along the side, just inside and just outside the radius
+        assert!(hit(&cylinder, Vec3::new(1.0 - 1e-9, 1.0, 5.0), Vec3::new(0.0, 0.0, -1.0)).is_some());
+        assert!(hit(&cylinder, Vec3::new(1.0 + 1e-9, 1.0, 5.0), Vec3::new(0.0, 0.0, -1.0)).is_none());
+        // past the top and parallel to the axis outside
+        assert!(hit(&cylinder, Vec3::new(0.0, 2.0 + 1e-9, 5.0), Vec3::new(0.0, 0.0, -1.0)).is_none());
+        assert!(hit(&cylinder, Vec3::new(1.0 + 1e-9, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0)).is_none());
+    }
+
+    /// The slanted side of a cone has a normal tilted up by the cone's
+    /// angle, and the mirrored half of the double cone is never hit
+    #[test]
+    fn cone() {
+        let cone = Cone::new(Vec3::zero(), Vec3::new(0.0, 1.0, 0.0), 1.0, material());
+
+        let rec = hit(&cone, Vec3::new(0.0, 0.5, 5.0), Vec3::new(0.0, 0.0, -1.0)).unwrap();
+        assert!((rec.t - 4.5).abs() < 1e-12 && rec.front_face);
+        assert!((rec.normal - Vec3::new(0.0, 1.0, 1.0).unit()).len() < 1e-12 && (rec.v - 0.5).abs() < 1e-12);
+
+        let rec = hit(&cone, Vec3::new(0.2, -3.0, 0.0), Vec3::new(0.0, 1.0, 0.0)).unwrap();
+        assert!((rec.t - 3.0).abs() < 1e-12 && (rec.normal - Vec3::new(0.0, -1.0, 0.0)).len() < 1e-12);
+
+        // from inside straight up through the side
+        let rec = hit(&cone, Vec3::new(0.5, 0.1, 0.0), Vec3::new(0.0, 1.0, 0.0)).unwrap();
+        assert!((rec.t - 0.4).abs() < 1e-12 && !rec.front_face);
+
+        assert!(hit(&cone, Vec3::new(0.0, 1.5, 5.0), Vec3::new(0.0, 0.0, -1.0)).is_none());
+        // along the slant just outside, parallel to the surface
+        assert!(hit(&cone, Vec3::new(1e-9, 1.0 + 1e-9, 0.0) + Vec3::new(1.0, -1.0, 0.0) * -2.0, Vec3::new(1.0, -1.0, 0.0)).is_none());
+    }
+
+    /// Capsules are hit on their round ends, grazed at the radius, and left
+    /// through the side from inside
+    #[test]
+    fn capsule() {
+        let capsule = Capsule::new(Vec3::zero(), Vec3::new(2.0, 0.0, 0.0), 0.5, material());
+
+        let rec = hit(&capsule, Vec3::new(5.0, 0.0, 0.0), Vec3::new(-1.0, 0.0, 0.0)).unwrap();
+        assert!((rec.t - 2.5).abs() < 1e-12 && (rec.normal - Vec3::new(1.0, 0.0, 0.0)).len() < 1e-12);
+        assert!((rec.v - 1.0).abs() < 1e-12);
+
+        let rec = hit(&capsule, Vec3::new(-0.1, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0)).unwrap();
+        let expected = 5.0 - (0.25f64 - 0.01).sqrt();
+        assert!((rec.t - expected).abs() < 1e-12 && rec.normal.x < 0.0);
+
+        let rec = hit(&capsule, Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0)).unwrap();
+        assert!((rec.t - 0.5).abs() < 1e-12 && !rec.front_face);
+
+        assert!(hit(&capsule, Vec3::new(-5.0, 0.5 - 1e-9, 0.0), Vec3::new(1.0, 0.0, 0.0)).is_some());
+        assert!(hit(&capsule, Vec3::new(-5.0, 0.5 + 1e-9, 0.0), Vec3::new(1.0, 0.0, 0.0)).is_none());
+    }
+}
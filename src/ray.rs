@@ -8,11 +8,16 @@ use crate::vec3::Vec3;
 pub struct Ray {
     pub origin: Vec3,
     pub direction: Vec3,
+    /// When the ray was sent, 0 to 1 over a frame. Moving objects are hit where they are at this time.
+    pub time: f64,
 }
 
 impl Ray {
     pub const fn new(origin: Vec3, direction: Vec3) -> Self  {
-        Self {origin, direction}
+        Self {origin, direction, time: 0.0}
+    }
+    pub const fn with_time(origin: Vec3, direction: Vec3, time: f64) -> Self {
+        Self {origin, direction, time}
     }
     pub fn at(&self, t: f64) -> Vec3 {
         self.origin + self.direction*t
@@ -21,6 +26,7 @@ impl Ray {
         Self {
             origin: self.origin,
             direction: self.direction.unit(),
+            time: self.time,
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,341 @@
+use std::f64::consts::PI;
+
+use rand::Rng;
+
+use crate::{utils::{mix_bits, Pcg32}, vec3::Vec3};
+
+/// How the sample points of a pixel are distributed
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum SamplerKind {
+    /// Every value drawn independently, the slowest to converge
+    Independent,
+    /// Jittered strata, shuffled independently for each dimension
+    Stratified,
+    /// The Halton sequence with a random shift per pixel and dimension
+    Halton,
+    /// Owen scrambled Sobol points, padded in shuffled pairs of dimensions
+    #[default]
+    Sobol,
+}
+
+impl SamplerKind {
+    pub const NAMES: &'static [&'static str] = &["independent", "stratified", "halton", "sobol"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "independent" => Some(SamplerKind::Independent),
+            "stratified" => Some(SamplerKind::Stratified),
+            "halton" => Some(SamplerKind::Halton),
+            "sobol" => Some(SamplerKind::Sobol),
+            _ => None,
+        }
+    }
+}
+
+/// Halton bases, dimensions past the end of the table fall back to independent values
+const PRIMES: [u32; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53,
+    59, 61, 67, 71, 73, 79, 83, 89, 97, 101, 103, 107, 109, 113, 127, 131,
+];
+
+/// Sample values for one pixel.
+///
+/// Every sample walks through the dimensions in the same order: the pixel
+/// position, the lens position, then the scattering decisions of each bounce.
+/// Call `start_sample` before each sample so the dimensions line up.
+pub struct Sampler {
+    kind: SamplerKind,
+    samples_per_pixel: usize,
+    pixel_hash: u64,
+    sample: usize,
+    dimension: u32,
+    rng: Pcg32,
+}
+
+impl Sampler {
+    pub fn new(kind: SamplerKind, samples_per_pixel: usize, seed: u64, pixel: u64) -> Self {
+        Self {
+            kind,
+            samples_per_pixel,
+            pixel_hash: mix_bits(seed ^ mix_bits(pixel.wrapping_add(0x9e3779b97f4a7c15))),
+            sample: 0,
+            dimension: 0,
+            rng: Pcg32::for_pixel(seed, pixel),
+        }
+    }
+
+    pub fn start_sample(&mut self, index: usize) {
+        self.sample = index;
+        self.dimension = 0;
+    }
+
+    /// Generator for anything that needs an unbounded number of values, like rejection sampling
+    pub fn rng(&mut self) -> &mut Pcg32 {
+        &mut self.rng
+    }
+
+    /// A value in [0, 1)
+    pub fn get_1d(&mut self) -> f64 {
+        let dimension = self.dimension;
+        self.dimension += 1;
+        let hash = self.dimension_hash(dimension);
+
+        match self.kind {
+            SamplerKind::Independent => self.rng.gen(),
+            SamplerKind::Stratified => {
+                let count = self.samples_per_pixel as u32;
+                let stratum = permutation_element(self.sample as u32, count, hash);
+                (stratum as f64 + self.rng.gen::<f64>()) / count as f64
+            },
+            SamplerKind::Halton => self.halton(dimension, hash),
+            SamplerKind::Sobol => {
+                let index = nested_uniform_scramble(self.sample as u32, hash);
+                to_unit(nested_uniform_scramble(index.reverse_bits(), hash_combine(hash, 1)))
+            },
+        }
+    }
+
+    /// A point in [0, 1)²
+    pub fn get_2d(&mut self) -> (f64, f64) {
+        match self.kind {
+            SamplerKind::Independent | SamplerKind::Halton => (self.get_1d(), self.get_1d()),
+            SamplerKind::Stratified => {
+                let dimension = self.dimension;
+                self.dimension += 2;
+                let hash = self.dimension_hash(dimension);
+
+                // as close to square as the sample count allows, a few strata stay empty otherwise
+                let count = self.samples_per_pixel as u32;
+                let nx = (count as f64).sqrt() as u32;
+                let ny = count.div_ceil(nx);
+                let stratum = permutation_element(self.sample as u32, nx * ny, hash);
+                (
+                    ((stratum % nx) as f64 + self.rng.gen::<f64>()) / nx as f64,
+                    ((stratum / nx) as f64 + self.rng.gen::<f64>()) / ny as f64,
+                )
+            },
+            SamplerKind::Sobol => {
+                let dimension = self.dimension;
+                self.dimension += 2;
+                let hash = self.dimension_hash(dimension);
+
+                let index = nested_uniform_scramble(self.sample as u32, hash);
+                let x = nested_uniform_scramble(index.reverse_bits(), hash_combine(hash, 1));
+                let y = nested_uniform_scramble(sobol_second_dimension(index), hash_combine(hash, 2));
+                (to_unit(x), to_unit(y))
+            },
+        }
+    }
+
+    fn dimension_hash(&self, dimension: u32) -> u32 {
+        mix_bits(self.pixel_hash ^ (dimension as u64).wrapping_mul(0xd1b54a32d192ed03)) as u32
+    }
+
+    fn halton(&mut self, dimension: u32, hash: u32) -> f64 {
+        let Some(&base) = PRIMES.get(dimension as usize) else {
+            return self.rng.gen();
+        };
+        // Cranley-Patterson rotation, decorrelates neighbouring pixels
+        let value = radical_inverse(base, self.sample as u64) + to_unit(hash);
+        if value >= 1.0 {value - 1.0} else {value}
+    }
+}
+
+fn to_unit(x: u32) -> f64 {
+    x as f64 / 4294967296.0
+}
+
+fn hash_combine(hash: u32, v: u32) -> u32 {
+    mix_bits(((hash as u64) << 32) | v as u64) as u32
+}
+
+/// The digits of `index` in `base` mirrored around the radix point
+fn radical_inverse(base: u32, mut index: u64) -> f64 {
+    let inv_base = 1.0 / base as f64;
+    let mut inv_base_n = 1.0;
+    let mut reversed = 0;
+    while index > 0 {
+        let next = index / base as u64;
+        reversed = reversed * base as u64 + (index - next * base as u64);
+        inv_base_n *= inv_base;
+        index = next;
+    }
+    (reversed as f64 * inv_base_n).min(1.0 - f64::EPSILON)
+}
+
+/// Second dimension of the Sobol sequence, the first is the bit reversed index
+fn sobol_second_dimension(mut index: u32) -> u32 {
+    let mut v = 1 << 31;
+    let mut result = 0;
+    while index != 0 {
+        if index & 1 != 0 {
+            result ^= v;
+        }
+        v ^= v >> 1;
+        index >>= 1;
+    }
+    result
+}
+
+/// Laine-Karras style hash, each bit only depends on the bits below it
+fn laine_karras_permutation(mut x: u32, seed: u32) -> u32 {
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50b47c);
+    x ^= x.wrapping_mul(0xb82f1e52);
+    x ^= x.wrapping_mul(0xc7afe638);
+    x ^= x.wrapping_mul(0x8d22f6e6);
+    x
+}
+
+/// Owen scrambling of a binary fraction (Burley 2020)
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+}
+
+/// Element `i` of a random permutation of `0..l` chosen by `p` (Kensler 2013)
+fn permutation_element(mut i: u32, l: u32, p: u32) -> u32 {
+    let mut w = l - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < l {
+            break;
+        }
+    }
+    i.wrapping_add(p) % l
+}
+
+/// Maps the unit square onto the unit disk, keeping strata compact (Shirley-Chiu)
+pub fn sample_unit_disk((u, v): (f64, f64)) -> (f64, f64) {
+    let (a, b) = (2.0 * u - 1.0, 2.0 * v - 1.0);
+    if a == 0.0 && b == 0.0 {
+        return (0.0, 0.0);
+    }
+    let (r, theta) = if a.abs() > b.abs() {
+        (a, PI / 4.0 * (b / a))
+    } else {
+        (b, PI / 2.0 - PI / 4.0 * (a / b))
+    };
+    (r * theta.cos(), r * theta.sin())
+}
+
+/// Maps the unit square onto the hemisphere around +z with density `cos(theta) / pi`
+pub fn sample_cosine_hemisphere(uv: (f64, f64)) -> Vec3 {
+    let (x, y) = sample_unit_disk(uv);
+    Vec3::new(x, y, (1.0 - x * x - y * y).max(0.0).sqrt())
+}
+
+/// Maps the unit square onto the unit sphere
+pub fn sample_unit_sphere((u, v): (f64, f64)) -> Vec3 {
+    let z = 1.0 - 2.0 * u;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * PI * v;
+    Vec3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+/// Maps the unit cube onto the solid unit ball
+pub fn sample_unit_ball(uv: (f64, f64), w: f64) -> Vec3 {
+    sample_unit_sphere(uv) * w.cbrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KINDS: [SamplerKind; 4] = [SamplerKind::Independent, SamplerKind::Stratified, SamplerKind::Halton, SamplerKind::Sobol];
+
+    #[test]
+    fn values_in_unit_interval() {
+        for kind in KINDS {
+            let mut sampler = Sampler::new(kind, 37, 3, 11);
+            for i in 0..37 {
+                sampler.start_sample(i);
+                for _ in 0..50 {
+                    let x = sampler.get_1d();
+                    let (y, z) = sampler.get_2d();
+                    assert!([x, y, z].iter().all(|v| (0.0..1.0).contains(v)), "{kind:?} gave {x} {y} {z}");
+                }
+            }
+        }
+    }
+
+    /// With a power of two sample count each 1D stratum is hit exactly once
+    #[test]
+    fn stratified_in_one_dimension() {
+        for kind in [SamplerKind::Stratified, SamplerKind::Sobol] {
+            let mut sampler = Sampler::new(kind, 64, 5, 2);
+            for dimension in 0..8 {
+                let mut hits = [0; 64];
+                for i in 0..64 {
+                    sampler.start_sample(i);
+                    for _ in 0..dimension {
+                        sampler.get_1d();
+                    }
+                    hits[(sampler.get_1d() * 64.0) as usize] += 1;
+                }
+                assert!(hits.iter().all(|&h| h == 1), "{kind:?} dimension {dimension}: {hits:?}");
+            }
+        }
+    }
+
+    /// Halton points in base b hit each of b^k strata once over b^k samples,
+    /// the per pixel shift only rotates which sample lands where. Past the
+    /// table of bases values come from the pixel's generator.
+    #[test]
+    fn halton_strata() {
+        assert_eq!([1, 2, 3, 4].map(|i| radical_inverse(2, i)), [0.5, 0.25, 0.75, 0.125]);
+        assert!((radical_inverse(3, 5) - 7.0 / 9.0).abs() < 1e-15);
+
+        for (dimension, count) in [(0, 64), (1, 27)] {
+            let mut sampler = Sampler::new(SamplerKind::Halton, count, 5, 2);
+            let mut hits = vec![0; count];
+            for i in 0..count {
+                sampler.start_sample(i);
+                for _ in 0..dimension {
+                    sampler.get_1d();
+                }
+                hits[(sampler.get_1d() * count as f64) as usize] += 1;
+            }
+            assert!(hits.iter().all(|&h| h == 1), "dimension {dimension}: {hits:?}");
+        }
+
+        let past_table = |pixel| {
+            let mut sampler = Sampler::new(SamplerKind::Halton, 16, 5, pixel);
+            let mut values = Vec::new();
+            for i in 0..16 {
+                sampler.start_sample(i);
+                for _ in 0..PRIMES.len() {
+                    sampler.get_1d();
+                }
+                values.push(sampler.get_1d());
+            }
+            values
+        };
+        let values = past_table(2);
+        assert!(values.iter().all(|v| (0.0..1.0).contains(v)));
+        // the same for the same pixel and seed, different for another pixel
+        assert_eq!(values, past_table(2));
+        assert_ne!(values, past_table(3));
+    }
+}
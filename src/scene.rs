@@ -0,0 +1,860 @@
+use std::{cell::RefCell, collections::HashMap, error::Error, fmt, fs, path::{Path, PathBuf}, sync::Arc, thread};
+
+use toml::{Table, Value};
+
+use crate::{background::{Background, EnvironmentMap}, bvh::Bvh, camera::CameraBuilder, color::Color, csg::{Csg, CsgOperation}, hittable::Hittable, hittable_list::HittableList, instance::Instance, light::{DirectionalLight, Light, PointLight, SpotLight}, medium::{Atmosphere, ConstantMedium, PhaseFunction}, material::{Conductor, Dielectric, DiffuseLight, Lambertian, Material, Metal, RoughDielectric}, obj::{load_obj, ObjModel}, output::{BitDepth, ExrPixelType}, plane::{Disk, Plane}, principled::{scalar_texture, Principled}, quad::{Cuboid, Quad}, quadric::{Capsule, Cone, Cylinder}, sampler::SamplerKind, sphere::Sphere, texture::{Checker, ImageTexture, NoiseKind, NoiseTexture, Texture, WrapMode}, tonemap::ToneMapper, torus::Torus, transform::{Keyframe, Mat4, Trs}, triangle::Triangle, vec3::{cross, Vec3}};
+
+/// A camera and world read from a scene file
+pub struct Scene {
+    pub camera: CameraBuilder,
+    pub world: HittableList,
+    /// Where the scene asks to be rendered to, if anywhere
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug)]
+pub struct SceneError {
+    pub path: PathBuf,
+    /// Dotted path to the offending key, e.g. `objects[2].radius`
+    pub key: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.key {
+            Some(key) => write!(f, "{}: `{key}`: {}", self.path.display(), self.message),
+            None => write!(f, "{}: {}", self.path.display(), self.message),
+        }
+    }
+}
+
+impl Error for SceneError {}
+
+/// Errors before the file name is attached
+struct KeyError {
+    key: String,
+    message: String,
+}
+
+type Result<T> = std::result::Result<T, KeyError>;
+
+fn key_err<T>(key: &str, message: impl Into<String>) -> Result<T> {
+    Err(KeyError { key: key.to_string(), message: message.into() })
+}
+
+/// Loads a TOML scene file, see `scenes/example.toml` for the format.
+///
+/// Relative paths inside the file (OBJ models, environment maps) are resolved
+/// against the directory of the scene file.
+pub fn load_scene(path: impl AsRef<Path>) -> std::result::Result<Scene, SceneError> {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path)
+        .map_err(|e| SceneError { path: path.to_path_buf(), key: None, message: e.to_string() })?;
+    let table: Table = source.parse()
+        .map_err(|e: toml::de::Error| SceneError { path: path.to_path_buf(), key: None, message: e.to_string() })?;
+
+    let base = path.parent().unwrap_or(Path::new("")).to_path_buf();
+    SceneLoader { base, models: RefCell::new(HashMap::new()) }.load(&table)
+        .map_err(|e| SceneError { path: path.to_path_buf(), key: Some(e.key), message: e.message })
+}
+
+struct SceneLoader {
+    base: PathBuf,
+    /// OBJ files already loaded for instances, so every copy shares one BVH
+    models: RefCell<HashMap<PathBuf, Arc<Hittable>>>,
+}
+
+impl SceneLoader {
+    fn load(&self, table: &Table) -> Result<Scene> {
+        check_keys(table, "", &["camera", "background", "atmosphere", "materials", "objects", "lights", "output"])?;
+
+        let mut camera = match table.get("camera") {
+            Some(value) => self.camera(as_table(value, "camera")?, "camera")?,
+            None => Self::default_camera(),
+        };
+        if let Some(value) = table.get("background") {
+            camera.background = self.background(as_table(value, "background")?, "background")?;
+        }
+        if let Some(value) = table.get("atmosphere") {
+            camera.atmosphere = Some(self.atmosphere(as_table(value, "atmosphere")?, "atmosphere")?);
+        }
+
+        let mut materials = HashMap::new();
+        if let Some(value) = table.get("materials") {
+            for (name, value) in as_table(value, "materials")? {
+                let key = format!("materials.{name}");
+                materials.insert(name.clone(), self.material(as_table(value, &key)?, &key)?);
+            }
+        }
+
+        let mut world = HittableList::new();
+        if let Some(value) = table.get("objects") {
+            let Value::Array(objects) = value else {
+                return key_err("objects", "expected an array of tables, write objects as [[objects]]");
+            };
+            for (i, value) in objects.iter().enumerate() {
+                let key = format!("objects[{i}]");
+                self.object(as_table(value, &key)?, &key, &materials, &mut world)?;
+            }
+        }
+
+        if let Some(value) = table.get("lights") {
+            let Value::Array(lights) = value else {
+                return key_err("lights", "expected an array of tables, write lights as [[lights]]");
+            };
+            for (i, value) in lights.iter().enumerate() {
+                let key = format!("lights[{i}]");
+                world.add_light(self.light(as_table(value, &key)?, &key)?);
+            }
+        }
+
+        let output = table.get("output").map(|v| as_str(v, "output").map(PathBuf::from)).transpose()?;
+
+        Ok(Scene { camera, world, output })
+    }
+
+    fn default_camera() -> CameraBuilder {
+        CameraBuilder {
+            image_width: 800,
+            aspect_ratio: 16.0/9.0,
+            vfov: 90.0,
+            look_at: Vec3::new(0.0, 0.0, -1.0),
+            thread_count: thread::available_parallelism().map_or(1, |n| n.get()),
+            max_depth: 50,
+            samples_per_pixel: 100,
+            ..Default::default()
+        }
+    }
+
+    fn camera(&self, table: &Table, key: &str) -> Result<CameraBuilder> {
+        check_keys(table, key, &[
+            "image_width", "image_hight", "aspect_ratio", "vfov", "camera_center", "look_at", "depth_of_field_blur",
+            "shutter_open", "shutter_close", "thread_count", "max_depth", "samples_per_pixel", "sampler", "seed", "exposure", "tone_mapper", "bit_depth", "ascii_ppm", "exr_pixel_type",
+        ])?;
+        let mut camera = Self::default_camera();
+
+        if ["image_width", "image_hight", "aspect_ratio"].iter().all(|k| table.contains_key(*k)) {
+            return key_err(&format!("{key}.aspect_ratio"), "only two of `image_width`, `image_hight` and `aspect_ratio` can be set");
+        }
+        if table.contains_key("image_hight") && !table.contains_key("image_width") {
+            camera.image_width = 0;
+        }
+        if table.contains_key("image_hight") && table.contains_key("image_width") && !table.contains_key("aspect_ratio") {
+            camera.aspect_ratio = 0.0;
+        }
+
+        let sub = |name: &str| format!("{key}.{name}");
+        for (name, value) in table {
+            let k = sub(name);
+            match name.as_str() {
+                "image_width" => camera.image_width = positive_integer(value, &k)?,
+                "image_hight" => camera.image_hight = positive_integer(value, &k)?,
+                "aspect_ratio" => camera.aspect_ratio = positive_number(value, &k)?,
+                "vfov" => {
+                    camera.vfov = as_f64(value, &k)?;
+                    if !(camera.vfov > 0.0 && camera.vfov < 180.0) {
+                        return key_err(&k, "must be between 0 and 180 degrees");
+                    }
+                },
+                "camera_center" => camera.camera_center = as_vec3(value, &k)?,
+                "look_at" => camera.look_at = as_vec3(value, &k)?,
+                "depth_of_field_blur" => camera.depth_of_field_blur = non_negative_number(value, &k)?,
+                "shutter_open" => camera.shutter_open = unit_number(value, &k)?,
+                "shutter_close" => camera.shutter_close = unit_number(value, &k)?,
+                "thread_count" => camera.thread_count = positive_integer(value, &k)?,
+                "max_depth" => camera.max_depth = positive_integer(value, &k)?,
+                "samples_per_pixel" => camera.samples_per_pixel = positive_integer(value, &k)?,
+                "sampler" => {
+                    let name = as_str(value, &k)?;
+                    camera.sampler = match SamplerKind::from_name(name) {
+                        Some(kind) => kind,
+                        None => return key_err(&k, format!("must be one of {}", SamplerKind::NAMES.join(", "))),
+                    };
+                },
+                "seed" => {
+                    let seed = as_integer(value, &k)?;
+                    camera.seed = u64::try_from(seed).or_else(|_| key_err(&k, "must not be negative"))?;
+                },
+                "exposure" => camera.exposure = as_f64(value, &k)?,
+                "tone_mapper" => camera.tone_mapper = tone_mapper(value, &k)?,
+                "bit_depth" => camera.output_settings.bit_depth = match as_integer(value, &k)? {
+                    8 => BitDepth::Eight,
+                    16 => BitDepth::Sixteen,
+                    _ => return key_err(&k, "must be 8 or 16"),
+                },
+                "ascii_ppm" => camera.output_settings.ascii_ppm = as_bool(value, &k)?,
+                "exr_pixel_type" => camera.output_settings.exr_pixel_type = match as_str(value, &k)? {
+                    "half" => ExrPixelType::Half,
+                    "float" => ExrPixelType::Float,
+                    _ => return key_err(&k, "must be \"half\" or \"float\""),
+                },
+                _ => unreachable!(),
+            }
+        }
+
+        if (camera.camera_center - camera.look_at).near_zero() {
+            return key_err(&sub("look_at"), "must be different from `camera_center`");
+        }
+        // the camera keeps +y up, which can't be done looking along it
+        if cross(Vec3::new(0.0, 1.0, 0.0), (camera.look_at - camera.camera_center).unit()).near_zero() {
+            return key_err(&sub("look_at"), "must not be straight above or below `camera_center`");
+        }
+        if table.contains_key("shutter_open") && !table.contains_key("shutter_close") {
+            camera.shutter_close = camera.shutter_open;
+        }
+        if camera.shutter_close < camera.shutter_open {
+            return key_err(&sub("shutter_close"), "must not be before `shutter_open`");
+        }
+
+        Ok(camera)
+    }
+
+    fn background(&self, table: &Table, key: &str) -> Result<Background> {
+        let sub = |name: &str| format!("{key}.{name}");
+        match type_name(table, key)? {
+            "solid" => {
+                check_keys(table, key, &["type", "color"])?;
+                Ok(Background::Solid(as_color(required(table, "color", key)?, &sub("color"))?))
+            },
+            "gradient" => {
+                check_keys(table, key, &["type", "bottom", "top"])?;
+                Ok(Background::Gradient {
+                    bottom: as_color(required(table, "bottom", key)?, &sub("bottom"))?,
+                    top: as_color(required(table, "top", key)?, &sub("top"))?,
+                })
+            },
+            "environment" => {
+                check_keys(table, key, &["type", "path", "rotation", "intensity"])?;
+                let path = self.base.join(as_str(required(table, "path", key)?, &sub("path"))?);
+                let rotation = optional(table, "rotation", key, as_f64)?.unwrap_or(0.0);
+                let intensity = optional(table, "intensity", key, non_negative_number)?.unwrap_or(1.0);
+                EnvironmentMap::load(path, rotation, intensity).or_else(|e| key_err(&sub("path"), e.to_string()))
+            },
+            other => key_err(&sub("type"), format!("unknown background type `{other}`, expected solid, gradient or environment")),
+        }
+    }
+
+    fn atmosphere(&self, table: &Table, key: &str) -> Result<Atmosphere> {
+        check_keys(table, key, &["density", "albedo", "anisotropy", "height"])?;
+        let density = positive_number(required(table, "density", key)?, &format!("{key}.density"))?;
+        let albedo = optional(table, "albedo", key, as_color)?.unwrap_or(Color::new(1.0, 1.0, 1.0));
+        let phase = PhaseFunction::from_anisotropy(optional(table, "anisotropy", key, anisotropy)?.unwrap_or(0.0));
+        let height = optional(table, "height", key, as_f64)?.unwrap_or(f64::INFINITY);
+        Ok(Atmosphere::new(density, albedo, phase, height))
+    }
+
+    fn material(&self, table: &Table, key: &str) -> Result<Material> {
+        let sub = |name: &str| format!("{key}.{name}");
+        match type_name(table, key)? {
+            "lambertian" => {
+                check_keys(table, key, &["type", "albedo"])?;
+                Ok(Lambertian::new(self.texture(required(table, "albedo", key)?, &sub("albedo"))?))
+            },
+            "metal" => {
+                check_keys(table, key, &["type", "albedo", "fuzz"])?;
+                let albedo = self.texture(required(table, "albedo", key)?, &sub("albedo"))?;
+                let fuzz = optional(table, "fuzz", key, non_negative_number)?.unwrap_or(0.0);
+                Ok(Metal::new(albedo, fuzz))
+            },
+            "dielectric" => {
+                check_keys(table, key, &["type", "refraction_index"])?;
+                Ok(Dielectric::new(positive_number(required(table, "refraction_index", key)?, &sub("refraction_index"))?))
+            },
+            "diffuse_light" => {
+                check_keys(table, key, &["type", "emit"])?;
+                Ok(DiffuseLight::new(self.texture(required(table, "emit", key)?, &sub("emit"))?))
+            },
+            "conductor" => {
+                check_keys(table, key, &["type", "preset", "eta", "k", "roughness"])?;
+                let roughness = optional(table, "roughness", key, unit_number)?.unwrap_or(0.0);
+                if let Some(preset) = table.get("preset") {
+                    if table.contains_key("eta") || table.contains_key("k") {
+                        return key_err(&sub("preset"), "can't be combined with `eta` and `k`");
+                    }
+                    return match as_str(preset, &sub("preset"))? {
+                        "gold" => Ok(Conductor::gold(roughness)),
+                        "copper" => Ok(Conductor::copper(roughness)),
+                        "aluminium" => Ok(Conductor::aluminium(roughness)),
+                        other => key_err(&sub("preset"), format!("unknown preset `{other}`, expected gold, copper or aluminium")),
+                    };
+                }
+                let eta = as_color(required(table, "eta", key)?, &sub("eta"))?;
+                let k = as_color(required(table, "k", key)?, &sub("k"))?;
+                Ok(Conductor::new(eta, k, roughness))
+            },
+            "rough_dielectric" => {
+                check_keys(table, key, &["type", "refraction_index", "roughness"])?;
+                let refraction_index = positive_number(required(table, "refraction_index", key)?, &sub("refraction_index"))?;
+                let roughness = unit_number(required(table, "roughness", key)?, &sub("roughness"))?;
+                Ok(RoughDielectric::new(refraction_index, roughness))
+            },
+            "principled" => {
+                check_keys(table, key, &[
+                    "type", "base_color", "metallic", "roughness", "specular", "sheen", "sheen_tint",
+                    "clearcoat", "clearcoat_roughness", "transmission", "ior", "emission",
+                ])?;
+                let defaults = Principled::default();
+                let texture = |name: &str, default: Texture| table.get(name).map_or(Ok(default), |v| self.texture(v, &sub(name)));
+                let scalar = |name: &str, default: Texture| table.get(name).map_or(Ok(default), |v| self.scalar_texture(v, &sub(name)));
+                let number = |name: &str, default: f64| Ok(optional(table, name, key, unit_number)?.unwrap_or(default));
+                Ok(Material::from(Principled {
+                    base_color: texture("base_color", defaults.base_color)?,
+                    metallic: scalar("metallic", defaults.metallic)?,
+                    roughness: scalar("roughness", defaults.roughness)?,
+                    specular: number("specular", defaults.specular)?,
+                    sheen: number("sheen", defaults.sheen)?,
+                    sheen_tint: number("sheen_tint", defaults.sheen_tint)?,
+                    clearcoat: number("clearcoat", defaults.clearcoat)?,
+                    clearcoat_roughness: number("clearcoat_roughness", defaults.clearcoat_roughness)?,
+                    transmission: number("transmission", defaults.transmission)?,
+                    ior: optional(table, "ior", key, positive_number)?.unwrap_or(defaults.ior),
+                    emission: texture("emission", defaults.emission)?,
+                }))
+            },
+            other => key_err(&sub("type"), format!("unknown material type `{other}`, expected lambertian, metal, dielectric, diffuse_light, conductor, rough_dielectric or principled")),
+        }
+    }
+
+    /// A number from 0 to 1 or a texture table, for the scalar material parameters
+    fn scalar_texture(&self, value: &Value, key: &str) -> Result<Texture> {
+        match value {
+            Value::Table(_) => self.texture(value, key),
+            _ => unit_number(value, key).map(scalar_texture),
+        }
+    }
+
+    /// A color `[r, g, b]` or a texture table
+    fn texture(&self, value: &Value, key: &str) -> Result<Texture> {
+        let Value::Table(table) = value else {
+            return as_color(value, key).map(Texture::from);
+        };
+        let sub = |name: &str| format!("{key}.{name}");
+        match type_name(table, key)? {
+            "solid" => {
+                check_keys(table, key, &["type", "color"])?;
+                Ok(Texture::from(as_color(required(table, "color", key)?, &sub("color"))?))
+            },
+            "checker" => {
+                check_keys(table, key, &["type", "scale", "even", "odd"])?;
+                let scale = positive_number(required(table, "scale", key)?, &sub("scale"))?;
+                let even = self.texture(required(table, "even", key)?, &sub("even"))?;
+                let odd = self.texture(required(table, "odd", key)?, &sub("odd"))?;
+                Ok(Checker::new(scale, even, odd))
+            },
+            "image" => {
+                check_keys(table, key, &["type", "path", "wrap"])?;
+                let path = self.base.join(as_str(required(table, "path", key)?, &sub("path"))?);
+                let wrap = match table.get("wrap").map(|v| as_str(v, &sub("wrap"))).transpose()?.unwrap_or("repeat") {
+                    "repeat" => WrapMode::Repeat,
+                    "mirror" => WrapMode::Mirror,
+                    "clamp" => WrapMode::Clamp,
+                    _ => return key_err(&sub("wrap"), "must be \"repeat\", \"mirror\" or \"clamp\""),
+                };
+                ImageTexture::load(path, wrap).or_else(|e| key_err(&sub("path"), e.to_string()))
+            },
+            "noise" => {
+                check_keys(table, key, &["type", "kind", "scale", "color"])?;
+                let kind = match table.get("kind").map(|v| as_str(v, &sub("kind"))).transpose()?.unwrap_or("perlin") {
+                    "perlin" => NoiseKind::Perlin,
+                    "turbulence" => NoiseKind::Turbulence,
+                    "marble" => NoiseKind::Marble,
+                    _ => return key_err(&sub("kind"), "must be \"perlin\", \"turbulence\" or \"marble\""),
+                };
+                let scale = optional(table, "scale", key, positive_number)?.unwrap_or(1.0);
+                let color = optional(table, "color", key, as_color)?.unwrap_or(Color::new(1.0, 1.0, 1.0));
+                Ok(NoiseTexture::new(kind, scale, color))
+            },
+            other => key_err(&sub("type"), format!("unknown texture type `{other}`, expected solid, checker, image or noise")),
+        }
+    }
+
+    fn object(&self, table: &Table, key: &str, materials: &HashMap<String, Material>, world: &mut HittableList) -> Result<()> {
+        // an OBJ file left where it is goes in triangle by triangle, so the scene's BVH can split it up
+        if type_name(table, key)? == "obj" && !table.contains_key("transform") && !table.contains_key("keyframes") {
+            check_keys(table, key, &["type", "path"])?;
+            let k = format!("{key}.path");
+            let model = self.load_obj(self.base.join(as_str(required(table, "path", key)?, &k)?), &k)?;
+            world.extend(model.into_hittables());
+            return Ok(());
+        }
+        world.add(self.shape(table, key, materials)?);
+        Ok(())
+    }
+
+    /// One hittable, which `csg` objects are built from
+    fn shape(&self, table: &Table, key: &str, materials: &HashMap<String, Material>) -> Result<Hittable> {
+        let sub = |name: &str| format!("{key}.{name}");
+        let material = |table: &Table| -> Result<Material> {
+            let name = as_str(required(table, "material", key)?, &sub("material"))?;
+            materials.get(name).cloned().map_or_else(|| key_err(&sub("material"), format!("no material named `{name}`")), Ok)
+        };
+
+        let transform = table.get("transform").map(|v| self.transform(as_table(v, &sub("transform"))?, &sub("transform"))).transpose()?;
+        let keyframes = table.get("keyframes").map(|v| self.keyframes(v, &sub("keyframes"))).transpose()?;
+        if transform.is_some() && keyframes.is_some() {
+            return key_err(&sub("keyframes"), "can't be combined with `transform`");
+        }
+
+        let object = match type_name(table, key)? {
+            "sphere" => {
+                check_keys(table, key, &["type", "center", "center_end", "radius", "material", "transform", "keyframes"])?;
+                let center = as_vec3(required(table, "center", key)?, &sub("center"))?;
+                let radius = positive_number(required(table, "radius", key)?, &sub("radius"))?;
+                match optional(table, "center_end", key, as_vec3)? {
+                    Some(center_end) => Sphere::moving(center, center_end, radius, material(table)?),
+                    None => Sphere::new(center, radius, material(table)?),
+                }
+            },
+            "triangle" => {
+                check_keys(table, key, &["type", "vertices", "material", "transform", "keyframes"])?;
+                let k = sub("vertices");
+                let vertices = as_array(required(table, "vertices", key)?, &k)?;
+                let [v0, v1, v2] = vertices else {
+                    return key_err(&k, format!("expected 3 vertices, found {}", vertices.len()));
+                };
+                Triangle::new(
+                    as_vec3(v0, &format!("{k}[0]"))?,
+                    as_vec3(v1, &format!("{k}[1]"))?,
+                    as_vec3(v2, &format!("{k}[2]"))?,
+                    material(table)?,
+                )
+            },
+            "quad" => {
+                check_keys(table, key, &["type", "corner", "u", "v", "material", "transform", "keyframes"])?;
+                let corner = as_vec3(required(table, "corner", key)?, &sub("corner"))?;
+                let u = as_vec3(required(table, "u", key)?, &sub("u"))?;
+                let v = as_vec3(required(table, "v", key)?, &sub("v"))?;
+                if cross(u, v).near_zero() {
+                    return key_err(&sub("v"), "must not be zero or parallel to `u`");
+                }
+                Quad::new(corner, u, v, material(table)?)
+            },
+            "plane" => {
+                check_keys(table, key, &["type", "point", "normal", "material", "transform", "keyframes"])?;
+                let point = as_vec3(required(table, "point", key)?, &sub("point"))?;
+                let normal = direction(required(table, "normal", key)?, &sub("normal"))?;
+                Plane::new(point, normal, material(table)?)
+            },
+            "disk" => {
+                check_keys(table, key, &["type", "center", "normal", "radius", "material", "transform", "keyframes"])?;
+                let center = as_vec3(required(table, "center", key)?, &sub("center"))?;
+                let normal = direction(required(table, "normal", key)?, &sub("normal"))?;
+                let radius = positive_number(required(table, "radius", key)?, &sub("radius"))?;
+                Disk::new(center, normal, radius, material(table)?)
+            },
+            "box" => {
+                check_keys(table, key, &["type", "min", "max", "rotate", "material", "transform", "keyframes"])?;
+                self.cuboid(table, key, material(table)?)?
+            },
+            kind @ ("cylinder" | "cone") => {
+                let end = if kind == "cone" {"apex"} else {"top"};
+                check_keys(table, key, &["type", "base", end, "radius", "material", "transform", "keyframes"])?;
+                let base = as_vec3(required(table, "base", key)?, &sub("base"))?;
+                let top = as_vec3(required(table, end, key)?, &sub(end))?;
+                if (top - base).near_zero() {
+                    return key_err(&sub(end), "must be different from `base`");
+                }
+                let radius = positive_number(required(table, "radius", key)?, &sub("radius"))?;
+                if kind == "cone" {Cone::new(base, top, radius, material(table)?)} else {Cylinder::new(base, top, radius, material(table)?)}
+            },
+            "capsule" => {
+                check_keys(table, key, &["type", "start", "end", "radius", "material", "transform", "keyframes"])?;
+                let start = as_vec3(required(table, "start", key)?, &sub("start"))?;
+                let end = as_vec3(required(table, "end", key)?, &sub("end"))?;
+                let radius = positive_number(required(table, "radius", key)?, &sub("radius"))?;
+                Capsule::new(start, end, radius, material(table)?)
+            },
+            "torus" => {
+                check_keys(table, key, &["type", "center", "axis", "major_radius", "minor_radius", "material", "transform", "keyframes"])?;
+                let center = as_vec3(required(table, "center", key)?, &sub("center"))?;
+                let axis = direction(required(table, "axis", key)?, &sub("axis"))?;
+                let major_radius = positive_number(required(table, "major_radius", key)?, &sub("major_radius"))?;
+                let minor_radius = positive_number(required(table, "minor_radius", key)?, &sub("minor_radius"))?;
+                Torus::new(center, axis, major_radius, minor_radius, material(table)?)
+            },
+            "obj" => {
+                check_keys(table, key, &["type", "path", "transform", "keyframes"])?;
+                let path = self.base.join(as_str(required(table, "path", key)?, &sub("path"))?);
+                Hittable::clone(&*self.model(path, &sub("path"))?)
+            },
+            "csg" => {
+                check_keys(table, key, &["type", "operation", "left", "right", "transform", "keyframes"])?;
+                let operation = match as_str(required(table, "operation", key)?, &sub("operation"))? {
+                    "union" => CsgOperation::Union,
+                    "intersection" => CsgOperation::Intersection,
+                    "difference" => CsgOperation::Difference,
+                    other => return key_err(&sub("operation"), format!("unknown operation `{other}`, expected union, intersection or difference")),
+                };
+                let left = self.csg_operand(table, "left", key, materials)?;
+                let right = self.csg_operand(table, "right", key, materials)?;
+                Csg::new(operation, left, right)
+            },
+            "constant_medium" => {
+                check_keys(table, key, &["type", "boundary", "density", "albedo", "anisotropy", "transform", "keyframes"])?;
+                let density = positive_number(required(table, "density", key)?, &sub("density"))?;
+                let albedo = optional(table, "albedo", key, as_color)?.unwrap_or(Color::new(1.0, 1.0, 1.0));
+                let phase = PhaseFunction::from_anisotropy(optional(table, "anisotropy", key, anisotropy)?.unwrap_or(0.0));
+                let boundary = self.boundary(as_table(required(table, "boundary", key)?, &sub("boundary"))?, &sub("boundary"))?;
+                ConstantMedium::new(boundary, density, albedo, phase)
+            },
+            other => key_err(&sub("type"), format!("unknown object type `{other}`, expected sphere, triangle, quad, plane, disk, box, cylinder, cone, capsule, torus, obj, csg or constant_medium"))?,
+        };
+        Ok(match (transform, keyframes) {
+            (Some(transform), _) => Instance::new(object, transform),
+            (_, Some(keyframes)) => Instance::animated(object, keyframes),
+            (None, None) => object,
+        })
+    }
+
+    /// One side of a `csg` object, which has to bound a solid: open surfaces
+    /// and media have no inside to combine
+    fn csg_operand(&self, table: &Table, side: &str, key: &str, materials: &HashMap<String, Material>) -> Result<Hittable> {
+        let k = format!("{key}.{side}");
+        let operand = as_table(required(table, side, key)?, &k)?;
+        match type_name(operand, &k)? {
+            kind @ ("triangle" | "quad" | "disk" | "obj") => key_err(&format!("{k}.type"), format!("`{kind}` is an open surface, csg needs closed objects or planes")),
+            "constant_medium" => key_err(&format!("{k}.type"), "`constant_medium` is a volume, csg needs closed objects or planes"),
+            _ => self.shape(operand, &k, materials),
+        }
+    }
+
+    /// Loads an OBJ file, passing on what it skipped as warnings
+    fn load_obj(&self, path: PathBuf, key: &str) -> Result<ObjModel> {
+        let model = load_obj(path).or_else(|e| key_err(key, e.to_string()))?;
+        for warning in &model.warnings {
+            eprintln!("warning: {warning}");
+        }
+        Ok(model)
+    }
+
+    /// An OBJ file as one shared BVH, loaded on first use
+    fn model(&self, path: PathBuf, key: &str) -> Result<Arc<Hittable>> {
+        if let Some(model) = self.models.borrow().get(&path) {
+            return Ok(model.clone());
+        }
+        let model = self.load_obj(path.clone(), key)?;
+        let mut list = HittableList::new();
+        list.extend(model.into_hittables());
+        let model = Arc::new(Hittable::Bvh(Arc::new(Bvh::new(list))));
+        self.models.borrow_mut().insert(path, model.clone());
+        Ok(model)
+    }
+
+    fn transform(&self, table: &Table, key: &str) -> Result<Mat4> {
+        check_keys(table, key, &["translate", "rotate", "scale"])?;
+        let transform = self.trs(table, key)?;
+        if transform.matrix().inverse().is_none() {
+            return key_err(key, "must be invertible");
+        }
+        Ok(transform.matrix())
+    }
+
+    /// An array of `{ time, translate, rotate, scale }` tables
+    fn keyframes(&self, value: &Value, key: &str) -> Result<Vec<Keyframe>> {
+        let array = as_array(value, key)?;
+        if array.is_empty() {
+            return key_err(key, "needs at least one keyframe");
+        }
+        array.iter().enumerate().map(|(i, value)| {
+            let k = format!("{key}[{i}]");
+            let table = as_table(value, &k)?;
+            check_keys(table, &k, &["time", "translate", "rotate", "scale"])?;
+            let time = as_f64(required(table, "time", &k)?, &format!("{k}.time"))?;
+            Ok(Keyframe { time, transform: self.trs(table, &k)? })
+        }).collect()
+    }
+
+    /// Scales, then rotates around x, y and z in that order, then translates
+    fn trs(&self, table: &Table, key: &str) -> Result<Trs> {
+        let sub = |name: &str| format!("{key}.{name}");
+        let scale = match table.get("scale") {
+            None => Vec3::new(1.0, 1.0, 1.0),
+            Some(value @ Value::Array(_)) => {
+                let scale = as_vec3(value, &sub("scale"))?;
+                if scale.x == 0.0 || scale.y == 0.0 || scale.z == 0.0 {
+                    return key_err(&sub("scale"), "components must not be zero");
+                }
+                scale
+            },
+            Some(value) => {
+                let factor = positive_number(value, &sub("scale"))?;
+                Vec3::new(factor, factor, factor)
+            },
+        };
+        Ok(Trs {
+            translate: optional(table, "translate", key, as_vec3)?.unwrap_or(Vec3::zero()),
+            rotate: optional(table, "rotate", key, as_vec3)?.unwrap_or(Vec3::zero()),
+            scale,
+        })
+    }
+
+    /// The convex shape a medium fills, it needs no material
+    fn boundary(&self, table: &Table, key: &str) -> Result<Hittable> {
+        let sub = |name: &str| format!("{key}.{name}");
+        match type_name(table, key)? {
+            "sphere" => {
+                check_keys(table, key, &["type", "center", "radius"])?;
+                let center = as_vec3(required(table, "center", key)?, &sub("center"))?;
+                let radius = positive_number(required(table, "radius", key)?, &sub("radius"))?;
+                Ok(Sphere::new(center, radius, Lambertian::new(Color::new(0.0, 0.0, 0.0))))
+            },
+            "box" => {
+                check_keys(table, key, &["type", "min", "max", "rotate"])?;
+                self.cuboid(table, key, Lambertian::new(Color::new(0.0, 0.0, 0.0)))
+            },
+            other => key_err(&sub("type"), format!("unknown boundary type `{other}`, expected sphere or box")),
+        }
+    }
+
+    /// A box between `min` and `max`, turned around its center by `rotate` like a transform
+    fn cuboid(&self, table: &Table, key: &str, material: Material) -> Result<Hittable> {
+        let sub = |name: &str| format!("{key}.{name}");
+        let min = as_vec3(required(table, "min", key)?, &sub("min"))?;
+        let max = as_vec3(required(table, "max", key)?, &sub("max"))?;
+        if !(min.x < max.x && min.y < max.y && min.z < max.z) {
+            return key_err(&sub("max"), "must be greater than `min` in every component");
+        }
+        let Some(rotate) = optional(table, "rotate", key, as_vec3)? else {
+            return Ok(Cuboid::new(min, max, material));
+        };
+        let rotation = Trs { rotate, ..Trs::default() }.matrix();
+        let (center, d) = ((min + max) * 0.5, max - min);
+        let edges = [Vec3::new(d.x, 0.0, 0.0), Vec3::new(0.0, d.y, 0.0), Vec3::new(0.0, 0.0, d.z)].map(|e| rotation.transform_vector(e));
+        Ok(Cuboid::oriented(center + rotation.transform_vector(min - center), edges, material))
+    }
+
+    fn light(&self, table: &Table, key: &str) -> Result<Light> {
+        let sub = |name: &str| format!("{key}.{name}");
+        match type_name(table, key)? {
+            "point" => {
+                check_keys(table, key, &["type", "position", "intensity"])?;
+                let position = as_vec3(required(table, "position", key)?, &sub("position"))?;
+                let intensity = as_color(required(table, "intensity", key)?, &sub("intensity"))?;
+                Ok(PointLight::new(position, intensity))
+            },
+            "spot" => {
+                check_keys(table, key, &["type", "position", "look_at", "intensity", "inner_angle", "outer_angle"])?;
+                let position = as_vec3(required(table, "position", key)?, &sub("position"))?;
+                let look_at = as_vec3(required(table, "look_at", key)?, &sub("look_at"))?;
+                if (look_at - position).near_zero() {
+                    return key_err(&sub("look_at"), "must be different from `position`");
+                }
+                let intensity = as_color(required(table, "intensity", key)?, &sub("intensity"))?;
+                let outer_angle = positive_number(required(table, "outer_angle", key)?, &sub("outer_angle"))?;
+                if outer_angle > 180.0 {
+                    return key_err(&sub("outer_angle"), "must be at most 180 degrees");
+                }
+                let inner_angle = optional(table, "inner_angle", key, non_negative_number)?.unwrap_or(outer_angle);
+                if inner_angle > outer_angle {
+                    return key_err(&sub("inner_angle"), "must not be larger than `outer_angle`");
+                }
+                Ok(SpotLight::new(position, look_at, intensity, inner_angle, outer_angle))
+            },
+            "directional" => {
+                check_keys(table, key, &["type", "direction", "irradiance", "angular_diameter"])?;
+                let direction = direction(required(table, "direction", key)?, &sub("direction"))?;
+                let irradiance = as_color(required(table, "irradiance", key)?, &sub("irradiance"))?;
+                let angular_diameter = optional(table, "angular_diameter", key, non_negative_number)?.unwrap_or(0.0);
+                if angular_diameter >= 180.0 {
+                    return key_err(&sub("angular_diameter"), "must be less than 180 degrees");
+                }
+                Ok(DirectionalLight::new(direction, irradiance, angular_diameter))
+            },
+            other => key_err(&sub("type"), format!("unknown light type `{other}`, expected point, spot or directional")),
+        }
+    }
+}
+
+fn tone_mapper(value: &Value, key: &str) -> Result<ToneMapper> {
+    let (name, table) = match value {
+        Value::String(name) => (name.as_str(), None),
+        Value::Table(table) => (type_name(table, key)?, Some(table)),
+        _ => return key_err(key, "expected a name or a table with a `type`"),
+    };
+    let no_params = |mapper| match table {
+        Some(table) => check_keys(table, key, &["type"]).map(|_| mapper),
+        None => Ok(mapper),
+    };
+    match name {
+        "clamp" => no_params(ToneMapper::Clamp),
+        "reinhard" => no_params(ToneMapper::Reinhard),
+        "aces" => no_params(ToneMapper::Aces),
+        "hable" => no_params(ToneMapper::Hable),
+        "agx" => no_params(ToneMapper::Agx),
+        "extended_reinhard" => {
+            let Some(table) = table else {
+                return key_err(key, "extended_reinhard needs a `white` point, write it as { type = \"extended_reinhard\", white = 4.0 }");
+            };
+            check_keys(table, key, &["type", "white"])?;
+            let white = positive_number(required(table, "white", key)?, &format!("{key}.white"))?;
+            Ok(ToneMapper::ExtendedReinhard { white })
+        },
+        other => key_err(key, format!("unknown tone mapper `{other}`, expected clamp, reinhard, extended_reinhard, aces, hable or agx")),
+    }
+}
+
+/// Rejects keys not in `allowed`, which catches typos that would otherwise be silently ignored
+fn check_keys(table: &Table, key: &str, allowed: &[&str]) -> Result<()> {
+    for name in table.keys() {
+        if !allowed.contains(&name.as_str()) {
+            let full = if key.is_empty() {name.clone()} else {format!("{key}.{name}")};
+            return key_err(&full, format!("unknown key, expected one of: {}", allowed.join(", ")));
+        }
+    }
+    Ok(())
+}
+
+fn required<'a>(table: &'a Table, name: &str, key: &str) -> Result<&'a Value> {
+    table.get(name).map_or_else(|| key_err(&format!("{key}.{name}"), "missing required key"), Ok)
+}
+
+fn optional<T>(table: &Table, name: &str, key: &str, parse: fn(&Value, &str) -> Result<T>) -> Result<Option<T>> {
+    table.get(name).map(|value| parse(value, &format!("{key}.{name}"))).transpose()
+}
+
+fn type_name<'a>(table: &'a Table, key: &str) -> Result<&'a str> {
+    as_str(required(table, "type", key)?, &format!("{key}.type"))
+}
+
+fn as_table<'a>(value: &'a Value, key: &str) -> Result<&'a Table> {
+    value.as_table().map_or_else(|| key_err(key, format!("expected a table, found {}", value.type_str())), Ok)
+}
+
+fn as_array<'a>(value: &'a Value, key: &str) -> Result<&'a [Value]> {
+    value.as_array().map_or_else(|| key_err(key, format!("expected an array, found {}", value.type_str())), |a| Ok(a.as_slice()))
+}
+
+fn as_str<'a>(value: &'a Value, key: &str) -> Result<&'a str> {
+    value.as_str().map_or_else(|| key_err(key, format!("expected a string, found {}", value.type_str())), Ok)
+}
+
+fn as_bool(value: &Value, key: &str) -> Result<bool> {
+    value.as_bool().map_or_else(|| key_err(key, format!("expected a boolean, found {}", value.type_str())), Ok)
+}
+
+fn as_integer(value: &Value, key: &str) -> Result<i64> {
+    value.as_integer().map_or_else(|| key_err(key, format!("expected an integer, found {}", value.type_str())), Ok)
+}
+
+/// Integers are accepted wherever a number is expected
+fn as_f64(value: &Value, key: &str) -> Result<f64> {
+    match value {
+        Value::Float(f) => Ok(*f),
+        Value::Integer(i) => Ok(*i as f64),
+        _ => key_err(key, format!("expected a number, found {}", value.type_str())),
+    }
+}
+
+fn positive_number(value: &Value, key: &str) -> Result<f64> {
+    let number = as_f64(value, key)?;
+    if number > 0.0 {Ok(number)} else {key_err(key, "must be greater than zero")}
+}
+
+fn non_negative_number(value: &Value, key: &str) -> Result<f64> {
+    let number = as_f64(value, key)?;
+    if number >= 0.0 {Ok(number)} else {key_err(key, "must not be negative")}
+}
+
+fn unit_number(value: &Value, key: &str) -> Result<f64> {
+    let number = as_f64(value, key)?;
+    if (0.0..=1.0).contains(&number) {Ok(number)} else {key_err(key, "must be between 0 and 1")}
+}
+
+/// The Henyey-Greenstein asymmetry parameter
+fn anisotropy(value: &Value, key: &str) -> Result<f64> {
+    let number = as_f64(value, key)?;
+    if number > -1.0 && number < 1.0 {Ok(number)} else {key_err(key, "must be between -1 and 1")}
+}
+
+fn positive_integer(value: &Value, key: &str) -> Result<usize> {
+    let number = as_integer(value, key)?;
+    if number > 0 {Ok(number as usize)} else {key_err(key, "must be greater than zero")}
+}
+
+fn as_triple(value: &Value, key: &str) -> Result<[f64; 3]> {
+    let array = as_array(value, key)?;
+    let [a, b, c] = array else {
+        return key_err(key, format!("expected 3 numbers, found {}", array.len()));
+    };
+    Ok([as_f64(a, &format!("{key}[0]"))?, as_f64(b, &format!("{key}[1]"))?, as_f64(c, &format!("{key}[2]"))?])
+}
+
+fn as_vec3(value: &Value, key: &str) -> Result<Vec3> {
+    let [x, y, z] = as_triple(value, key)?;
+    Ok(Vec3::new(x, y, z))
+}
+
+/// A vector that can be normalized
+fn direction(value: &Value, key: &str) -> Result<Vec3> {
+    let direction = as_vec3(value, key)?;
+    if direction.near_zero() {
+        return key_err(key, "must not be zero");
+    }
+    Ok(direction)
+}
+
+fn as_color(value: &Value, key: &str) -> Result<Color> {
+    let [r, g, b] = as_triple(value, key)?;
+    if r < 0.0 || g < 0.0 || b < 0.0 {
+        return key_err(key, "color components must not be negative");
+    }
+    Ok(Color::new(r, g, b))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process;
+
+    use super::*;
+
+    /// Loads `source` as a scene file, returning the key and message of the error
+    fn error(test: &str, source: &str) -> (Option<String>, String) {
+        let path = std::env::temp_dir().join(format!("scene-{test}-{}.toml", process::id()));
+        fs::write(&path, source).unwrap();
+        let result = load_scene(&path);
+        fs::remove_file(&path).unwrap();
+        let error = result.err().expect("the scene should not load");
+        assert_eq!(error.path, path);
+        (error.key, error.message)
+    }
+
+    const MATERIALS: &str = "[materials.red]\ntype = \"lambertian\"\nalbedo = [0.8, 0.1, 0.1]\n";
+
+    /// Errors point at the key they're about, down into arrays and tables
+    #[test]
+    fn key_errors() {
+        let sphere = |lines: &str| format!("{MATERIALS}[[objects]]\ntype = \"sphere\"\n{lines}");
+        let csg = |left: &str| format!("{MATERIALS}[[objects]]\ntype = \"csg\"\noperation = \"difference\"\n{left}right = {{ type = \"sphere\", center = [0, 0, 0], radius = 1, material = \"red\" }}\n");
+        let cases = [
+            ("unknown-key", String::from("[camera]\nfov = 40\n"), "camera.fov", "unknown key, expected one of: image_width, image_hight, aspect_ratio, vfov"),
+            ("wrong-type", String::from("[camera]\nvfov = \"wide\"\n"), "camera.vfov", "expected a number, found string"),
+            ("wrong-element", sphere("center = [0, \"up\", 0]\nradius = 1\nmaterial = \"red\"\n"), "objects[0].center[1]", "expected a number, found string"),
+            ("wrong-length", sphere("center = [0, 0]\nradius = 1\nmaterial = \"red\"\n"), "objects[0].center", "expected 3 numbers, found 2"),
+            ("out-of-range", String::from("[camera]\nvfov = 180\n"), "camera.vfov", "must be between 0 and 180 degrees"),
+            ("negative", sphere("center = [0, 0, 0]\nradius = -1\nmaterial = \"red\"\n"), "objects[0].radius", "must be greater than zero"),
+            ("unknown-material", sphere("center = [0, 0, 0]\nradius = 1\nmaterial = \"blue\"\n"), "objects[0].material", "no material named `blue`"),
+            ("missing-key", sphere("center = [0, 0, 0]\nmaterial = \"red\"\n"), "objects[0].radius", "missing required key"),
+            ("open-operand", csg("left = { type = \"quad\", corner = [0, 0, 0], u = [1, 0, 0], v = [0, 1, 0], material = \"red\" }\n"), "objects[0].left.type", "`quad` is an open surface"),
+            ("nested-operand", csg("left = { type = \"csg\", operation = \"union\", left = { type = \"plane\", point = [0, 0, 0], normal = [0, 1, 0], material = \"red\" }, right = { type = \"obj\", path = \"model.obj\" } }\n"), "objects[0].left.right.type", "`obj` is an open surface"),
+            ("medium-operand", csg("left = { type = \"constant_medium\", boundary = { type = \"sphere\", center = [0, 0, 0], radius = 1 }, density = 1 }\n"), "objects[0].left.type", "`constant_medium` is a volume"),
+        ];
+        for (test, source, key, message) in cases {
+            let (got_key, got_message) = error(test, &source);
+            assert_eq!(got_key.as_deref(), Some(key), "{test}");
+            assert!(got_message.starts_with(message), "{test}: {got_message}");
+        }
+    }
+
+    /// The camera can't look where it is, nor straight up or down since
+    /// that leaves no way to keep +y up
+    #[test]
+    fn camera_direction() {
+        for (test, center, look_at) in [("same", "[1, 2, 3]", "[1, 2, 3]"), ("up", "[1, 2, 3]", "[1, 5, 3]"), ("down", "[0, 0, 0]", "[0, -0.5, 0]")] {
+            let (key, message) = error(test, &format!("[camera]\ncamera_center = {center}\nlook_at = {look_at}\n"));
+            assert_eq!(key.as_deref(), Some("camera.look_at"));
+            let expected = if test == "same" {"must be different from `camera_center`"} else {"must not be straight above or below `camera_center`"};
+            assert_eq!(message, expected);
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use std::thread;
+
+use crate::{background::Background, camera::CameraBuilder, color::Color, hittable_list::HittableList, light::DirectionalLight, material::{Dielectric, DiffuseLight, Lambertian, Material, Metal}, principled::{scalar_texture, Principled}, sphere::Sphere, texture::{Checker, Texture}, triangle::Triangle, vec3::Vec3};
+
+/// Names accepted by `builtin`
+pub const BUILTIN_SCENES: &[&str] = &["spheres", "cornell", "materials"];
+
+/// Scenes compiled into the binary, for when no scene file is given
+pub fn builtin(name: &str) -> Option<(CameraBuilder, HittableList)> {
+    match name {
+        "spheres" => Some(spheres()),
+        "cornell" => Some(cornell_box()),
+        "materials" => Some(material_preview()),
+        _ => None,
+    }
+}
+
+fn available_threads() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// Adds the parallelogram `q`, `q + u`, `q + u + v`, `q + v` as two triangles facing `cross(u, v)`
+fn add_quad(world: &mut HittableList, q: Vec3, u: Vec3, v: Vec3, mat: &Material) {
+    world.add(Triangle::new(q, q + u, q + u + v, mat.clone()));
+    world.add(Triangle::new(q, q + u + v, q + v, mat.clone()));
+}
+
+pub fn spheres() -> (CameraBuilder, HittableList) {
+    let mut world = HittableList::new();
+    let matte_blue = Lambertian::new(Color::new(0.1, 0.2, 0.5));
+    let matte_yellow = Lambertian::new(Color::new(0.8, 0.7, 0.0));
+    let matte_white = Lambertian::new(Color::new(1.0, 1.0, 1.0));
+    let metal = Metal::new(Color::new(0.8, 0.8, 0.8), 0.0);
+    let metal_fuzzy = Metal::new(Color::new(0.8, 0.8, 0.8), 1.0);
+    let glass = Dielectric::new(1.5);
+    let glass_inside = Dielectric::new(0.75);
+
+    world.add(Sphere::new(Vec3::new( 0.0, -100.5, -1.0), 100.0, matte_yellow)); // floor
+    world.add(Sphere::new(Vec3::new( 0.0, 0.0,    -1.2), 0.5,   matte_blue));   // center
+    world.add(Sphere::new(Vec3::new(-1.0, 0.0,    -1.0), 0.5,   glass));        // left
+    world.add(Sphere::new(Vec3::new(-1.0, 0.0,    -1.0), 0.4,   glass_inside)); // left (inside)
+    world.add(Sphere::new(Vec3::new( 1.0, 0.0,    -1.0), 0.5,   metal_fuzzy));  // right
+
+    let camera = CameraBuilder {
+        camera_center: Vec3::new(-2.0, 2.0, 1.0),
+        look_at: Vec3::new(0.0, 0.0, -1.0),
+        image_width: 800,
+        aspect_ratio: 16.0/9.0,
+        vfov: 50.0,
+        depth_of_field_blur: 0.15,
+        thread_count: available_threads(),
+        max_depth: 50,
+        samples_per_pixel: 100,
+        ..Default::default()
+    };
+
+    (camera, world)
+}
+
+/// The classic Cornell box, lit only by the ceiling light
+pub fn cornell_box() -> (CameraBuilder, HittableList) {
+    let mut world = HittableList::new();
+    let red = Lambertian::new(Color::new(0.65, 0.05, 0.05));
+    let white = Lambertian::new(Color::new(0.73, 0.73, 0.73));
+    let green = Lambertian::new(Color::new(0.12, 0.45, 0.15));
+    let light = DiffuseLight::new(Color::new(15.0, 15.0, 15.0));
+
+    add_quad(&mut world, Vec3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 555.0), Vec3::new(0.0, 555.0, 0.0), &green); // left
+    add_quad(&mut world, Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 555.0, 0.0), Vec3::new(0.0, 0.0, 555.0), &red); // right
+    add_quad(&mut world, Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 555.0), Vec3::new(555.0, 0.0, 0.0), &white); // floor
+    add_quad(&mut world, Vec3::new(0.0, 555.0, 0.0), Vec3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 555.0), &white); // ceiling
+    add_quad(&mut world, Vec3::new(0.0, 0.0, 555.0), Vec3::new(0.0, 555.0, 0.0), Vec3::new(555.0, 0.0, 0.0), &white); // back
+    add_quad(&mut world, Vec3::new(213.0, 554.0, 227.0), Vec3::new(130.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 105.0), &light); // light, facing down
+
+    world.add(Sphere::new(Vec3::new(190.0, 90.0, 190.0), 90.0, white));
+    world.add(Sphere::new(Vec3::new(370.0, 90.0, 370.0), 90.0, Dielectric::new(1.5)));
+
+    let camera = CameraBuilder {
+        camera_center: Vec3::new(278.0, 278.0, -800.0),
+        look_at: Vec3::new(278.0, 278.0, 0.0),
+        image_width: 600,
+        aspect_ratio: 1.0,
+        vfov: 40.0,
+        background: Background::Solid(Color::new(0.0, 0.0, 0.0)),
+        thread_count: available_threads(),
+        max_depth: 50,
+        samples_per_pixel: 200,
+        ..Default::default()
+    };
+
+    (camera, world)
+}
+
+/// Swatches of the principled material: a roughness sweep of plastic and of
+/// metal, then clear coat, sheen, glass, frosted glass and emission
+pub fn material_preview() -> (CameraBuilder, HittableList) {
+    let mut world = HittableList::new();
+    let floor = Lambertian::new(Checker::new(0.5, Color::new(0.2, 0.2, 0.2), Color::new(0.8, 0.8, 0.8)));
+    world.add(Sphere::new(Vec3::new(0.0, -1000.0, 0.0), 1000.0, floor));
+
+    let red = Texture::from(Color::new(0.8, 0.1, 0.1));
+    let gold = Texture::from(Color::new(1.0, 0.78, 0.34));
+    for i in 0..5 {
+        let x = 1.2 * (i as f64 - 2.0);
+        let roughness = scalar_texture(i as f64 / 4.0);
+        let plastic = Principled { base_color: red.clone(), roughness: roughness.clone(), ..Default::default() };
+        let metal = Principled { base_color: gold.clone(), metallic: scalar_texture(1.0), roughness, ..Default::default() };
+        world.add(Sphere::new(Vec3::new(x, 0.5, -2.4), 0.5, plastic.into()));
+        world.add(Sphere::new(Vec3::new(x, 0.5, -1.2), 0.5, metal.into()));
+    }
+
+    let swatches = [
+        Principled {
+            base_color: Texture::from(Color::new(0.05, 0.15, 0.6)),
+            roughness: scalar_texture(0.6),
+            clearcoat: 1.0,
+            ..Default::default()
+        },
+        Principled {
+            base_color: Texture::from(Color::new(0.5, 0.05, 0.3)),
+            roughness: scalar_texture(1.0),
+            specular: 0.0,
+            sheen: 1.0,
+            sheen_tint: 0.8,
+            ..Default::default()
+        },
+        Principled { base_color: Texture::from(Color::new(1.0, 1.0, 1.0)), roughness: scalar_texture(0.0), transmission: 1.0, ..Default::default() },
+        Principled { base_color: Texture::from(Color::new(0.7, 0.9, 0.8)), roughness: scalar_texture(0.3), transmission: 1.0, ..Default::default() },
+        Principled {
+            base_color: Texture::from(Color::new(0.1, 0.1, 0.1)),
+            emission: Texture::from(Color::new(1.5, 0.8, 0.3)),
+            ..Default::default()
+        },
+    ];
+    for (i, swatch) in swatches.into_iter().enumerate() {
+        world.add(Sphere::new(Vec3::new(1.2 * (i as f64 - 2.0), 0.5, 0.0), 0.5, swatch.into()));
+    }
+    world.add_light(DirectionalLight::new(Vec3::new(-1.0, -2.0, -1.5), Color::new(2.5, 2.5, 2.5), 2.0));
+
+    let camera = CameraBuilder {
+        camera_center: Vec3::new(0.0, 3.2, 5.0),
+        look_at: Vec3::new(0.0, 0.3, -1.2),
+        image_width: 800,
+        aspect_ratio: 16.0/9.0,
+        vfov: 40.0,
+        thread_count: available_threads(),
+        max_depth: 50,
+        samples_per_pixel: 128,
+        ..Default::default()
+    };
+
+    (camera, world)
+}
@@ -1,21 +1,42 @@
-use crate::{hittable::{HitRecord, Hittable}, interval::Interval, material::Material, ray::Ray, vec3::{dot, Vec3}};
+use std::f64::consts::PI;
 
-#[derive(Clone, Copy)]
+use crate::{aabb::Aabb, hittable::{HitRecord, Hittable}, interval::Interval, material::Material, onb::Onb, ray::Ray, sampler::sample_unit_sphere, vec3::{dot, Vec3}};
+
+#[derive(Clone)]
 pub struct Sphere {
+    /// Where the center is at time 0
     pub center: Vec3,
     pub radius: f64,
     pub mat: Material,
+    /// How far the center moves from time 0 to 1
+    pub motion: Vec3,
 }
 
 impl Sphere {
     pub fn new(center: Vec3, radius: f64, matterial: Material) -> Hittable {
-        Hittable::Sphere(Self {center, radius, mat: matterial})
+        Hittable::Sphere(Box::new(Self {center, radius, mat: matterial, motion: Vec3::zero()}))
+    }
+    /// A sphere moving in a straight line from `center` at time 0 to `center_end` at time 1
+    pub fn moving(center: Vec3, center_end: Vec3, radius: f64, matterial: Material) -> Hittable {
+        Hittable::Sphere(Box::new(Self {center, radius, mat: matterial, motion: center_end - center}))
+    }
+    pub fn is_moving(&self) -> bool {
+        self.motion.len_squared() > 0.0
+    }
+    pub fn center_at(&self, time: f64) -> Vec3 {
+        self.center + self.motion * time
     }
 }
 
 impl Sphere {
-    pub fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord> {
-        let oc = self.center - ray.origin;
+    pub fn bounding_box(&self) -> Aabb {
+        let r = Vec3::new(self.radius, self.radius, self.radius);
+        let end = self.center_at(1.0);
+        Aabb::new(self.center - r, self.center + r).union(Aabb::new(end - r, end + r))
+    }
+    pub fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let center = self.center_at(ray.time);
+        let oc = center - ray.origin;
         let a = ray.direction.len_squared();
         let h = dot(ray.direction, oc);
         let c = oc.len_squared() - (self.radius*self.radius);
@@ -36,7 +57,8 @@ impl Sphere {
         }
 
         let p = ray.at(root);
-        let mut normal = (ray.at(root) - self.center) / self.radius;
+        let mut normal = (ray.at(root) - center) / self.radius;
+        let (u, v) = Self::uv(normal);
 
         let mut front_face = true;
         if ray.direction.dot(normal) > 0.0 {
@@ -49,9 +71,81 @@ impl Sphere {
             normal,
             t: root,
             front_face,
-            mat: self.mat
+            mat: &self.mat,
+            u,
+            v,
         };
 
         Some(hit)
     }
-}
\ No newline at end of file
+    /// Longitude and latitude of a point on the unit sphere, both in [0, 1].
+    /// `u` goes around the y axis starting at -x, `v` from the bottom pole to the top.
+    fn uv(outward: Vec3) -> (f64, f64) {
+        let theta = (-outward.y).clamp(-1.0, 1.0).acos();
+        let phi = (-outward.z).atan2(outward.x) + PI;
+        (phi / (2.0 * PI), theta / PI)
+    }
+    /// Density, per solid angle at `origin`, of reaching `direction` with `sample`.
+    /// Light sampling only looks at static spheres.
+    pub fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f64 {
+        let distance_squared = (self.center - origin).len_squared();
+        let radius_squared = self.radius * self.radius;
+        if distance_squared <= radius_squared {
+            return 1.0 / (4.0 * PI);
+        }
+        if self.hit(&Ray::new(origin, direction), Interval::new(0.001, f64::INFINITY)).is_none() {
+            return 0.0;
+        }
+        let cos_theta_max = (1.0 - radius_squared / distance_squared).sqrt();
+        1.0 / (2.0 * PI * (1.0 - cos_theta_max))
+    }
+    /// Direction towards a point on the sphere, uniform over the cone of
+    /// directions it covers, or over all directions from inside it
+    pub fn sample(&self, origin: Vec3, (u, v): (f64, f64)) -> Vec3 {
+        let to_center = self.center - origin;
+        let distance_squared = to_center.len_squared();
+        let radius_squared = self.radius * self.radius;
+        if distance_squared <= radius_squared {
+            return sample_unit_sphere((u, v));
+        }
+        let cos_theta_max = (1.0 - radius_squared / distance_squared).sqrt();
+        let z = 1.0 + u * (cos_theta_max - 1.0);
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        let phi = 2.0 * PI * v;
+        Onb::new(to_center.unit()).to_world(Vec3::new(r * phi.cos(), r * phi.sin(), z))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{hit, material};
+
+    /// Surface coordinates from `hit`, at the poles, around the equator and
+    /// on either side of the seam
+    #[test]
+    fn uv() {
+        let sphere = Sphere::new(Vec3::new(3.0, 1.0, -2.0), 2.0, material());
+        let uv_from = |direction: Vec3| {
+            let rec = hit(&sphere, Vec3::new(3.0, 1.0, -2.0) - direction * 5.0, direction).unwrap();
+            (rec.u, rec.v)
+        };
+
+        assert_eq!(uv_from(Vec3::new(0.0, 1.0, 0.0)).1, 0.0);
+        assert_eq!(uv_from(Vec3::new(0.0, -1.0, 0.0)).1, 1.0);
+        // around y from -x, through +z, +x and -z
+        let equator = [(Vec3::new(1.0, 0.0, 0.0), 0.0), (Vec3::new(0.0, 0.0, -1.0), 0.25), (Vec3::new(-1.0, 0.0, 0.0), 0.5), (Vec3::new(0.0, 0.0, 1.0), 0.75)];
+        for (direction, u) in equator {
+            let (got_u, got_v) = uv_from(direction);
+            assert!((got_u - u).abs() < 1e-12 && (got_v - 0.5).abs() < 1e-12, "{direction:?}: {got_u} {got_v}");
+        }
+
+        // just either side of -x the seam goes from 1 back to 0
+        let near_seam = |z: f64| {
+            let rec = hit(&sphere, Vec3::new(-5.0, 1.0, -2.0 + z), Vec3::new(1.0, 0.0, 0.0)).unwrap();
+            rec.u
+        };
+        assert!(near_seam(-1e-3) > 0.999 && near_seam(-1e-3) <= 1.0);
+        assert!(near_seam(1e-3) < 1e-3 && near_seam(1e-3) >= 0.0);
+    }
+}
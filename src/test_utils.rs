@@ -0,0 +1,13 @@
+//! Fixtures shared by the unit tests of the shapes
+
+use crate::{color::Color, hittable::{HitRecord, Hittable}, interval::Interval, material::{Lambertian, Material}, ray::Ray, vec3::Vec3};
+
+/// A plain grey diffuse material for shapes whose material doesn't matter
+pub fn material() -> Material {
+    Lambertian::new(Color::new(0.5, 0.5, 0.5))
+}
+
+/// The nearest hit in front of `origin` along `direction`
+pub fn hit(object: &Hittable, origin: Vec3, direction: Vec3) -> Option<HitRecord<'_>> {
+    object.hit(&Ray::new(origin, direction), Interval::new(0.001, f64::INFINITY))
+}
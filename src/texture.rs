@@ -0,0 +1,419 @@
+use std::{fs::{self, File}, io::BufReader, path::Path, sync::Arc};
+
+use rand::seq::SliceRandom;
+
+use crate::{color::Color, hdr::{load_hdr, HdrError, HdrImage}, utils::Pcg32, vec3::Vec3};
+
+/// A color that varies over a surface, looked up with the hit's surface
+/// coordinates and position
+#[derive(Debug, Clone)]
+pub enum Texture {
+    Solid(Color),
+    Checker(Checker),
+    Image(ImageTexture),
+    Noise(NoiseTexture),
+}
+
+impl Texture {
+    pub fn value(&self, u: f64, v: f64, p: Vec3) -> Color {
+        match self {
+            Texture::Solid(color) => *color,
+            Texture::Checker(checker) => checker.value(u, v, p),
+            Texture::Image(image) => image.value(u, v),
+            Texture::Noise(noise) => noise.value(p),
+        }
+    }
+}
+
+impl From<Color> for Texture {
+    fn from(color: Color) -> Self {
+        Texture::Solid(color)
+    }
+}
+
+/// Alternating cubes of two textures filling space
+#[derive(Debug, Clone)]
+pub struct Checker {
+    inv_scale: f64,
+    pub even: Arc<Texture>,
+    pub odd: Arc<Texture>,
+}
+
+impl Checker {
+    /// `scale` is the edge length of one cube
+    pub fn new(scale: f64, even: impl Into<Texture>, odd: impl Into<Texture>) -> Texture {
+        Texture::Checker(Checker { inv_scale: 1.0 / scale, even: Arc::new(even.into()), odd: Arc::new(odd.into()) })
+    }
+    fn value(&self, u: f64, v: f64, p: Vec3) -> Color {
+        let x = (p.x * self.inv_scale).floor() as i64;
+        let y = (p.y * self.inv_scale).floor() as i64;
+        let z = (p.z * self.inv_scale).floor() as i64;
+        if (x + y + z).rem_euclid(2) == 0 {self.even.value(u, v, p)} else {self.odd.value(u, v, p)}
+    }
+}
+
+/// What happens to surface coordinates outside [0, 1]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum WrapMode {
+    #[default]
+    Repeat,
+    /// Repeats every other copy flipped, so edges always line up
+    Mirror,
+    /// Stretches the edge texels outwards
+    Clamp,
+}
+
+impl WrapMode {
+    fn apply(self, i: i64, size: usize) -> usize {
+        let size = size as i64;
+        let i = match self {
+            WrapMode::Repeat => i.rem_euclid(size),
+            WrapMode::Mirror => {
+                let i = i.rem_euclid(2 * size);
+                if i < size {i} else {2 * size - 1 - i}
+            },
+            WrapMode::Clamp => i.clamp(0, size - 1),
+        };
+        i as usize
+    }
+}
+
+/// An image mapped onto the surface coordinates with bilinear filtering,
+/// `v` runs from the bottom row at 0 to the top row at 1
+#[derive(Debug, Clone)]
+pub struct ImageTexture {
+    pub image: Arc<HdrImage>,
+    pub wrap: WrapMode,
+}
+
+impl ImageTexture {
+    pub fn new(image: Arc<HdrImage>, wrap: WrapMode) -> Texture {
+        Texture::Image(ImageTexture { image, wrap })
+    }
+    /// Loads a `.png`, `.ppm` or `.hdr` image
+    pub fn load(path: impl AsRef<Path>, wrap: WrapMode) -> Result<Texture, HdrError> {
+        Ok(Self::new(Arc::new(load_image(path)?), wrap))
+    }
+    fn value(&self, u: f64, v: f64) -> Color {
+        let (width, height) = (self.image.width, self.image.height);
+
+        // texel centers sit at half integer coordinates
+        let x = u * width as f64 - 0.5;
+        let y = (1.0 - v) * height as f64 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+
+        let texel = |dx: i64, dy: i64| {
+            let xi = self.wrap.apply(x0 as i64 + dx, width);
+            let yi = self.wrap.apply(y0 as i64 + dy, height);
+            self.image.get(xi, yi)
+        };
+
+        let top = texel(0, 0) * (1.0 - tx) + texel(1, 0) * tx;
+        let bottom = texel(0, 1) * (1.0 - tx) + texel(1, 1) * tx;
+        top * (1.0 - ty) + bottom * ty
+    }
+}
+
+/// Loads an image as linear colors, picking the decoder by extension.
+/// 8 and 16 bit images are assumed to be sRGB encoded.
+pub fn load_image(path: impl AsRef<Path>) -> Result<HdrImage, HdrError> {
+    let path = path.as_ref();
+    let extension = path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase);
+    let format_err = |message: String| HdrError::Format { path: path.to_path_buf(), message };
+    match extension.as_deref() {
+        Some("hdr") => load_hdr(path),
+        Some("png") => {
+            let file = File::open(path).map_err(|error| HdrError::Io { path: path.to_path_buf(), error })?;
+            decode_png(BufReader::new(file)).map_err(format_err)
+        },
+        Some("ppm") => {
+            let data = fs::read(path).map_err(|error| HdrError::Io { path: path.to_path_buf(), error })?;
+            decode_ppm(&data).map_err(format_err)
+        },
+        _ => Err(format_err(String::from("unsupported image format, expected .png, .ppm or .hdr"))),
+    }
+}
+
+pub(crate) fn decode_png(reader: impl std::io::Read) -> Result<HdrImage, String> {
+    let mut decoder = png::Decoder::new(reader);
+    // palettes and low bit depths become 8 bit, 16 bit stays
+    decoder.set_transformations(png::Transformations::EXPAND);
+    let mut reader = decoder.read_info().map_err(|e| e.to_string())?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).map_err(|e| e.to_string())?;
+    let data = &buffer[..info.buffer_size()];
+
+    let channels = info.color_type.samples();
+    let samples: Vec<f64> = match info.bit_depth {
+        png::BitDepth::Sixteen => data.chunks_exact(2).map(|b| u16::from_be_bytes([b[0], b[1]]) as f64 / 65535.0).collect(),
+        png::BitDepth::Eight => data.iter().map(|&b| b as f64 / 255.0).collect(),
+        depth => return Err(format!("unsupported bit depth {depth:?}")),
+    };
+
+    let pixels = samples.chunks_exact(channels)
+        .map(|s| match s {
+            // alpha is ignored
+            [gray] | [gray, _] => Color::new(srgb_to_linear(*gray), srgb_to_linear(*gray), srgb_to_linear(*gray)),
+            [r, g, b] | [r, g, b, _] => Color::new(srgb_to_linear(*r), srgb_to_linear(*g), srgb_to_linear(*b)),
+            _ => unreachable!(),
+        })
+        .collect();
+    Ok(HdrImage { width: info.width as usize, height: info.height as usize, pixels })
+}
+
+/// Plain (P3) and binary (P6) PPM
+pub(crate) fn decode_ppm(data: &[u8]) -> Result<HdrImage, String> {
+    let mut pos = 0;
+    // header fields are separated by whitespace, `#` starts a comment
+    let mut next_field = || -> Result<&str, String> {
+        loop {
+            while data.get(pos).is_some_and(|b| b.is_ascii_whitespace()) {
+                pos += 1;
+            }
+            if data.get(pos) == Some(&b'#') {
+                while data.get(pos).is_some_and(|&b| b != b'\n') {
+                    pos += 1;
+                }
+                continue;
+            }
+            break;
+        }
+        let start = pos;
+        while data.get(pos).is_some_and(|b| !b.is_ascii_whitespace()) {
+            pos += 1;
+        }
+        if start == pos {
+            return Err(String::from("unexpected end of header"));
+        }
+        std::str::from_utf8(&data[start..pos]).map_err(|_| String::from("header is not valid text"))
+    };
+
+    let magic = next_field()?.to_string();
+    if magic != "P3" && magic != "P6" {
+        return Err(format!("unsupported PPM type `{magic}`, expected P3 or P6"));
+    }
+    let mut number = |name: &str| -> Result<usize, String> {
+        let field = next_field()?;
+        field.parse().map_err(|_| format!("bad {name} `{field}`"))
+    };
+    let width = number("width")?;
+    let height = number("height")?;
+    let max = number("maximum value")?;
+    if width == 0 || height == 0 {
+        return Err(String::from("image has no pixels"));
+    }
+    if max == 0 || max > 65535 {
+        return Err(format!("maximum value {max} out of range"));
+    }
+
+    let count = width * height * 3;
+    let samples: Vec<usize> = if magic == "P3" {
+        (0..count).map(|_| number("sample")).collect::<Result<_, _>>()?
+    } else {
+        // exactly one whitespace byte separates the header from the pixels
+        let start = pos + 1;
+        let bytes_per_sample = if max < 256 {1} else {2};
+        let body = data.get(start..start + count * bytes_per_sample).ok_or("unexpected end of pixel data")?;
+        if bytes_per_sample == 1 {
+            body.iter().map(|&b| b as usize).collect()
+        } else {
+            body.chunks_exact(2).map(|b| u16::from_be_bytes([b[0], b[1]]) as usize).collect()
+        }
+    };
+
+    let pixels = samples.chunks_exact(3)
+        .map(|s| {
+            let channel = |v: usize| srgb_to_linear(v.min(max) as f64 / max as f64);
+            Color::new(channel(s[0]), channel(s[1]), channel(s[2]))
+        })
+        .collect();
+    Ok(HdrImage { width, height, pixels })
+}
+
+/// Inverse of `tonemap::srgb_oetf`
+pub fn srgb_to_linear(encoded: f64) -> f64 {
+    if encoded <= 0.04045 {
+        encoded / 12.92
+    } else {
+        ((encoded + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// How the noise value becomes a color
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum NoiseKind {
+    /// Smooth Perlin noise
+    #[default]
+    Perlin,
+    /// Several octaves of noise summed, like smoke or clouds
+    Turbulence,
+    /// Sine stripes along z, disturbed by turbulence
+    Marble,
+}
+
+/// Procedural noise scaling `color`
+#[derive(Debug, Clone)]
+pub struct NoiseTexture {
+    perlin: Arc<Perlin>,
+    pub kind: NoiseKind,
+    /// Frequency of the noise, larger values give finer detail
+    pub scale: f64,
+    pub color: Color,
+}
+
+impl NoiseTexture {
+    pub fn new(kind: NoiseKind, scale: f64, color: Color) -> Texture {
+        Texture::Noise(NoiseTexture { perlin: Arc::new(Perlin::new()), kind, scale, color })
+    }
+    fn value(&self, p: Vec3) -> Color {
+        let p = p * self.scale;
+        let value = match self.kind {
+            NoiseKind::Perlin => 0.5 * (1.0 + self.perlin.noise(p)),
+            NoiseKind::Turbulence => self.perlin.turbulence(p, 7),
+            NoiseKind::Marble => 0.5 * (1.0 + (p.z + 10.0 * self.perlin.turbulence(p, 7)).sin()),
+        };
+        self.color * value
+    }
+}
+
+const POINT_COUNT: usize = 256;
+
+/// Gradient noise on a lattice of random unit vectors
+#[derive(Debug)]
+struct Perlin {
+    gradients: Vec<Vec3>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+}
+
+impl Perlin {
+    /// Always the same lattice, so noise textures don't change between renders
+    fn new() -> Self {
+        let mut rng = Pcg32::new(0x5eed, 0);
+        let gradients = (0..POINT_COUNT).map(|_| Vec3::random_vector(&mut rng).unit()).collect();
+        let mut permutation = || {
+            let mut p: Vec<usize> = (0..POINT_COUNT).collect();
+            p.shuffle(&mut rng);
+            p
+        };
+        let (perm_x, perm_y, perm_z) = (permutation(), permutation(), permutation());
+        Self { gradients, perm_x, perm_y, perm_z }
+    }
+
+    /// In roughly [-1, 1]
+    fn noise(&self, p: Vec3) -> f64 {
+        let (fx, fy, fz) = (p.x.floor(), p.y.floor(), p.z.floor());
+        let (u, v, w) = (p.x - fx, p.y - fy, p.z - fz);
+        let (i, j, k) = (fx as i64, fy as i64, fz as i64);
+
+        // Hermite smoothing hides the lattice
+        let (uu, vv, ww) = (u * u * (3.0 - 2.0 * u), v * v * (3.0 - 2.0 * v), w * w * (3.0 - 2.0 * w));
+
+        let mut accum = 0.0;
+        for di in 0..2 {
+            for dj in 0..2 {
+                for dk in 0..2 {
+                    let gradient = self.gradients[
+                        self.perm_x[((i + di) & 255) as usize] ^
+                        self.perm_y[((j + dj) & 255) as usize] ^
+                        self.perm_z[((k + dk) & 255) as usize]
+                    ];
+                    let (fi, fj, fk) = (di as f64, dj as f64, dk as f64);
+                    let weight = Vec3::new(u - fi, v - fj, w - fk);
+                    accum += (fi * uu + (1.0 - fi) * (1.0 - uu))
+                        * (fj * vv + (1.0 - fj) * (1.0 - vv))
+                        * (fk * ww + (1.0 - fk) * (1.0 - ww))
+                        * gradient.dot(weight);
+                }
+            }
+        }
+        accum
+    }
+
+    /// Sum of `depth` octaves, each at twice the frequency and half the weight
+    fn turbulence(&self, p: Vec3, depth: usize) -> f64 {
+        let mut accum = 0.0;
+        let mut p = p;
+        let mut weight = 1.0;
+        for _ in 0..depth {
+            accum += weight * self.noise(p);
+            weight *= 0.5;
+            p = p * 2.0;
+        }
+        accum.abs()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(width: usize, height: usize) -> Arc<HdrImage> {
+        let pixels = (0..width * height).map(|i| Color::new(i as f64, 0.0, 0.0)).collect();
+        Arc::new(HdrImage { width, height, pixels })
+    }
+
+    #[test]
+    fn bilinear_hits_texel_centers() {
+        let texture = ImageTexture::new(image(4, 2), WrapMode::Clamp);
+        // center of the texel in column 1 of the top row
+        assert!((texture.value(0.375, 0.75, Vec3::zero()).r - 1.0).abs() < 1e-12);
+        // halfway between columns 1 and 2 of the bottom row
+        assert!((texture.value(0.5, 0.25, Vec3::zero()).r - 5.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn wrap_modes() {
+        assert_eq!(WrapMode::Repeat.apply(-1, 4), 3);
+        assert_eq!(WrapMode::Repeat.apply(5, 4), 1);
+        assert_eq!(WrapMode::Mirror.apply(-1, 4), 0);
+        assert_eq!(WrapMode::Mirror.apply(5, 4), 2);
+        assert_eq!(WrapMode::Clamp.apply(-3, 4), 0);
+        assert_eq!(WrapMode::Clamp.apply(9, 4), 3);
+    }
+
+    #[test]
+    fn ppm_plain_and_binary_match() {
+        let plain = decode_ppm(b"P3\n# comment\n2 1\n255\n255 0 0  0 128 255\n").unwrap();
+        let binary = decode_ppm(b"P6 2 1 255\n\xff\x00\x00\x00\x80\xff").unwrap();
+        assert_eq!((plain.width, plain.height), (2, 1));
+        for (a, b) in plain.pixels.iter().zip(&binary.pixels) {
+            assert_eq!((a.r, a.g, a.b), (b.r, b.g, b.b));
+        }
+        assert_eq!(plain.get(0, 0).r, 1.0);
+    }
+
+    /// Crossing one cell edge along any axis swaps the two textures,
+    /// crossing two swaps them back
+    #[test]
+    fn checker_parity() {
+        let checker = Checker::new(0.5, Color::new(1.0, 1.0, 1.0), Color::new(0.0, 0.0, 0.0));
+        let is_even = |x, y, z| checker.value(0.3, 0.7, Vec3::new(x, y, z)).r == 1.0;
+        assert!(is_even(0.1, 0.1, 0.1));
+        assert!(!is_even(0.6, 0.1, 0.1) && !is_even(0.1, 0.6, 0.1) && !is_even(0.1, 0.1, 0.6));
+        assert!(!is_even(-0.1, 0.1, 0.1));
+        assert!(is_even(0.6, 0.6, 0.1) && is_even(-0.4, 0.1, 0.9));
+        // the surface coordinates don't matter
+        assert_eq!(checker.value(0.0, 0.0, Vec3::new(0.6, 0.1, 0.1)).r, checker.value(0.9, 0.9, Vec3::new(0.6, 0.1, 0.1)).r);
+    }
+
+    /// Every noise texture builds the same lattice, and each kind stays in
+    /// its range
+    #[test]
+    fn noise() {
+        let points: Vec<Vec3> = (0..2000).map(|i| Vec3::new(i as f64 * 0.173, (i % 37) as f64 * 0.61 - 9.0, (i % 11) as f64 * -1.3)).collect();
+        for (kind, max) in [(NoiseKind::Perlin, 1.0), (NoiseKind::Turbulence, 2.0), (NoiseKind::Marble, 1.0)] {
+            let (a, b) = (NoiseTexture::new(kind, 3.0, Color::new(1.0, 1.0, 1.0)), NoiseTexture::new(kind, 3.0, Color::new(1.0, 1.0, 1.0)));
+            let values: Vec<f64> = points.iter().map(|&p| a.value(0.0, 0.0, p).r).collect();
+            assert!(points.iter().zip(&values).all(|(&p, &v)| b.value(0.0, 0.0, p).r == v), "{kind:?}");
+            assert!(values.iter().all(|v| (0.0..=max).contains(v)), "{kind:?}");
+            let (lo, hi) = values.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), &v| (lo.min(v), hi.max(v)));
+            assert!(hi - lo > 0.3 * max, "{kind:?} only varies from {lo} to {hi}");
+        }
+        // gradient noise is zero on the lattice
+        let perlin = Perlin::new();
+        assert!([Vec3::zero(), Vec3::new(3.0, -7.0, 12.0)].iter().all(|&p| perlin.noise(p) == 0.0));
+    }
+}
@@ -0,0 +1,219 @@
+use crate::color::Color;
+
+/// Maps scene linear radiance into the displayable [0, 1] range
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum ToneMapper {
+    /// No tone mapping, anything above 1 is clipped
+    #[default]
+    Clamp,
+    /// `L / (1 + L)` on luminance
+    Reinhard,
+    /// Reinhard that maps luminance `white` (and above) to pure white
+    ExtendedReinhard { white: f64 },
+    /// Stephen Hill's fit of the ACES reference rendering and sRGB output transforms
+    Aces,
+    /// John Hable's Uncharted 2 filmic curve
+    Hable,
+    /// Log encoded sigmoid in the AgX style, desaturates highlights instead of skewing hue
+    Agx,
+}
+
+impl ToneMapper {
+    /// Returns linear display values, ready for the sRGB transfer function
+    pub fn apply(&self, c: Color) -> Color {
+        match self {
+            ToneMapper::Clamp => c,
+            ToneMapper::Reinhard => scale_luminance(c, |l| l / (1.0 + l)),
+            ToneMapper::ExtendedReinhard { white } => {
+                let white_squared = white * white;
+                scale_luminance(c, |l| l * (1.0 + l / white_squared) / (1.0 + l))
+            },
+            ToneMapper::Aces => aces(c),
+            ToneMapper::Hable => hable(c),
+            ToneMapper::Agx => agx(c),
+        }
+    }
+}
+
+/// Exposure, tone mapping and the sRGB transfer function, applied to LDR outputs
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DisplayTransform {
+    /// Exposure adjustment in stops, each stop doubles the brightness
+    pub exposure: f64,
+    pub tone_mapper: ToneMapper,
+}
+
+impl DisplayTransform {
+    /// Converts linear radiance to sRGB encoded values in [0, 1]
+    pub fn apply(&self, c: Color) -> Color {
+        let exposed = c * 2f64.powf(self.exposure);
+        let mapped = self.tone_mapper.apply(exposed);
+        Color::new(srgb_oetf(mapped.r), srgb_oetf(mapped.g), srgb_oetf(mapped.b))
+    }
+}
+
+/// The piecewise sRGB opto-electronic transfer function, clamped to [0, 1]
+pub fn srgb_oetf(linear: f64) -> f64 {
+    let linear = linear.clamp(0.0, 1.0);
+    if linear <= 0.0031308 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Applies `f` to the luminance and scales the color to match, preserving hue
+fn scale_luminance(c: Color, f: impl Fn(f64) -> f64) -> Color {
+    let l = c.luminance();
+    if l <= 0.0 {
+        return Color::new(0.0, 0.0, 0.0);
+    }
+    c * (f(l) / l)
+}
+
+fn mat_mul(m: [[f64; 3]; 3], c: Color) -> Color {
+    Color::new(
+        m[0][0] * c.r + m[0][1] * c.g + m[0][2] * c.b,
+        m[1][0] * c.r + m[1][1] * c.g + m[1][2] * c.b,
+        m[2][0] * c.r + m[2][1] * c.g + m[2][2] * c.b,
+    )
+}
+
+fn map_channels(c: Color, f: impl Fn(f64) -> f64) -> Color {
+    Color::new(f(c.r), f(c.g), f(c.b))
+}
+
+fn aces(c: Color) -> Color {
+    // sRGB => XYZ => D65_2_D60 => AP1 => RRT_SAT
+    const INPUT: [[f64; 3]; 3] = [
+        [0.59719, 0.35458, 0.04823],
+        [0.07600, 0.90834, 0.01566],
+        [0.02840, 0.13383, 0.83777],
+    ];
+    // ODT_SAT => XYZ => D60_2_D65 => sRGB
+    const OUTPUT: [[f64; 3]; 3] = [
+        [1.60475, -0.53108, -0.07367],
+        [-0.10208, 1.10813, -0.00605],
+        [-0.00327, -0.07276, 1.07602],
+    ];
+
+    let v = mat_mul(INPUT, c);
+    let v = map_channels(v, |x| (x * (x + 0.0245786) - 0.000090537) / (x * (0.983729 * x + 0.4329510) + 0.238081));
+    map_channels(mat_mul(OUTPUT, v), |x| x.clamp(0.0, 1.0))
+}
+
+fn hable(c: Color) -> Color {
+    const A: f64 = 0.15; // shoulder strength
+    const B: f64 = 0.50; // linear strength
+    const C: f64 = 0.10; // linear angle
+    const D: f64 = 0.20; // toe strength
+    const E: f64 = 0.02; // toe numerator
+    const F: f64 = 0.30; // toe denominator
+    const WHITE: f64 = 11.2;
+    const EXPOSURE_BIAS: f64 = 2.0;
+
+    let curve = |x: f64| ((x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F)) - E / F;
+    let white_scale = 1.0 / curve(WHITE);
+    map_channels(c, |x| curve(x.max(0.0) * EXPOSURE_BIAS) * white_scale)
+}
+
+fn agx(c: Color) -> Color {
+    // pulls colors towards the achromatic axis so bright saturated values desaturate smoothly
+    const INSET: [[f64; 3]; 3] = [
+        [0.842479062253094, 0.0784335999999992, 0.0792237451477643],
+        [0.0423282422610123, 0.878468636469772, 0.0791661274605434],
+        [0.0423756549057051, 0.0784336, 0.879142973793104],
+    ];
+    const OUTSET: [[f64; 3]; 3] = [
+        [1.19687900512017, -0.0980208811401368, -0.0990297440797205],
+        [-0.0528968517574562, 1.15190312990417, -0.0989611768448433],
+        [-0.0529716355144438, -0.0980434501171241, 1.15107367264116],
+    ];
+    const MIN_EV: f64 = -12.47393;
+    const MAX_EV: f64 = 4.026069;
+
+    let v = mat_mul(INSET, c);
+    let v = map_channels(v, |x| {
+        let log = x.max(1e-10).log2().clamp(MIN_EV, MAX_EV);
+        let x = (log - MIN_EV) / (MAX_EV - MIN_EV);
+        // polynomial fit of the default AgX contrast sigmoid
+        let x2 = x * x;
+        let x4 = x2 * x2;
+        15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x - 0.00232
+    });
+    // the sigmoid output is display encoded with a 2.2 power, return to linear
+    map_channels(mat_mul(OUTSET, v), |x| x.max(0.0).powf(2.2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TONE_MAPPERS: [ToneMapper; 6] = [
+        ToneMapper::Clamp,
+        ToneMapper::Reinhard,
+        ToneMapper::ExtendedReinhard { white: 4.0 },
+        ToneMapper::Aces,
+        ToneMapper::Hable,
+        ToneMapper::Agx,
+    ];
+
+    /// Black stays black and brighter input never gets darker, for greys
+    /// and for a saturated color. AgX is left out of the latter as it
+    /// desaturates by taking from the channels that have reached white.
+    #[test]
+    fn black_and_monotonic() {
+        for tone_mapper in TONE_MAPPERS {
+            let black = tone_mapper.apply(Color::new(0.0, 0.0, 0.0));
+            assert!(black.r.abs() < 1e-9 && black.g.abs() < 1e-9 && black.b.abs() < 1e-9, "{tone_mapper:?} {black:?}");
+
+            for tint in [Color::new(1.0, 1.0, 1.0), Color::new(1.0, 0.3, 0.05)] {
+                if tint.b < 1.0 && tone_mapper == ToneMapper::Agx {
+                    continue;
+                }
+                let mut previous = black;
+                for i in 0..=400 {
+                    // from 2^-12 to 2^8
+                    let c = tone_mapper.apply(tint * 2f64.powf(i as f64 / 20.0 - 12.0));
+                    for (a, b) in [(previous.r, c.r), (previous.g, c.g), (previous.b, c.b)] {
+                        assert!(b >= a - 1e-12, "{tone_mapper:?} at step {i}: {previous:?} then {c:?}");
+                    }
+                    previous = c;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn extended_reinhard_white_point() {
+        for white in [1.0, 4.0, 11.2] {
+            let c = ToneMapper::ExtendedReinhard { white }.apply(Color::new(white, white, white));
+            assert!((c.luminance() - 1.0).abs() < 1e-12, "{white} {c:?}");
+        }
+        // plain Reinhard only gets there at infinity
+        assert!((ToneMapper::Reinhard.apply(Color::new(1.0, 1.0, 1.0)).luminance() - 0.5).abs() < 1e-12);
+    }
+
+    /// The linear and power segments meet at the breakpoint, and the ends of
+    /// the range map to themselves
+    #[test]
+    fn srgb_transfer_function() {
+        let knee = 0.0031308;
+        // the standard's rounded constants leave a gap of about 3e-8
+        assert!((srgb_oetf(knee) - srgb_oetf(knee + 1e-12)).abs() < 1e-7);
+        assert!((knee * 12.92 - (1.055 * knee.powf(1.0 / 2.4) - 0.055)).abs() < 1e-7);
+        assert_eq!(srgb_oetf(0.0), 0.0);
+        assert!((srgb_oetf(1.0) - 1.0).abs() < 1e-12);
+        assert_eq!(srgb_oetf(-1.0), 0.0);
+        assert!((srgb_oetf(5.0) - 1.0).abs() < 1e-12);
+        assert!((srgb_oetf(0.18) - 0.4613561).abs() < 1e-6);
+    }
+
+    #[test]
+    fn eight_bit_rounding() {
+        assert_eq!(Color::new(0.0, 1.0, 0.5).to_rgb8(), [0, 255, 128]);
+        // halfway between two levels rounds up, just below rounds down
+        assert_eq!(Color::new(0.5 / 255.0, 1.5 / 255.0, 1.49 / 255.0).to_rgb8(), [1, 2, 1]);
+        assert_eq!(Color::new(-0.5, 2.0, 254.6 / 255.0).to_rgb8(), [0, 255, 255]);
+    }
+}
@@ -0,0 +1,198 @@
+use std::f64::consts::PI;
+
+use crate::{aabb::Aabb, hittable::{HitRecord, Hittable}, interval::Interval, material::Material, quadric::{around, solve_quadratic, AxisFrame, Nearest}, ray::Ray, vec3::Vec3};
+
+/// Value of the polynomial with `coefficients` from the highest power down, and its slope
+fn evaluate(coefficients: &[f64], x: f64) -> (f64, f64) {
+    coefficients.iter().fold((0.0, 0.0), |(value, slope), &c| (value * x + c, slope * x + value))
+}
+
+/// The root of a polynomial that changes sign between `lo` and `hi`, Newton
+/// steps kept inside the shrinking bracket
+fn refine(coefficients: &[f64], mut lo: f64, mut hi: f64) -> f64 {
+    let lo_negative = evaluate(coefficients, lo).0 < 0.0;
+    let mut x = 0.5 * (lo + hi);
+    for _ in 0..100 {
+        let (value, slope) = evaluate(coefficients, x);
+        if value == 0.0 {
+            return x;
+        }
+        if (value < 0.0) == lo_negative {lo = x} else {hi = x}
+        let newton = x - value / slope;
+        let next = if newton > lo && newton < hi {newton} else {0.5 * (lo + hi)};
+        if (next - x).abs() <= 1e-14 * x.abs().max(1.0) {
+            return next;
+        }
+        x = next;
+    }
+    x
+}
+
+/// Real roots between `lo` and `hi` of the polynomial with `coefficients`
+/// from the highest power down, degree 4 at most, in increasing order.
+///
+/// The roots of the derivative split the range into pieces where the
+/// polynomial is monotonic, each holds a root if the signs at its ends differ.
+/// Returns how many were written to `roots`.
+fn solve_polynomial(coefficients: &[f64], lo: f64, hi: f64, roots: &mut [f64; 4]) -> usize {
+    let degree = coefficients.len() - 1;
+    if degree <= 2 {
+        let (a, b, c) = match coefficients {
+            [a, b, c] => (*a, *b, *c),
+            [b, c] => (0.0, *b, *c),
+            _ => return 0,
+        };
+        let Some((t0, t1)) = solve_quadratic(a, b, c) else {
+            return 0;
+        };
+        let mut count = 0;
+        for (i, t) in [t0, t1].into_iter().enumerate() {
+            if (lo..=hi).contains(&t) && (i == 0 || t != t0) {
+                roots[count] = t;
+                count += 1;
+            }
+        }
+        return count;
+    }
+
+    let mut derivative = [0.0; 4];
+    for (i, c) in coefficients[..degree].iter().enumerate() {
+        derivative[i] = c * (degree - i) as f64;
+    }
+    let mut critical = [0.0; 4];
+    let critical_count = solve_polynomial(&derivative[..degree], lo, hi, &mut critical);
+
+    let mut count = 0;
+    let mut start = lo;
+    for &end in critical[..critical_count].iter().chain([hi].iter()) {
+        let (a, b) = (evaluate(coefficients, start).0, evaluate(coefficients, end).0);
+        if a == 0.0 && count == 0 {
+            roots[count] = start;
+            count += 1;
+        }
+        if a * b < 0.0 || (b == 0.0 && a != 0.0) {
+            roots[count] = if b == 0.0 {end} else {refine(coefficients, start, end)};
+            count += 1;
+        }
+        start = end;
+    }
+    count
+}
+
+/// A ring around an axis. `u` goes around the axis and `v` around the tube,
+/// starting from its outer edge.
+#[derive(Clone)]
+pub struct Torus {
+    /// Distance from the center to the middle of the tube
+    pub major_radius: f64,
+    /// Radius of the tube
+    pub minor_radius: f64,
+    pub mat: Material,
+    frame: AxisFrame,
+}
+
+impl Torus {
+    /// The torus lying in the plane through `center` at right angles to `axis`
+    pub fn new(center: Vec3, axis: Vec3, major_radius: f64, minor_radius: f64, matterial: Material) -> Hittable {
+        Hittable::Torus(Box::new(Self { major_radius, minor_radius, mat: matterial, frame: AxisFrame::new(center, axis) }))
+    }
+}
+
+impl Torus {
+    pub fn bounding_box(&self) -> Aabb {
+        let (big, r) = (self.major_radius + self.minor_radius, self.minor_radius);
+        self.frame.bounding_box(Vec3::new(-big, -big, -r), Vec3::new(big, big, r))
+    }
+    pub fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let (o, d) = self.frame.local_ray(ray);
+        let (major, minor) = (self.major_radius, self.minor_radius);
+        let speed = d.len();
+        let d = d / speed;
+
+        // solving from the point nearest the center keeps the quartic well
+        // conditioned for distant rays, and the bounding sphere limits where its roots can be
+        let shift = -o.dot(d);
+        let o = o + d * shift;
+        let bound = major + minor;
+        let half_chord_squared = bound * bound - o.len_squared();
+        if half_chord_squared < 0.0 {
+            return None;
+        }
+        let half_chord = half_chord_squared.sqrt();
+        let lo = (-half_chord).max(ray_t.min * speed - shift);
+        let hi = half_chord.min(ray_t.max * speed - shift);
+        if lo > hi {
+            return None;
+        }
+
+        // (|p|^2 + R^2 - r^2)^2 = 4 R^2 (x^2 + y^2) along p = o + s d
+        let (f, e) = (o.dot(d), o.len_squared() + major * major - minor * minor);
+        let r4 = 4.0 * major * major;
+        let coefficients = [
+            1.0,
+            4.0 * f,
+            4.0 * f * f + 2.0 * e - r4 * (d.x * d.x + d.y * d.y),
+            4.0 * f * e - 2.0 * r4 * (o.x * d.x + o.y * d.y),
+            e * e - r4 * (o.x * o.x + o.y * o.y),
+        ];
+        let mut roots = [0.0; 4];
+        let count = solve_polynomial(&coefficients, lo, hi, &mut roots);
+
+        let mut nearest = Nearest::new(ray_t);
+        for &s in &roots[..count] {
+            let p = o + d * s;
+            let ring = (p.x * p.x + p.y * p.y).sqrt();
+            // the tube's center line is the nearest point of the ring of radius R
+            let spine = if ring > 0.0 {Vec3::new(p.x, p.y, 0.0) * (major / ring)} else {Vec3::zero()};
+            let v = (p.z.atan2(ring - major) + PI) / (2.0 * PI);
+            nearest.offer((s + shift) / speed, p - spine, (around(p), v));
+        }
+        Some(self.frame.record(ray, nearest.hit?, &self.mat))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{hit, material};
+
+    #[test]
+    fn polynomial_roots() {
+        // (x + 2)(x - 1)(x - 1.5)(x - 4), one root outside the range
+        let coefficients = [1.0, -4.5, -1.5, 17.0, -12.0];
+        let mut roots = [0.0; 4];
+        let count = solve_polynomial(&coefficients, -1.0, 10.0, &mut roots);
+        assert_eq!(count, 3);
+        for (root, expected) in roots.iter().zip([1.0, 1.5, 4.0]) {
+            assert!((root - expected).abs() < 1e-12, "{roots:?}");
+        }
+        // x^4 + 1 has no real roots
+        assert_eq!(solve_polynomial(&[1.0, 0.0, 0.0, 0.0, 1.0], -10.0, 10.0, &mut roots), 0);
+    }
+
+    /// Rays through the hole, across the tube, from inside the tube and
+    /// grazing its outer edge
+    #[test]
+    fn torus() {
+        let torus = Torus::new(Vec3::new(0.0, 0.0, -10.0), Vec3::new(0.0, 1.0, 0.0), 2.0, 0.5, material());
+
+        // straight down the axis through the hole
+        assert!(hit(&torus, Vec3::new(0.0, 5.0, -10.0), Vec3::new(0.0, -1.0, 0.0)).is_none());
+        // along the plane of the ring from far away, the outer edge comes first
+        let rec = hit(&torus, Vec3::new(0.0, 0.0, 100.0), Vec3::new(0.0, 0.0, -2.0)).unwrap();
+        assert!((rec.t - (110.0 - 2.5) / 2.0).abs() < 1e-9, "{}", rec.t);
+        assert!((rec.normal - Vec3::new(0.0, 0.0, 1.0)).len() < 1e-9 && rec.front_face);
+        // down onto the top of the tube
+        let rec = hit(&torus, Vec3::new(2.0, 3.0, -10.0), Vec3::new(0.0, -1.0, 0.0)).unwrap();
+        assert!((rec.t - 2.5).abs() < 1e-9 && (rec.normal - Vec3::new(0.0, 1.0, 0.0)).len() < 1e-9);
+        assert!((rec.v - 0.75).abs() < 1e-9);
+
+        // from the middle of the tube outwards
+        let rec = hit(&torus, Vec3::new(-2.0, 0.0, -10.0), Vec3::new(1.0, 0.0, 0.0)).unwrap();
+        assert!((rec.t - 0.5).abs() < 1e-9 && !rec.front_face);
+
+        // tangent to the top of the tube, just below and just above it
+        assert!(hit(&torus, Vec3::new(-5.0, 0.5 - 1e-6, -8.0), Vec3::new(1.0, 0.0, 0.0)).is_some());
+        assert!(hit(&torus, Vec3::new(-5.0, 0.5 + 1e-6, -8.0), Vec3::new(1.0, 0.0, 0.0)).is_none());
+    }
+}
@@ -0,0 +1,214 @@
+use std::ops::Mul;
+
+use crate::{aabb::Aabb, utils::degrees_to_radians, vec3::{cross, Vec3}};
+
+/// A 4x4 matrix for affine transforms, row major, acting on column vectors
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mat4 {
+    pub m: [[f64; 4]; 4],
+}
+
+impl Mat4 {
+    pub const fn identity() -> Self {
+        Self { m: [[1.0, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0], [0.0, 0.0, 0.0, 1.0]] }
+    }
+    pub fn translation(offset: Vec3) -> Self {
+        let mut matrix = Self::identity();
+        matrix.m[0][3] = offset.x;
+        matrix.m[1][3] = offset.y;
+        matrix.m[2][3] = offset.z;
+        matrix
+    }
+    /// Scales each axis by the matching component of `factors`
+    pub fn scale(factors: Vec3) -> Self {
+        let mut matrix = Self::identity();
+        matrix.m[0][0] = factors.x;
+        matrix.m[1][1] = factors.y;
+        matrix.m[2][2] = factors.z;
+        matrix
+    }
+    /// Counterclockwise rotation by `degrees` around `axis`, looking against the axis
+    pub fn rotation(axis: Vec3, degrees: f64) -> Self {
+        let a = axis.unit();
+        let (sin, cos) = degrees_to_radians(degrees).sin_cos();
+        let t = 1.0 - cos;
+        Self { m: [
+            [t * a.x * a.x + cos, t * a.x * a.y - sin * a.z, t * a.x * a.z + sin * a.y, 0.0],
+            [t * a.x * a.y + sin * a.z, t * a.y * a.y + cos, t * a.y * a.z - sin * a.x, 0.0],
+            [t * a.x * a.z - sin * a.y, t * a.y * a.z + sin * a.x, t * a.z * a.z + cos, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ] }
+    }
+    pub fn transpose(&self) -> Self {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.m[j][i];
+            }
+        }
+        Self { m }
+    }
+    /// `None` if the matrix is singular, like a scale by zero
+    pub fn inverse(&self) -> Option<Self> {
+        // Gauss-Jordan elimination with partial pivoting
+        let mut a = self.m;
+        let mut inv = Self::identity().m;
+        for column in 0..4 {
+            let pivot = (column..4).max_by(|&i, &j| a[i][column].abs().total_cmp(&a[j][column].abs()))?;
+            if a[pivot][column].abs() < 1e-12 {
+                return None;
+            }
+            a.swap(column, pivot);
+            inv.swap(column, pivot);
+
+            let scale = 1.0 / a[column][column];
+            for j in 0..4 {
+                a[column][j] *= scale;
+                inv[column][j] *= scale;
+            }
+            for row in 0..4 {
+                let factor = a[row][column];
+                if row == column || factor == 0.0 {
+                    continue;
+                }
+                for j in 0..4 {
+                    a[row][j] -= factor * a[column][j];
+                    inv[row][j] -= factor * inv[column][j];
+                }
+            }
+        }
+        Some(Self { m: inv })
+    }
+    /// Determinant of the upper left 3x3 block, how much the transform scales volumes
+    pub fn determinant3(&self) -> f64 {
+        let row = |i: usize| Vec3::new(self.m[i][0], self.m[i][1], self.m[i][2]);
+        row(0).dot(cross(row(1), row(2)))
+    }
+    pub fn transform_point(&self, p: Vec3) -> Vec3 {
+        let m = &self.m;
+        Vec3::new(
+            m[0][0] * p.x + m[0][1] * p.y + m[0][2] * p.z + m[0][3],
+            m[1][0] * p.x + m[1][1] * p.y + m[1][2] * p.z + m[1][3],
+            m[2][0] * p.x + m[2][1] * p.y + m[2][2] * p.z + m[2][3],
+        )
+    }
+    /// Ignores the translation
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        let m = &self.m;
+        Vec3::new(
+            m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+            m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+            m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+        )
+    }
+    /// The box around all eight transformed corners of `bbox`
+    pub fn transform_box(&self, bbox: Aabb) -> Aabb {
+        if bbox.is_empty() {
+            return bbox;
+        }
+        if !bbox.is_finite() {
+            // infinite corners would turn into NaN
+            return Aabb::everything();
+        }
+        bbox.corners().iter().fold(Aabb::empty(), |result, &corner| result.grow(self.transform_point(corner)))
+    }
+}
+
+impl Default for Mat4 {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Mat4;
+    /// `a * b` applies `b` first
+    fn mul(self, other: Mat4) -> Mat4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.m[i][k] * other.m[k][j]).sum();
+            }
+        }
+        Mat4 { m }
+    }
+}
+
+/// A transform kept as scale, rotation and translation, which interpolate
+/// far better than the matrix they make up
+#[derive(Debug, Clone, Copy)]
+pub struct Trs {
+    pub translate: Vec3,
+    /// Degrees around x, then y, then z
+    pub rotate: Vec3,
+    pub scale: Vec3,
+}
+
+impl Default for Trs {
+    fn default() -> Self {
+        Self { translate: Vec3::zero(), rotate: Vec3::zero(), scale: Vec3::new(1.0, 1.0, 1.0) }
+    }
+}
+
+impl Trs {
+    /// Scales, then rotates around x, y and z in that order, then translates
+    pub fn matrix(&self) -> Mat4 {
+        Mat4::translation(self.translate)
+            * Mat4::rotation(Vec3::new(0.0, 0.0, 1.0), self.rotate.z)
+            * Mat4::rotation(Vec3::new(0.0, 1.0, 0.0), self.rotate.y)
+            * Mat4::rotation(Vec3::new(1.0, 0.0, 0.0), self.rotate.x)
+            * Mat4::scale(self.scale)
+    }
+    /// Component-wise blend from `self` at 0 to `other` at 1
+    pub fn lerp(&self, other: &Trs, t: f64) -> Trs {
+        let mix = |a: Vec3, b: Vec3| a * (1.0 - t) + b * t;
+        Trs { translate: mix(self.translate, other.translate), rotate: mix(self.rotate, other.rotate), scale: mix(self.scale, other.scale) }
+    }
+}
+
+/// The transform an animated instance has at `time`
+#[derive(Debug, Clone, Copy)]
+pub struct Keyframe {
+    pub time: f64,
+    pub transform: Trs,
+}
+
+/// Interpolates linearly between the keyframes around `time`, holding the
+/// first and last ones before and after. `keyframes` must be sorted by time and not empty.
+pub fn interpolate(keyframes: &[Keyframe], time: f64) -> Trs {
+    let next = keyframes.partition_point(|k| k.time <= time);
+    if next == 0 {
+        return keyframes[0].transform;
+    }
+    if next == keyframes.len() {
+        return keyframes[next - 1].transform;
+    }
+    let (a, b) = (&keyframes[next - 1], &keyframes[next]);
+    a.transform.lerp(&b.transform, (time - a.time) / (b.time - a.time))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).len() < 1e-9, "{a:?} != {b:?}");
+    }
+
+    #[test]
+    fn compose_and_invert() {
+        let transform = Mat4::translation(Vec3::new(1.0, 2.0, 3.0))
+            * Mat4::rotation(Vec3::new(0.0, 1.0, 0.0), 90.0)
+            * Mat4::scale(Vec3::new(2.0, 1.0, 0.5));
+        // scale x to 2, turn it onto -z, then move
+        assert_close(transform.transform_point(Vec3::new(1.0, 0.0, 0.0)), Vec3::new(1.0, 2.0, 1.0));
+        assert_close(transform.transform_vector(Vec3::new(0.0, 0.0, 2.0)), Vec3::new(1.0, 0.0, 0.0));
+        assert!((transform.determinant3() - 1.0).abs() < 1e-12);
+
+        let inverse = transform.inverse().unwrap();
+        let p = Vec3::new(-0.3, 4.0, 2.5);
+        assert_close(inverse.transform_point(transform.transform_point(p)), p);
+        assert_close((transform * inverse).transform_point(p), p);
+        assert!(Mat4::scale(Vec3::new(1.0, 0.0, 1.0)).inverse().is_none());
+    }
+}
@@ -0,0 +1,171 @@
+use crate::{aabb::Aabb, hittable::{HitRecord, Hittable}, interval::Interval, material::Material, ray::Ray, vec3::{cross, dot, Vec3}};
+
+#[derive(Clone)]
+pub struct Triangle {
+    pub v0: Vec3,
+    pub v1: Vec3,
+    pub v2: Vec3,
+    pub mat: Material,
+}
+
+impl Triangle {
+    pub fn new(v0: Vec3, v1: Vec3, v2: Vec3, matterial: Material) -> Hittable {
+        Hittable::Triangle(Box::new(Self { v0, v1, v2, mat: matterial }))
+    }
+}
+
+impl Triangle {
+    pub fn bounding_box(&self) -> Aabb {
+        Aabb::from_points(self.v0, self.v1).grow(self.v2).pad(1e-4)
+    }
+    pub fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let (t, b1, b2) = intersect(ray, ray_t, self.v0, self.v1, self.v2)?;
+
+        let geometric = cross(self.v1 - self.v0, self.v2 - self.v0).unit();
+        let (normal, front_face) = face_forward(ray, geometric, geometric);
+
+        Some(HitRecord {
+            p: ray.at(t),
+            normal,
+            t,
+            front_face,
+            mat: &self.mat,
+            u: b1,
+            v: b2,
+        })
+    }
+    pub fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f64 {
+        solid_angle_pdf(origin, direction, self.v0, self.v1, self.v2)
+    }
+    pub fn sample(&self, origin: Vec3, uv: (f64, f64)) -> Vec3 {
+        sample_point(self.v0, self.v1, self.v2, uv) - origin
+    }
+}
+
+/// Uniformly distributed point on the triangle
+pub fn sample_point(v0: Vec3, v1: Vec3, v2: Vec3, (u, v): (f64, f64)) -> Vec3 {
+    let su = u.sqrt();
+    let (b1, b2) = (su * (1.0 - v), su * v);
+    v0 + (v1 - v0) * b1 + (v2 - v0) * b2
+}
+
+/// Density, per solid angle at `origin`, of reaching `direction` with `sample_point`.
+/// Zero if the ray misses the triangle.
+pub fn solid_angle_pdf(origin: Vec3, direction: Vec3, v0: Vec3, v1: Vec3, v2: Vec3) -> f64 {
+    let ray = Ray::new(origin, direction.unit());
+    let Some((t, _, _)) = intersect(&ray, Interval::new(0.001, f64::INFINITY), v0, v1, v2) else {
+        return 0.0;
+    };
+    let normal = cross(v1 - v0, v2 - v0);
+    let area = normal.len() * 0.5;
+    let cosine = ray.direction.dot(normal).abs() / normal.len();
+    if cosine <= 0.0 {
+        return 0.0;
+    }
+    t * t / (cosine * area)
+}
+
+/// Möller–Trumbore ray/triangle intersection.
+///
+/// Returns the ray parameter and the barycentric weights of `v1` and `v2`.
+/// Rays nearly parallel to the plane are rejected with a tolerance relative to
+/// the triangle size so that tiny and huge triangles behave the same.
+pub fn intersect(ray: &Ray, ray_t: Interval, v0: Vec3, v1: Vec3, v2: Vec3) -> Option<(f64, f64, f64)> {
+    let edge1 = v1 - v0;
+    let edge2 = v2 - v0;
+    let pvec = cross(ray.direction, edge2);
+    let det = dot(edge1, pvec);
+
+    let scale = edge1.len() * edge2.len() * ray.direction.len();
+    if det.abs() <= scale * 1e-12 {
+        return None;
+    }
+    let inv_det = 1.0 / det;
+
+    let tvec = ray.origin - v0;
+    let b1 = dot(tvec, pvec) * inv_det;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+
+    let qvec = cross(tvec, edge1);
+    let b2 = dot(ray.direction, qvec) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+
+    let t = dot(edge2, qvec) * inv_det;
+    if !ray_t.surrounds(t) {
+        return None;
+    }
+
+    Some((t, b1, b2))
+}
+
+/// Flips `shading` to the side of the surface the ray came from, using the
+/// geometric normal to decide which side that is
+pub fn face_forward(ray: &Ray, geometric: Vec3, shading: Vec3) -> (Vec3, bool) {
+    let front_face = ray.direction.dot(geometric) < 0.0;
+    let outward = if shading.dot(geometric) < 0.0 {-shading} else {shading};
+    if front_face {(outward, true)} else {(-outward, false)}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{hit, material};
+
+    /// The right triangle with its right angle at (0, 0, -2), facing +z
+    fn triangle() -> Hittable {
+        Triangle::new(Vec3::new(0.0, 0.0, -2.0), Vec3::new(1.0, 0.0, -2.0), Vec3::new(0.0, 1.0, -2.0), material())
+    }
+
+    /// A ray through the inside reports its distance, where it crossed as
+    /// barycentric weights of `v1` and `v2`, and the face it came from
+    #[test]
+    fn hits() {
+        let triangle = triangle();
+        let rec = hit(&triangle, Vec3::new(0.25, 0.5, 0.0), Vec3::new(0.0, 0.0, -1.0)).unwrap();
+        assert!((rec.t - 2.0).abs() < 1e-12 && (rec.p - Vec3::new(0.25, 0.5, -2.0)).len() < 1e-12);
+        assert!((rec.u - 0.25).abs() < 1e-12 && (rec.v - 0.5).abs() < 1e-12);
+        assert!(rec.front_face && (rec.normal - Vec3::new(0.0, 0.0, 1.0)).len() < 1e-12);
+
+        // the same point from behind, the normal turns to face the ray
+        let rec = hit(&triangle, Vec3::new(0.25, 0.5, -4.0), Vec3::new(0.0, 0.0, 1.0)).unwrap();
+        assert!((rec.t - 2.0).abs() < 1e-12 && (rec.u - 0.25).abs() < 1e-12 && (rec.v - 0.5).abs() < 1e-12);
+        assert!(!rec.front_face && (rec.normal - Vec3::new(0.0, 0.0, -1.0)).len() < 1e-12);
+
+        // the triangle behind the ray or past the end of the interval
+        assert!(hit(&triangle, Vec3::new(0.25, 0.5, 0.0), Vec3::new(0.0, 0.0, 1.0)).is_none());
+        assert!(triangle.hit(&Ray::new(Vec3::new(0.25, 0.5, 0.0), Vec3::new(0.0, 0.0, -1.0)), Interval::new(0.001, 1.5)).is_none());
+    }
+
+    /// Edges and vertices belong to the triangle, points just outside don't
+    #[test]
+    fn edges_and_vertices() {
+        let triangle = triangle();
+        let down = Vec3::new(0.0, 0.0, -1.0);
+        for (x, y, u, v) in [(0.5, 0.0, 0.5, 0.0), (0.0, 0.5, 0.0, 0.5), (0.5, 0.5, 0.5, 0.5), (0.0, 0.0, 0.0, 0.0), (1.0, 0.0, 1.0, 0.0), (0.0, 1.0, 0.0, 1.0)] {
+            let rec = hit(&triangle, Vec3::new(x, y, 0.0), down).unwrap_or_else(|| panic!("missed ({x}, {y})"));
+            assert!((rec.u - u).abs() < 1e-12 && (rec.v - v).abs() < 1e-12, "({x}, {y})");
+        }
+        for (x, y) in [(0.5, -1e-9), (-1e-9, 0.5), (0.5 + 1e-9, 0.5), (1.0 + 1e-9, 0.0)] {
+            assert!(hit(&triangle, Vec3::new(x, y, 0.0), down).is_none(), "hit ({x}, {y})");
+        }
+    }
+
+    /// Rays parallel to the plane miss, whether in it or beside it, and so
+    /// do rays close enough to parallel for the determinant to be noise
+    #[test]
+    fn parallel_rays() {
+        let triangle = triangle();
+        assert!(hit(&triangle, Vec3::new(-1.0, 0.25, -2.0), Vec3::new(1.0, 0.0, 0.0)).is_none());
+        assert!(hit(&triangle, Vec3::new(-1.0, 0.25, -1.0), Vec3::new(1.0, 0.0, 0.0)).is_none());
+        assert!(hit(&triangle, Vec3::new(-1.0, 0.25, -2.0), Vec3::new(1.0, 0.0, 1e-14)).is_none());
+
+        // the size of the triangle doesn't change what counts as parallel
+        let tiny = Triangle::new(Vec3::new(0.0, 0.0, -2.0), Vec3::new(1e-6, 0.0, -2.0), Vec3::new(0.0, 1e-6, -2.0), material());
+        let rec = hit(&tiny, Vec3::new(2.5e-7, 2.5e-7, 0.0), Vec3::new(0.0, 0.0, -1.0)).unwrap();
+        assert!((rec.t - 2.0).abs() < 1e-12);
+    }
+}
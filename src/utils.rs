@@ -1,15 +1,68 @@
 use std::{f64::consts::PI, sync::{mpsc, Arc, Mutex}, thread::{self, JoinHandle}};
 
-use rand::random;
+use rand::{Rng, RngCore};
 
-use crate::vec3::Vec3;
+pub fn _random_range(rng: &mut Pcg32, min: f64, max: f64) -> f64 {
+    (max-min)*rng.gen::<f64>() + min
+}
+
+/// PCG32 (XSH RR) random number generator.
+///
+/// Small, fast and its output is fixed by this code alone, so renders stay
+/// reproducible whatever the `rand` version. Each pixel gets its own
+/// generator (see `Pcg32::for_pixel`) which makes the image independent of
+/// how pixels are spread across threads. `Sampler` builds on it.
+#[derive(Debug, Clone)]
+pub struct Pcg32 {
+    state: u64,
+    increment: u64,
+}
+
+impl Pcg32 {
+    const MULTIPLIER: u64 = 6364136223846793005;
+
+    pub fn new(seed: u64, stream: u64) -> Self {
+        let mut rng = Self { state: 0, increment: (stream << 1) | 1 };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+        rng
+    }
+
+    /// The generator for one pixel of a render seeded with `seed`
+    pub fn for_pixel(seed: u64, pixel: u64) -> Self {
+        // neighbouring streams of the same seed are correlated, so scramble the seed too
+        Self::new(mix_bits(seed ^ mix_bits(pixel)), pixel)
+    }
+}
 
-pub fn _random_range(min: f64, max: f64) -> f64 {
-    (max-min)*random::<f64>() + min
+impl RngCore for Pcg32 {
+    fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old.wrapping_mul(Self::MULTIPLIER).wrapping_add(self.increment);
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        let rotation = (old >> 59) as u32;
+        xorshifted.rotate_right(rotation)
+    }
+    fn next_u64(&mut self) -> u64 {
+        ((self.next_u32() as u64) << 32) | self.next_u32() as u64
+    }
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(4) {
+            chunk.copy_from_slice(&self.next_u32().to_le_bytes()[..chunk.len()]);
+        }
+    }
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
 }
 
-pub fn sample_square() -> Vec3 {
-    Vec3::new(random::<f64>()-0.5, random::<f64>()-0.5, 0.0)
+/// SplitMix64 finaliser, spreads every input bit over the whole output
+pub fn mix_bits(mut v: u64) -> u64 {
+    v = (v ^ (v >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    v = (v ^ (v >> 27)).wrapping_mul(0x94d049bb133111eb);
+    v ^ (v >> 31)
 }
 
 pub fn degrees_to_radians(x: f64) -> f64 {
@@ -1,6 +1,8 @@
-use std::ops::{Add, Div, Mul, Neg, Sub};
+use std::ops::{Add, Div, Index, Mul, Neg, Sub};
 
-use rand::random;
+use rand::Rng;
+
+use crate::utils::Pcg32;
 
 #[derive(Clone, Copy, Debug)]
 #[derive(Default)]
@@ -31,16 +33,17 @@ impl Vec3 {
         (self.y * other.y) +
         (self.z * other.z)
     }
-    pub fn random_vector() -> Self {
+    /// Uniformly distributed inside the unit sphere
+    pub fn random_vector(rng: &mut Pcg32) -> Self {
         loop {
-            let v = Vec3::new(random::<f64>()-0.5, random::<f64>()-0.5, random::<f64>()-0.5)*2.0;
+            let v = Vec3::new(rng.gen::<f64>()-0.5, rng.gen::<f64>()-0.5, rng.gen::<f64>()-0.5)*2.0;
             if v.len_squared() < 1.0 {
                 return v;
             }
         }
     }
-    pub fn _random_on_hemisphere(normal: Vec3) -> Vec3 {
-        let v = Self::random_vector();
+    pub fn _random_on_hemisphere(rng: &mut Pcg32, normal: Vec3) -> Vec3 {
+        let v = Self::random_vector(rng);
         if normal.dot(v) > 0.0 {v} else {-v}
     }
     pub fn near_zero(&self) -> bool {
@@ -50,6 +53,14 @@ impl Vec3 {
     pub fn reflect(&self, normal: Vec3) -> Vec3 {
         *self - normal*self.dot(normal)*2.0
     }
+    /// Component-wise minimum
+    pub fn min(&self, other: Vec3) -> Vec3 {
+        Vec3::new(self.x.min(other.x), self.y.min(other.y), self.z.min(other.z))
+    }
+    /// Component-wise maximum
+    pub fn max(&self, other: Vec3) -> Vec3 {
+        Vec3::new(self.x.max(other.x), self.y.max(other.y), self.z.max(other.z))
+    }
     pub fn refract(&self, normal: Vec3, ri: f64) -> Vec3 {
         let cos_theta = (-*self).dot(normal).min(1.0);
         let perp = (*self + normal*cos_theta) * ri;
@@ -72,6 +83,19 @@ pub fn cross(a: Vec3, b: Vec3) -> Vec3 {
     )
 }
 
+impl Index<usize> for Vec3 {
+    type Output = f64;
+
+    fn index(&self, axis: usize) -> &Self::Output {
+        match axis {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("Vec3 axis out of range: {axis}"),
+        }
+    }
+}
+
 impl Add for Vec3 {
     type Output = Vec3;
     